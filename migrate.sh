@@ -16,7 +16,7 @@ POSTGRES_HOST=192.168.1.5
 POSTGRES_PORT=5432
 POSTGRES_USER=postgres
 POSTGRES_DATABASE=postgres
-POSTGRES_DDL=postgres/ddl.sql
+POSTGRES_DDL=migrations/postgres/0001_baseline.sql
 
 TABLE_NAMES=(question user game answer game_user vote)
 TABLE_NAMES_WITH_AUTO_INCREMENT_IDS=(question game answer vote)
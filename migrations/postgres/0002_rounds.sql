@@ -0,0 +1,3 @@
+ALTER TABLE game ADD COLUMN round INT DEFAULT 1 NOT NULL;
+
+ALTER TABLE answer ADD COLUMN round INT NOT NULL DEFAULT 1;
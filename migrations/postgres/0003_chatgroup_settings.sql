@@ -0,0 +1,6 @@
+CREATE TABLE chatgroup_settings
+(
+    chatgroup  BIGINT PRIMARY KEY,
+    begin_role TEXT   NOT NULL DEFAULT 'host',
+    end_role   TEXT   NOT NULL DEFAULT 'host'
+);
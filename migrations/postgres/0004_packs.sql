@@ -0,0 +1,30 @@
+CREATE TABLE pack
+(
+    id                 BIGSERIAL PRIMARY KEY,
+    name               TEXT    NOT NULL,
+    enabled_by_default BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE UNIQUE INDEX ix_pack_name ON pack (name);
+
+-- Questions from before packs are all in the first one
+INSERT INTO pack (name) VALUES ('classic');
+
+CREATE TABLE pack_tag
+(
+    pack_id BIGINT NOT NULL REFERENCES pack ON DELETE CASCADE,
+    tag     TEXT   NOT NULL,
+    PRIMARY KEY (pack_id, tag)
+);
+
+ALTER TABLE question ADD COLUMN pack_id BIGINT NOT NULL DEFAULT 1 REFERENCES pack;
+
+CREATE INDEX fk_question_pack ON question (pack_id);
+
+CREATE TABLE chatgroup_pack
+(
+    chatgroup BIGINT  NOT NULL,
+    pack_id   BIGINT  NOT NULL REFERENCES pack ON DELETE CASCADE,
+    enabled   BOOLEAN NOT NULL,
+    PRIMARY KEY (chatgroup, pack_id)
+);
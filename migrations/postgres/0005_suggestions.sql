@@ -0,0 +1,15 @@
+INSERT INTO pack (name) VALUES ('suggestions');
+
+ALTER TABLE question ADD COLUMN chatgroup BIGINT NULL;
+
+CREATE TABLE suggestion
+(
+    id        BIGSERIAL PRIMARY KEY,
+    chatgroup BIGINT                   NOT NULL,
+    user_id   BIGINT                   NOT NULL REFERENCES "user",
+    text      TEXT                     NOT NULL,
+    state     TEXT                     NOT NULL DEFAULT 'pending',
+    created   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX ix_suggestion_chatgroup ON suggestion (chatgroup);
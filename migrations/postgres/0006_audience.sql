@@ -0,0 +1,3 @@
+ALTER TABLE chatgroup_settings ADD COLUMN audience BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE vote ADD COLUMN audience BOOLEAN NOT NULL DEFAULT FALSE;
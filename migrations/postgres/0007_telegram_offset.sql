@@ -0,0 +1,6 @@
+-- The next update to fetch when polling telegram instead of receiving updates by webhook
+CREATE TABLE telegram_offset
+(
+    id        INT    PRIMARY KEY,
+    update_id BIGINT NOT NULL
+);
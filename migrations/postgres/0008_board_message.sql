@@ -0,0 +1,2 @@
+-- The message edited in place as the game goes on, rather than sending a new one each time
+ALTER TABLE game ADD COLUMN board_message_id BIGINT NULL;
@@ -0,0 +1,2 @@
+-- Users who started the bot in a private chat, where they are sent their prompts to answer
+ALTER TABLE "user" ADD COLUMN private_chat BOOLEAN NOT NULL DEFAULT FALSE;
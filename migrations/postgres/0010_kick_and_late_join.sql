@@ -0,0 +1,3 @@
+-- Who can kick players out of a game, and whether people can join a game that has begun to vote
+ALTER TABLE chatgroup_settings ADD COLUMN kick_role TEXT NOT NULL DEFAULT 'host';
+ALTER TABLE chatgroup_settings ADD COLUMN late_join BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,131 @@
+CREATE TABLE pack
+(
+    id                 INTEGER PRIMARY KEY,
+    name               TEXT    NOT NULL,
+    enabled_by_default BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+CREATE UNIQUE INDEX ix_pack_name ON pack (name);
+
+INSERT INTO pack (name) VALUES ('classic'), ('suggestions');
+
+CREATE TABLE pack_tag
+(
+    pack_id INTEGER NOT NULL REFERENCES pack ON DELETE CASCADE,
+    tag     TEXT    NOT NULL,
+    PRIMARY KEY (pack_id, tag)
+);
+
+CREATE TABLE question
+(
+    id        INTEGER PRIMARY KEY,
+    text      TEXT    NOT NULL,
+    pack_id   INTEGER NOT NULL DEFAULT 1 REFERENCES pack,
+    chatgroup INTEGER NULL
+);
+
+CREATE INDEX fk_question_pack ON question (pack_id);
+
+CREATE TABLE "user"
+(
+    id         INTEGER PRIMARY KEY,
+    is_bot     BOOLEAN NOT NULL,
+    first_name TEXT    NULL,
+    last_name  TEXT    NULL,
+    username   TEXT    NULL
+);
+
+-- Timestamps are seconds since the unix epoch
+CREATE TABLE game
+(
+    id                          INTEGER PRIMARY KEY,
+    host_id                     INTEGER              NULL REFERENCES "user",
+    current_question_id         INTEGER              NULL,
+    chatgroup                   INTEGER              NOT NULL,
+    state                       TEXT                 NOT NULL,
+    gathering_votes_started     INTEGER              NULL,
+    gathering_users_started     INTEGER              NULL,
+    gathering_answers_started   INTEGER              NULL,
+    warning_state_state         TEXT                 NOT NULL,
+    warning_state_warning_value INTEGER              NOT NULL,
+    has_timer                   BOOLEAN DEFAULT TRUE NOT NULL,
+    round                       INTEGER DEFAULT 1    NOT NULL
+);
+
+CREATE INDEX fk_game_host ON game (host_id);
+CREATE INDEX fk_game_current_question ON game (current_question_id);
+
+CREATE TABLE answer
+(
+    id          INTEGER PRIMARY KEY,
+    user_id     INTEGER NULL REFERENCES "user",
+    question_id INTEGER NULL REFERENCES question,
+    game_id     INTEGER NULL REFERENCES game,
+    response    TEXT,
+    token       TEXT    NOT NULL,
+    round       INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX fk_answer_user ON answer (user_id);
+CREATE INDEX fk_answer_question ON answer (question_id);
+CREATE INDEX fk_answer_game ON answer (game_id);
+CREATE UNIQUE INDEX ix_answer_token ON answer (token);
+
+CREATE TABLE game_user
+(
+    game_id INTEGER NOT NULL REFERENCES game ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    PRIMARY KEY (game_id, user_id)
+);
+
+CREATE INDEX fk_game_user_user ON game_user (user_id);
+
+CREATE INDEX fk_game_user_game ON game_user (game_id);
+
+CREATE TABLE vote
+(
+    id          INTEGER PRIMARY KEY,
+    answer_id   INTEGER NOT NULL REFERENCES answer,
+    question_id INTEGER NOT NULL REFERENCES question,
+    user_id     INTEGER NOT NULL REFERENCES "user",
+    game_id     INTEGER NOT NULL REFERENCES game,
+    audience    BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX fk_vote_question ON vote (question_id);
+
+CREATE INDEX fk_vote_user ON vote (user_id);
+
+CREATE INDEX fk_vote_answer ON vote (answer_id);
+
+CREATE INDEX fk_vote_game ON vote (game_id);
+
+CREATE UNIQUE INDEX uq_vote ON vote(user_id, answer_id);
+
+CREATE TABLE chatgroup_settings
+(
+    chatgroup  INTEGER PRIMARY KEY,
+    begin_role TEXT    NOT NULL DEFAULT 'host',
+    end_role   TEXT    NOT NULL DEFAULT 'host',
+    audience   BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE chatgroup_pack
+(
+    chatgroup INTEGER NOT NULL,
+    pack_id   INTEGER NOT NULL REFERENCES pack ON DELETE CASCADE,
+    enabled   BOOLEAN NOT NULL,
+    PRIMARY KEY (chatgroup, pack_id)
+);
+
+CREATE TABLE suggestion
+(
+    id        INTEGER PRIMARY KEY,
+    chatgroup INTEGER NOT NULL,
+    user_id   INTEGER NOT NULL REFERENCES "user",
+    text      TEXT    NOT NULL,
+    state     TEXT    NOT NULL DEFAULT 'pending',
+    created   INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX ix_suggestion_chatgroup ON suggestion (chatgroup);
@@ -0,0 +1,6 @@
+-- The next update to fetch when polling telegram instead of receiving updates by webhook
+CREATE TABLE telegram_offset
+(
+    id        INTEGER PRIMARY KEY,
+    update_id INTEGER NOT NULL
+);
@@ -19,16 +19,14 @@ export default class App extends HTMLElement {
 
     async init() {
         let params = (new URL(document.location)).searchParams;
-        let group_id = params.get('group_id');
         let token = params.get('token');
-        params = new URLSearchParams({group_id, token});
+        params = new URLSearchParams({token});
         let response = await fetch(`/app?${params.toString()}`);
         if (response.status === 200) {
             let { question } = await response.json();
             this.shadowRoot.append(htmlToElement(`
                 <app-prompt 
                     question="${question}"
-                    group="${group_id}"
                     token="${token}"
                 />`));
         } else {
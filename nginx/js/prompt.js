@@ -71,10 +71,9 @@ export default class Prompt extends HTMLElement {
     }
 
     async handleSubmit() {
-        const group_id = this.getAttribute("group");
         const token = this.getAttribute("token");
         const answer = this.shadowRoot.querySelector("#answer").value;
-        const params = new URLSearchParams({group_id, token});
+        const params = new URLSearchParams({token});
         const submitButton = this.shadowRoot.querySelector("#submit")
 
         submitButton.setAttribute("disabled", "disabled");
@@ -82,6 +81,7 @@ export default class Prompt extends HTMLElement {
 
         await fetch(`/app?${params.toString()}`, {
             method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ answer })
         });
         this.dispatchEvent(new CustomEvent(
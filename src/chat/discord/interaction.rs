@@ -0,0 +1,254 @@
+use crate::chat::discord::{decode_hex, EPHEMERAL, LAUNCH_CALLBACK};
+use crate::chat::ChatError::Deserialize;
+use crate::chat::{Event, Result};
+use crate::game::{Callback, ChatGroup, FullUser};
+use log::error;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde_json::{json, Value};
+
+const PING: i64 = 1;
+const APPLICATION_COMMAND: i64 = 2;
+const MESSAGE_COMPONENT: i64 = 3;
+
+const PONG: i64 = 1;
+const CHANNEL_MESSAGE_WITH_SOURCE: i64 = 4;
+
+/// An interaction posted by discord to the interactions endpoint
+#[derive(Debug)]
+pub struct Interaction(pub Value);
+
+/// Checks that the body was signed by the application's key, the signature covering the
+/// timestamp followed by the body
+pub fn verify(public_key: &[u8], signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let signature = match decode_hex(signature) {
+        None => return false,
+        Some(signature) => signature,
+    };
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&message, &signature)
+        .is_ok()
+}
+
+impl Interaction {
+    pub fn is_ping(&self) -> bool {
+        self.kind() == Some(PING)
+    }
+
+    pub fn pong() -> Value {
+        json!({ "type": PONG })
+    }
+
+    /// The event for the interaction, none for interactions the bot does not act on
+    pub fn event(&self) -> Result<Option<Event>> {
+        match self.kind() {
+            Some(APPLICATION_COMMAND) => Ok(Some(Event::Command {
+                user: self.user()?,
+                chat_group: self.chat_group()?,
+                text: self.command_text()?,
+            })),
+            Some(MESSAGE_COMPONENT) => {
+                let data = self.string("/data/custom_id")?;
+                if data == LAUNCH_CALLBACK {
+                    return Ok(Some(Event::LaunchGame {
+                        user: self.user()?,
+                        chat_group: self.chat_group()?,
+                        callback: self.callback()?,
+                    }));
+                }
+                Ok(Some(Event::Callback {
+                    user: self.user()?,
+                    chat_group: self.chat_group()?,
+                    callback: self.callback()?,
+                    data,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The response to discord for the event. Commands are answered by the messages the bot sends
+    /// to the channel so they are only acknowledged to the user, while buttons are answered by
+    /// the chat client through the interaction's callback
+    pub fn response(event: &Event) -> Option<Value> {
+        match event {
+            Event::Command { text, .. } => Some(json!({
+                "type": CHANNEL_MESSAGE_WITH_SOURCE,
+                "data": {
+                    "content": text,
+                    "flags": EPHEMERAL
+                }
+            })),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> Option<i64> {
+        let Interaction(value) = self;
+        value.get("type").and_then(Value::as_i64)
+    }
+
+    /// The command as it would have been typed, e.g. `/top global`
+    fn command_text(&self) -> Result<String> {
+        let Interaction(value) = self;
+        let name = self.string("/data/name")?;
+        let arguments: Vec<&str> = value
+            .pointer("/data/options")
+            .and_then(Value::as_array)
+            .map(|options| {
+                options
+                    .iter()
+                    .filter_map(|option| option.get("value").and_then(Value::as_str))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut text = format!("/{}", name);
+        for argument in arguments {
+            text.push(' ');
+            text.push_str(argument);
+        }
+        Ok(text)
+    }
+
+    fn chat_group(&self) -> Result<ChatGroup> {
+        Ok(ChatGroup(self.snowflake("/channel_id")?))
+    }
+
+    fn callback(&self) -> Result<Callback> {
+        Ok(Callback {
+            id: self.snowflake("/id")?,
+            message_id: Some(self.snowflake("/message/id")?),
+            token: Some(self.string("/token")?),
+        })
+    }
+
+    /// The user is nested in the member when the interaction comes from a server channel
+    fn user(&self) -> Result<FullUser> {
+        let Interaction(value) = self;
+        let user = value
+            .pointer("/member/user")
+            .or_else(|| value.get("user"))
+            .ok_or_else(|| {
+                error!("Interaction is missing its user: {}", value);
+                Deserialize
+            })?;
+        let user = Interaction(user.clone());
+        let username = user.0.get("username").and_then(Value::as_str);
+        let first_name = user
+            .0
+            .get("global_name")
+            .and_then(Value::as_str)
+            .or(username);
+
+        Ok(FullUser {
+            id: user.snowflake("/id")?,
+            is_bot: user.0.get("bot").and_then(Value::as_bool).unwrap_or(false),
+            first_name: first_name.map(String::from),
+            last_name: None,
+            username: username.map(String::from),
+        })
+    }
+
+    fn string(&self, pointer: &str) -> Result<String> {
+        let Interaction(value) = self;
+        match value.pointer(pointer).and_then(Value::as_str) {
+            None => {
+                error!("Expected string: {} {:?}", pointer, value);
+                Err(Deserialize)
+            }
+            Some(string) => Ok(string.to_string()),
+        }
+    }
+
+    /// Discord ids are sent as strings as they do not fit in a javascript number
+    fn snowflake(&self, pointer: &str) -> Result<i64> {
+        let string = self.string(pointer)?;
+        string.parse().map_err(|err| {
+            error!("Expected snowflake: {} {}, {:?}", pointer, string, err);
+            Deserialize
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chat::discord::encode_hex as hex;
+    use crate::chat::discord::interaction::{verify, Interaction};
+    use crate::chat::Event;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::json;
+
+    #[test]
+    fn test_verify() {
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&[7; 32]).unwrap();
+        let public_key = key_pair.public_key().as_ref();
+        let body = br#"{"type":1}"#;
+        let signature = hex(key_pair.sign(b"1612345678{\"type\":1}").as_ref());
+
+        assert!(verify(public_key, &signature, "1612345678", body));
+        assert!(
+            !verify(public_key, &signature, "1612345679", body),
+            "The timestamp is part of the signature"
+        );
+        assert!(!verify(public_key, &signature, "1612345678", b"{}"));
+        assert!(!verify(public_key, "not hex", "1612345678", body));
+    }
+
+    #[test]
+    fn test_event() {
+        let command = Interaction(json!({
+            "type": 2,
+            "id": "800000000000000001",
+            "token": "token",
+            "channel_id": "800000000000000002",
+            "member": {
+                "user": {
+                    "id": "800000000000000003",
+                    "username": "player",
+                    "global_name": "Player"
+                }
+            },
+            "data": {
+                "name": "top",
+                "options": [{"type": 3, "name": "argument", "value": "global"}]
+            }
+        }));
+        let event = command.event().unwrap().unwrap();
+        assert_eq!(event.argument().as_deref(), Some("global"));
+        match event {
+            Event::Command {
+                user,
+                chat_group,
+                text,
+            } => {
+                assert_eq!(text, "/top global");
+                assert_eq!(chat_group.0, 800000000000000002);
+                assert_eq!(user.id, 800000000000000003);
+                assert_eq!(user.first_name.as_deref(), Some("Player"));
+            }
+            event => panic!("Expected command, got {:?}", event),
+        }
+
+        let component = Interaction(json!({
+            "type": 3,
+            "id": "800000000000000001",
+            "token": "token",
+            "channel_id": "800000000000000002",
+            "user": {"id": "800000000000000003", "username": "player"},
+            "message": {"id": "800000000000000004"},
+            "data": {"custom_id": "/vote_callback abc", "component_type": 2}
+        }));
+        match component.event().unwrap() {
+            Some(Event::Callback { callback, data, .. }) => {
+                assert_eq!(data, "/vote_callback abc");
+                assert_eq!(callback.message_id, Some(800000000000000004));
+                assert_eq!(callback.token.as_deref(), Some("token"));
+            }
+            event => panic!("Expected callback, got {:?}", event),
+        }
+
+        assert!(Interaction(json!({"type": 1})).is_ping());
+    }
+}
@@ -0,0 +1,750 @@
+use crate::chat::message;
+use crate::chat::ChatError::{Deserialize, ServerError};
+use crate::chat::Result;
+use crate::chat::{Board, ChatClient, Inline};
+use crate::game::pack::Pack;
+use crate::game::round::Round;
+use crate::game::settings::{Command, Role, Settings};
+use crate::game::suggestion::Suggestion;
+use crate::game::timer::Phase;
+use crate::game::{Answer, Callback, ChatGroup, Score, User, Vote};
+use crate::game::{Choice, FullUser};
+use crate::http::client::Client;
+use httparse::EMPTY_HEADER;
+use log::error;
+use serde_json::{json, Value};
+
+pub mod interaction;
+
+/// Callback data of the button that opens the app, discord has no games to launch it with
+pub const LAUNCH_CALLBACK: &str = "/launch_callback";
+
+/// Flag for messages only shown to the user of the interaction
+const EPHEMERAL: i64 = 64;
+const CHANNEL_MESSAGE_WITH_SOURCE: i64 = 4;
+const ADMINISTRATOR: u64 = 0x8;
+/// Discord allows at most 5 buttons in an action row
+const BUTTONS_PER_ROW: usize = 5;
+
+/// Slash commands and the description of their argument, if they take one
+const COMMANDS: [(&str, &str, Option<&str>); 14] = [
+    ("start", "How to start a game", None),
+    (
+        "new",
+        "Start a new game",
+        Some("notimer to play without time limits"),
+    ),
+    ("begin", "Begin the game once everyone has joined", None),
+    ("status", "Show what the game is waiting for", None),
+    ("end", "End the current game", None),
+    ("leave", "Leave the current game", None),
+    (
+        "kick",
+        "Take a player out of the current game",
+        Some("the player to kick, e.g. @username"),
+    ),
+    (
+        "top",
+        "Show the top players",
+        Some("global to include all channels"),
+    ),
+    (
+        "permission",
+        "Show or change who can use each command",
+        Some("<command> <anyone|host|admin>"),
+    ),
+    (
+        "audience",
+        "Show or change whether people not playing can vote",
+        Some("<on|off>"),
+    ),
+    (
+        "latejoin",
+        "Show or change whether people can join a game that has begun",
+        Some("<on|off>"),
+    ),
+    ("packs", "Show the question packs", None),
+    (
+        "pack",
+        "Turn a question pack on or off",
+        Some("<name> <on|off>"),
+    ),
+    (
+        "suggest",
+        "Suggest a prompt for the games in this channel",
+        Some("the prompt, e.g. You should never ____ at a funeral"),
+    ),
+];
+
+pub struct Discord<'a> {
+    client: Client<'a>,
+    hostname: &'a str,
+    token: &'a str,
+    application_id: &'a str,
+}
+
+/// Decodes a hex string such as the keys and signatures used by discord
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|byte| match std::str::from_utf8(byte) {
+            Ok(byte) if byte.len() == 2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn buttons(buttons: &[(&str, String)]) -> Value {
+    let buttons: Vec<Value> = buttons
+        .iter()
+        .map(|(label, custom_id)| {
+            json!({
+                "type": 2,
+                "style": 1,
+                "label": label,
+                "custom_id": custom_id
+            })
+        })
+        .collect();
+    let rows: Vec<Value> = buttons
+        .chunks(BUTTONS_PER_ROW)
+        .map(|buttons| json!({ "type": 1, "components": buttons }))
+        .collect();
+    Value::Array(rows)
+}
+
+/// The buttons under the board, which are taken away once the game has ended. Joining late
+/// follows the votes
+fn board_components(board: &Board) -> Value {
+    let join = ("Join", "/join_callback".to_string());
+    match board {
+        Board::Joining { .. } => buttons(&[join]),
+        Board::Voting {
+            current, late_join, ..
+        } => {
+            let labels: Vec<String> = (0..current.len()).map(message::label).collect();
+            let mut options: Vec<(&str, String)> = current
+                .iter()
+                .zip(labels.iter())
+                .map(|(answer, label)| (label.as_str(), format!("/vote_callback {}", answer.token)))
+                .collect();
+            if *late_join {
+                options.push(join);
+            }
+            buttons(&options)
+        }
+        Board::Answering {
+            late_join: true, ..
+        } => buttons(&[join]),
+        Board::Answering { .. } | Board::Ended => json!([]),
+    }
+}
+
+impl<'a> Discord<'a> {
+    pub fn new(hostname: &'a str, token: &'a str, application_id: &'a str) -> Result<Self> {
+        let client = Client::new(hostname)?;
+        Ok(Self::with_client(client, hostname, token, application_id))
+    }
+
+    pub fn with_client(
+        client: Client<'a>,
+        hostname: &'a str,
+        token: &'a str,
+        application_id: &'a str,
+    ) -> Self {
+        Discord {
+            client,
+            hostname,
+            token,
+            application_id,
+        }
+    }
+
+    /// Registers the slash commands of the bot, replacing any that were registered before
+    pub fn register_commands(&self) -> Result<()> {
+        let commands: Vec<Value> = COMMANDS
+            .iter()
+            .map(|(name, description, argument)| {
+                let options: Vec<Value> = argument
+                    .iter()
+                    .map(|argument| {
+                        json!({
+                            "type": 3,
+                            "name": "argument",
+                            "description": argument,
+                            "required": false
+                        })
+                    })
+                    .collect();
+                json!({
+                    "name": name,
+                    "description": description,
+                    "options": options
+                })
+            })
+            .collect();
+        let path = format!("/applications/{}/commands", self.application_id);
+        self.request("PUT", &path, Some(Value::Array(commands)))?;
+        Ok(())
+    }
+
+    /// Sends the message in the direct message channel discord keeps with the user
+    fn send_private(&self, User { id }: &User, message: &str) -> Result<()> {
+        let body = json!({ "recipient_id": id.to_string() });
+        let channel = self.request("POST", "/users/@me/channels", Some(body))?;
+        match channel
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse().ok())
+        {
+            Some(channel_id) => self.send_message(&ChatGroup(channel_id), message),
+            None => {
+                error!(
+                    "Unexpected response for direct message channel: {}",
+                    channel
+                );
+                Err(Deserialize)
+            }
+        }
+    }
+
+    fn send_message(&self, chat_group: &ChatGroup, message: &str) -> Result<()> {
+        self.send_components(chat_group, message, json!([]))
+    }
+
+    fn send_components(
+        &self,
+        ChatGroup(id): &ChatGroup,
+        message: &str,
+        components: Value,
+    ) -> Result<()> {
+        let body = json!({
+            "content": message,
+            "components": components
+        });
+        let _body = self.request("POST", &format!("/channels/{}/messages", id), Some(body))?;
+        Ok(())
+    }
+
+    fn respond(&self, callback: &Callback, message: &str) -> Result<()> {
+        self.respond_with(
+            callback,
+            json!({
+                "content": message,
+                "flags": EPHEMERAL
+            }),
+        )
+    }
+
+    fn respond_with(&self, Callback { id, token, .. }: &Callback, data: Value) -> Result<()> {
+        let token = match token {
+            None => {
+                error!("Missing interaction token in callback");
+                return Err(Deserialize);
+            }
+            Some(token) => token,
+        };
+        let body = json!({
+            "type": CHANNEL_MESSAGE_WITH_SOURCE,
+            "data": data
+        });
+        let path = format!("/interactions/{}/{}/callback", id, token);
+        let _body = self.request("POST", &path, Some(body))?;
+        Ok(())
+    }
+
+    fn request(&self, verb: &'static str, path: &str, req_body: Option<Value>) -> Result<Value> {
+        let uri = format!("https://{}/api/v10{}", self.hostname, path);
+        let req_body = req_body.map(|body| body.to_string()).unwrap_or_default();
+
+        let req = http::Request::builder()
+            .method(verb)
+            .uri(&uri)
+            .header("Authorization", format!("Bot {}", self.token))
+            .body(req_body.clone())
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Failed to build request for discord: {} {} {}",
+                    verb, &uri, req_body
+                )
+            });
+        let mut header_buf = [EMPTY_HEADER; 100];
+        let mut res = httparse::Response::new(&mut header_buf);
+        let mut buf = Vec::new();
+        let (res, body) = self.client.send(req, &mut buf, &mut res)?;
+        match res.code {
+            Some(code) if (200..300).contains(&code) => {}
+            _ => {
+                error!(
+                    "Error response from discord: {:?} {:?}, {:?}",
+                    res.code,
+                    res.reason,
+                    String::from_utf8(body.to_vec())
+                );
+                return Err(ServerError);
+            }
+        }
+
+        if body.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        serde_json::from_slice(body).map_err(|err| {
+            error!("Could not parse response from discord: {}", err);
+            Deserialize
+        })
+    }
+}
+
+impl<'a> ChatClient for Discord<'a> {
+    /// Admins are the owner of the server or members with a role that has the administrator
+    /// permission, the @everyone role sharing the id of the server
+    fn is_admin(&self, ChatGroup(id): &ChatGroup, User { id: user_id }: &User) -> Result<bool> {
+        let channel = self.request("GET", &format!("/channels/{}", id), None)?;
+        let guild_id = match channel.get("guild_id").and_then(Value::as_str) {
+            // Direct messages have no admins
+            None => return Ok(false),
+            Some(guild_id) => guild_id,
+        };
+
+        let guild = self.request("GET", &format!("/guilds/{}", guild_id), None)?;
+        let user_id = user_id.to_string();
+        if guild.get("owner_id").and_then(Value::as_str) == Some(&user_id) {
+            return Ok(true);
+        }
+
+        let member = self.request(
+            "GET",
+            &format!("/guilds/{}/members/{}", guild_id, user_id),
+            None,
+        )?;
+
+        let (roles, member_roles) = match (
+            guild.get("roles").and_then(Value::as_array),
+            member.get("roles").and_then(Value::as_array),
+        ) {
+            (Some(roles), Some(member_roles)) => (roles, member_roles),
+            _ => {
+                error!("Unexpected response for guild member: {} {}", guild, member);
+                return Err(Deserialize);
+            }
+        };
+
+        let is_admin = roles
+            .iter()
+            .filter(|role| match role.get("id") {
+                Some(Value::String(role_id)) => {
+                    role_id == guild_id || member_roles.iter().any(|id| id == role_id.as_str())
+                }
+                _ => false,
+            })
+            .filter_map(|role| role.get("permissions").and_then(Value::as_str))
+            .filter_map(|permissions| permissions.parse::<u64>().ok())
+            .any(|permissions| permissions & ADMINISTRATOR != 0);
+        Ok(is_admin)
+    }
+
+    fn permission_denied_error(
+        &self,
+        chat_group: &ChatGroup,
+        command: Command,
+        role: Role,
+    ) -> Result<()> {
+        self.send_message(chat_group, &message::permission_denied(command, role))
+    }
+
+    fn admin_required_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::ADMIN_REQUIRED)
+    }
+
+    fn permissions_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::permissions(settings))
+    }
+
+    fn audience_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::audience(settings))
+    }
+
+    fn late_join_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::late_join(settings))
+    }
+
+    fn packs_message(&self, chat_group: &ChatGroup, packs: &[Pack]) -> Result<()> {
+        self.send_message(chat_group, &message::packs(packs))
+    }
+
+    fn not_enough_questions_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::NOT_ENOUGH_QUESTIONS)
+    }
+
+    fn invalid_suggestion_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, &message::invalid_suggestion())
+    }
+
+    fn suggestion_message(&self, chat_group: &ChatGroup, suggestion: &Suggestion) -> Result<()> {
+        self.send_components(
+            chat_group,
+            &message::suggestion(suggestion),
+            buttons(&[
+                ("Approve", format!("/approve_callback {}", suggestion.id)),
+                ("Reject", format!("/reject_callback {}", suggestion.id)),
+            ]),
+        )
+    }
+
+    fn update_suggestion_message(
+        &self,
+        ChatGroup(id): &ChatGroup,
+        suggestion: &Suggestion,
+        approved: bool,
+        Callback { message_id, .. }: &Callback,
+    ) -> Result<()> {
+        let message_id = match message_id {
+            None => {
+                error!("Missing message id in callback");
+                return Err(Deserialize);
+            }
+            Some(message_id) => message_id,
+        };
+
+        let body = json!({
+            "content": message::suggestion_reviewed(suggestion, approved),
+            "components": []
+        });
+        let path = format!("/channels/{}/messages/{}", id, message_id);
+        let _body = self.request("PATCH", &path, Some(body))?;
+        Ok(())
+    }
+
+    fn already_in_game_error(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::ALREADY_IN_GAME)
+    }
+
+    fn game_already_exists_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_ALREADY_EXISTS)
+    }
+
+    fn game_does_not_exist_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_DOES_NOT_EXIST)
+    }
+
+    fn require_at_least_three_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::REQUIRE_AT_LEAST_THREE)
+    }
+
+    fn game_expired_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_EXPIRED)
+    }
+
+    fn left_game_message(&self, chat_group: &ChatGroup, user: &FullUser) -> Result<()> {
+        self.send_message(chat_group, &message::left_game(user))
+    }
+
+    fn too_few_players_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::TOO_FEW_PLAYERS)
+    }
+
+    fn not_playing_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::NOT_PLAYING)
+    }
+
+    fn player_not_found_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::PLAYER_NOT_FOUND)
+    }
+
+    fn time_warning_message(
+        &self,
+        chat_group: &ChatGroup,
+        phase: Phase,
+        remaining: i64,
+    ) -> Result<()> {
+        self.send_message(chat_group, &message::time_warning(phase, remaining))
+    }
+
+    fn start_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::START)
+    }
+
+    fn private_start_message(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::PRIVATE_START)
+    }
+
+    fn prompt_message(&self, user: &User, prompt: &str) -> Result<()> {
+        self.send_private(user, prompt)
+    }
+
+    fn answers_done_message(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::ANSWERS_DONE)
+    }
+
+    fn no_prompts_error(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::NO_PROMPTS)
+    }
+
+    /// Discord has no inline queries, so none are ever answered
+    fn inline_results(&self, _query_id: &str, _inline: &Inline) -> Result<()> {
+        Ok(())
+    }
+
+    fn board_message(&self, ChatGroup(id): &ChatGroup, board: &Board) -> Result<i64> {
+        let body = json!({
+            "content": message::board(board),
+            "components": board_components(board)
+        });
+        let response = self.request("POST", &format!("/channels/{}/messages", id), Some(body))?;
+        // Snowflakes are sent as strings, being too large for some JSON parsers
+        match response
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse().ok())
+        {
+            Some(message_id) => Ok(message_id),
+            None => {
+                error!("Unexpected response for message: {}", response);
+                Err(Deserialize)
+            }
+        }
+    }
+
+    fn update_board_message(
+        &self,
+        ChatGroup(id): &ChatGroup,
+        message_id: i64,
+        board: &Board,
+    ) -> Result<()> {
+        let body = json!({
+            "content": message::board(board),
+            "components": board_components(board)
+        });
+        let path = format!("/channels/{}/messages/{}", id, message_id);
+        let _body = self.request("PATCH", &path, Some(body))?;
+        Ok(())
+    }
+
+    fn enter_prompts_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_components(
+            chat_group,
+            message::ENTER_PROMPTS,
+            buttons(&[("Open", LAUNCH_CALLBACK.to_string())]),
+        )
+    }
+
+    fn round_results_message(
+        &self,
+        choice: &Choice,
+        chat_group: &ChatGroup,
+        votes: &[Vote],
+        answers: &[Answer],
+        users: &[FullUser],
+        rounds: &[Round],
+    ) -> Result<()> {
+        if let Some(results) = message::round_results(choice, votes, answers, users, rounds) {
+            self.send_message(chat_group, &results)?;
+        }
+
+        Ok(())
+    }
+
+    fn game_over_message(
+        &self,
+        chat_group: &ChatGroup,
+        votes: &[Vote],
+        answers: &[Answer],
+        users: &[FullUser],
+        rounds: &[Round],
+    ) -> Result<()> {
+        self.send_message(
+            chat_group,
+            &message::game_over(votes, answers, users, rounds),
+        )
+    }
+
+    fn top_scores_message(
+        &self,
+        chat_group: &ChatGroup,
+        scores: &[Score],
+        global: bool,
+    ) -> Result<()> {
+        self.send_message(chat_group, &message::top_scores(scores, global))
+    }
+
+    fn join_game_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::JOINED_GAME)
+    }
+
+    fn game_does_not_exist_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::GAME_NO_LONGER_VALID)
+    }
+
+    fn launch_game_callback(&self, url: &str, callback: &Callback) -> Result<()> {
+        self.respond_with(
+            callback,
+            json!({
+                "content": message::ENTER_PROMPTS,
+                "flags": EPHEMERAL,
+                "components": [{
+                    "type": 1,
+                    "components": [{
+                        "type": 2,
+                        "style": 5,
+                        "label": "Open",
+                        "url": url
+                    }]
+                }]
+            }),
+        )
+    }
+
+    fn cannot_vote_own_question_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::CANNOT_VOTE_OWN_QUESTION)
+    }
+
+    fn only_vote_once_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::ONLY_VOTE_ONCE)
+    }
+
+    fn not_in_game_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::NOT_IN_GAME)
+    }
+
+    fn only_current_question_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::ONLY_CURRENT_QUESTION)
+    }
+
+    fn vote_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::VOTE_COUNTED)
+    }
+
+    fn review_admin_required_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::REVIEW_ADMIN_REQUIRED)
+    }
+
+    fn suggestion_reviewed_callback(&self, callback: &Callback, approved: bool) -> Result<()> {
+        let message = if approved {
+            message::SUGGESTION_APPROVED
+        } else {
+            message::SUGGESTION_REJECTED
+        };
+        self.respond(callback, message)
+    }
+
+    fn suggestion_already_reviewed_callback(&self, callback: &Callback) -> Result<()> {
+        self.respond(callback, message::SUGGESTION_ALREADY_REVIEWED)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chat::discord::Discord;
+    use crate::chat::{Board, ChatClient};
+    use crate::game::round::Round;
+    use crate::game::{Answer, Callback, ChatGroup, Question, User};
+    use crate::http::client::Client;
+    use crate::http::test::{client_config, serve};
+    use serde_json::{json, Value};
+
+    fn response(body: Value) -> String {
+        let body = body.to_string();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    fn request_body(request: &str) -> Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body.trim()).unwrap()
+    }
+
+    #[test]
+    fn test_is_admin() {
+        let guild = json!({
+            "id": "10",
+            "owner_id": "1",
+            "roles": [
+                {"id": "10", "permissions": "104324673"},
+                {"id": "11", "permissions": "8"}
+            ]
+        });
+        let (addr, handle) = serve(vec![
+            response(json!({"id": "5", "guild_id": "10"})),
+            response(guild.clone()),
+            response(json!({"roles": ["11"]})),
+            response(json!({"id": "5", "guild_id": "10"})),
+            response(guild),
+            response(json!({"roles": []})),
+        ]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let discord = Discord::with_client(client, "localhost", "secret", "1");
+
+        assert!(discord.is_admin(&ChatGroup(5), &User { id: 2 }).unwrap());
+        assert!(
+            !discord.is_admin(&ChatGroup(5), &User { id: 3 }).unwrap(),
+            "The @everyone role does not have the administrator permission"
+        );
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("GET https://localhost/api/v10/channels/5 HTTP/1.1"));
+        assert!(requests[0].contains("authorization: Bot secret\r\n"));
+        assert!(requests[2].starts_with("GET https://localhost/api/v10/guilds/10/members/2 "));
+    }
+
+    #[test]
+    fn test_board_message() {
+        let (addr, handle) = serve(vec![
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\na\r\n{\"id\":\"9\"}\r\n0\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 204 No Content\r\n\r\n".to_string(),
+        ]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let discord = Discord::with_client(client, "localhost", "secret", "1");
+        let answer = |user_id, token: &str| Answer {
+            question: Question {
+                id: 1,
+                text: "Question".to_string(),
+            },
+            user: User { id: user_id },
+            token: token.to_string(),
+            response: Some(format!("Answer {}", token)),
+            round: 1,
+        };
+
+        let current = [answer(1, "a"), answer(2, "b")];
+        let board = Board::Voting {
+            round: 1,
+            rounds: &[Round::HeadToHead { points: 1 }],
+            current: &current,
+            remaining: vec![],
+            late_join: false,
+        };
+        assert_eq!(discord.board_message(&ChatGroup(5), &board).unwrap(), 9);
+        discord
+            .vote_callback(&Callback {
+                id: 7,
+                message_id: Some(8),
+                token: Some("token".to_string()),
+            })
+            .unwrap();
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("POST https://localhost/api/v10/channels/5/messages "));
+        let body = request_body(&requests[0]);
+        assert!(body["content"]
+            .as_str()
+            .unwrap()
+            .contains("Question:\nA: Answer a\nB: Answer b"));
+        assert_eq!(
+            body["components"][0]["components"][1]["custom_id"],
+            "/vote_callback b"
+        );
+
+        assert!(requests[1]
+            .starts_with("POST https://localhost/api/v10/interactions/7/token/callback "));
+        assert_eq!(request_body(&requests[1])["data"]["flags"], 64);
+    }
+}
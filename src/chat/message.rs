@@ -0,0 +1,365 @@
+//! Message text shared by the chat backends
+
+use crate::chat::Board;
+use crate::game::pack::Pack;
+use crate::game::round::Round;
+use crate::game::scoring;
+use crate::game::settings::{Command, Role, Settings};
+use crate::game::suggestion::{Suggestion, MAX_SUGGESTION_LENGTH};
+use crate::game::timer::Phase;
+use crate::game::{Answer, Choice, FullUser, Score, Vote};
+use log::error;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+pub const ALREADY_IN_GAME: &str = "You are already in this game";
+pub const GAME_ALREADY_EXISTS: &str =
+    "Cannot start a new game, game already exists. Type /end to end current the game";
+pub const GAME_DOES_NOT_EXIST: &str = "There is no game currently running";
+pub const REQUIRE_AT_LEAST_THREE: &str = "You need at least 3 players to start the game";
+pub const GAME_EXPIRED: &str =
+    "Not enough players joined in time, the game has ended. Type /new to try again";
+pub const ADMIN_REQUIRED: &str = "Only a chat admin can change the settings";
+pub const NOT_ENOUGH_QUESTIONS: &str =
+    "The enabled question packs do not have enough questions, an admin can turn more on with /pack <name> on";
+pub const START: &str = "Add this bot to a group chat and send /new@QuiplashModeratorBot to start a new game in the chat!";
+pub const ENTER_PROMPTS: &str = "Enter your prompts";
+pub const JOINED_GAME: &str = "You have joined the game";
+pub const VOTE_COUNTED: &str = "Your vote has been counted";
+pub const GAME_NO_LONGER_VALID: &str = "This game is no longer valid";
+pub const CANNOT_VOTE_OWN_QUESTION: &str = "You cannot vote for your own question";
+pub const ONLY_VOTE_ONCE: &str = "You can only vote once per question";
+pub const NOT_IN_GAME: &str = "You must be a player in the current game to vote";
+pub const ONLY_CURRENT_QUESTION: &str = "You can only vote for the current question";
+pub const REVIEW_ADMIN_REQUIRED: &str = "Only a chat admin can review suggested prompts";
+pub const SUGGESTION_APPROVED: &str = "The prompt has been added to this chat's games";
+pub const SUGGESTION_REJECTED: &str = "The prompt has been rejected";
+pub const SUGGESTION_ALREADY_REVIEWED: &str = "This prompt has already been reviewed";
+pub const GAME_ENDED: &str = "This game has ended";
+/// Named in place of a player who left the game before their answers were voted on
+pub const LEFT_PLAYER: &str = "a player who left";
+pub const LATE_JOIN_HINT: &str = "Latecomers can still join to vote";
+pub const TOO_FEW_PLAYERS: &str =
+    "Fewer than 3 players are left, the game has ended. Type /new to start again";
+pub const NOT_PLAYING: &str = "You are not playing in the current game";
+pub const PLAYER_NOT_FOUND: &str =
+    "No player in the current game goes by that name, use /kick @username";
+pub const PRIVATE_START: &str =
+    "Your prompts will be sent here during games, reply to each one with your answer";
+pub const PRIVATE_HINT: &str =
+    "Can't open the game? Send /start to the bot in a private chat to answer there instead";
+pub const ANSWERS_DONE: &str = "Your answers are in, head back to the group to vote";
+pub const NO_PROMPTS: &str = "You have no prompts to answer right now";
+pub const INLINE_HINT: &str = "Type your answer after the bot's name, then tap here to send it";
+pub const INLINE_ANSWERED: &str = "I answered a prompt";
+pub const INLINE_VOTED: &str = "I voted for an answer";
+
+fn user_list<T: Display>(users: &[T]) -> String {
+    users
+        .iter()
+        .map(|user| format!("{}", user))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn permission_denied(command: Command, role: Role) -> String {
+    match role {
+        Role::Admin => format!("Only a chat admin can /{} the game", command),
+        _ => format!("Only the host or a chat admin can /{} the game", command),
+    }
+}
+
+pub fn permissions(settings: &Settings) -> String {
+    format!(
+        "Who can use each command:\n\
+        /begin: {}\n\
+        /end: {}\n\
+        /kick: {}\n\
+        Admins can change this with /permission <command> <anyone|host|admin>",
+        settings.begin, settings.end, settings.kick
+    )
+}
+
+pub fn audience(settings: &Settings) -> String {
+    let enabled = if settings.audience { "on" } else { "off" };
+    format!(
+        "Audience voting: {}\n\
+        When on, people not playing can vote too, sharing the points of two player votes\n\
+        Admins can change this with /audience <on|off>",
+        enabled
+    )
+}
+
+pub fn late_join(settings: &Settings) -> String {
+    let enabled = if settings.late_join { "on" } else { "off" };
+    format!(
+        "Late joining: {}\n\
+        When on, people can join a game that has begun to vote alongside the players\n\
+        Admins can change this with /latejoin <on|off>",
+        enabled
+    )
+}
+
+pub fn left_game(user: &FullUser) -> String {
+    format!("{} left the game", user)
+}
+
+pub fn packs(packs: &[Pack]) -> String {
+    let mut summary: Vec<String> = packs
+        .iter()
+        .map(|pack| {
+            let tags = if pack.tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", pack.tags.join(", "))
+            };
+            let enabled = if pack.enabled { "on" } else { "off" };
+            format!(
+                "{}{}: {}, {} questions",
+                pack.name, tags, enabled, pack.questions
+            )
+        })
+        .collect();
+
+    summary.insert(0, "Question packs:".to_string());
+    summary.push("Admins can change this with /pack <name> <on|off>".to_string());
+    summary.join("\n")
+}
+
+pub fn invalid_suggestion() -> String {
+    format!(
+        "Suggest a prompt of up to {} characters, e.g. /suggest You should never ____ at a funeral",
+        MAX_SUGGESTION_LENGTH
+    )
+}
+
+pub fn suggestion(suggestion: &Suggestion) -> String {
+    format!(
+        "{} suggested a prompt:\n\
+        {}\n\
+        A chat admin can add it to the games in this chat",
+        suggestion.user, suggestion.text
+    )
+}
+
+pub fn suggestion_reviewed(suggestion: &Suggestion, approved: bool) -> String {
+    let outcome = if approved { "Approved" } else { "Rejected" };
+    format!(
+        "{} prompt suggested by {}:\n{}",
+        outcome, suggestion.user, suggestion.text
+    )
+}
+
+pub fn time_warning(phase: Phase, remaining: i64) -> String {
+    let action = match phase {
+        Phase::Joining => "join the game",
+        Phase::Answering => "answer your prompts",
+        Phase::Voting => "vote",
+    };
+    format!("{} seconds left to {}!", remaining, action)
+}
+
+pub fn join_game(users: &[FullUser]) -> String {
+    format!(
+        "Click the Join button below \n\
+        Once everyone has joined, then type /begin to start the game\n\
+        Players:\n\
+        {}",
+        user_list(users)
+    )
+}
+
+pub fn remaining_voters(users: &[&FullUser]) -> String {
+    format!(
+        "The following people still need to vote:\n{}",
+        user_list(users)
+    )
+}
+
+pub fn remaining_answers(users: &[&FullUser]) -> String {
+    format!(
+        "The following people still need to answer their prompts:\n{}",
+        user_list(users)
+    )
+}
+
+pub fn round(number: i64, rounds: &[Round]) -> String {
+    match rounds.get((number - 1) as usize) {
+        Some(Round::LastLash { points }) => format!(
+            "Round {} of {}: the last lash! Everyone answers the same prompt and each vote is worth {} pts",
+            number,
+            rounds.len(),
+            points
+        ),
+        Some(round) => format!(
+            "Round {} of {}: each vote is worth {} pts",
+            number,
+            rounds.len(),
+            round.points()
+        ),
+        None => format!("Round {}", number),
+    }
+}
+
+/// The letter a player votes with for the answer at `index`
+pub fn label(index: usize) -> String {
+    ((b'A' + (index % 26) as u8) as char).to_string()
+}
+
+pub fn vote(answers: &[Answer]) -> String {
+    let mut lines = vec![format!(
+        "{}:",
+        answers
+            .first()
+            .map(|answer| answer.question.text.as_str())
+            .unwrap_or_default()
+    )];
+    for (i, answer) in answers.iter().enumerate() {
+        lines.push(format!("{}: {}", label(i), answer.response_or_forfeit()));
+    }
+    lines.join("\n")
+}
+
+fn with_late_join(text: String, late_join: bool) -> String {
+    if late_join {
+        format!("{}\n\n{}", text, LATE_JOIN_HINT)
+    } else {
+        text
+    }
+}
+
+pub fn board(board: &Board) -> String {
+    match board {
+        Board::Joining { users } => join_game(users),
+        Board::Answering {
+            round,
+            rounds,
+            remaining,
+            late_join,
+        } => with_late_join(
+            format!(
+                "{}\n\n{}",
+                self::round(*round, rounds),
+                remaining_answers(remaining)
+            ),
+            *late_join,
+        ),
+        Board::Voting {
+            round,
+            rounds,
+            current,
+            remaining,
+            late_join,
+        } => with_late_join(
+            format!(
+                "{}\n\n{}\n\n{}",
+                self::round(*round, rounds),
+                vote(current),
+                remaining_voters(remaining)
+            ),
+            *late_join,
+        ),
+        Board::Ended => GAME_ENDED.to_string(),
+    }
+}
+
+/// The points each answer to the question of `choice` scored, none when the question cannot be
+/// found
+pub fn round_results(
+    choice: &Choice,
+    votes: &[Vote],
+    answers: &[Answer],
+    users: &[FullUser],
+    rounds: &[Round],
+) -> Option<String> {
+    let chosen = answers.iter().find(|answer| answer.token.eq(&choice.token));
+    let chosen = match chosen {
+        None => {
+            error!("answer not found for choice: {:?}, {:?}", answers, choice);
+            return None;
+        }
+        Some(answer) => answer,
+    };
+
+    let users: HashMap<i64, &FullUser> = users.iter().map(|user| (user.id, user)).collect();
+    let name = |user_id: i64| {
+        users
+            .get(&user_id)
+            .map_or_else(|| LEFT_PLAYER.to_string(), |user| user.to_string())
+    };
+    let prompt = scoring::prompt(answers, chosen);
+    let scores = scoring::score_prompt(&prompt, votes, rounds);
+
+    let mut results = vec![];
+    for (answer, score) in prompt.iter().zip(scores.iter()) {
+        let audience = score
+            .audience
+            .map(|share| format!(", audience {}%", share))
+            .unwrap_or_default();
+        results.push(format!(
+            "{} ({} +{}{})",
+            answer.response_or_forfeit(),
+            name(answer.user.id),
+            score.points,
+            audience
+        ));
+    }
+    for score in scores.iter().filter(|score| score.quiplash) {
+        results.push(format!("QUIPLASH! {} swept the vote", name(score.user.id)));
+    }
+    Some(results.join("\n"))
+}
+
+pub fn game_over(
+    votes: &[Vote],
+    answers: &[Answer],
+    users: &[FullUser],
+    rounds: &[Round],
+) -> String {
+    let points = scoring::score_game(answers, votes, rounds);
+    let mut summary: Vec<(&FullUser, i64)> = users
+        .iter()
+        .map(|user| (user, *points.get(&user.id).unwrap_or(&0)))
+        .collect();
+
+    summary.sort_by(|(_, points_a), (_, points_b)| points_a.cmp(points_b).reverse());
+
+    let (winner, _) = summary.get(0).unwrap();
+
+    let mut summary: Vec<String> = summary
+        .iter()
+        .map(|(user, points)| format!("{}: {} pts", user, points))
+        .collect();
+
+    summary.insert(0, format!("Game Over! Winner: {}", winner));
+    summary.join("\n")
+}
+
+pub fn top_scores(scores: &[Score], global: bool) -> String {
+    if scores.is_empty() {
+        return "No games have been finished yet".to_string();
+    }
+
+    let mut summary: Vec<String> = scores
+        .iter()
+        .enumerate()
+        .map(|(i, score)| {
+            format!(
+                "{}. {}: {} pts, {} wins, {} games",
+                i + 1,
+                score.user,
+                score.points,
+                score.wins,
+                score.games
+            )
+        })
+        .collect();
+
+    if global {
+        summary.insert(0, "Top players across all groups:".to_string());
+    } else {
+        summary.insert(0, "Top players in this group:".to_string());
+    }
+
+    summary.join("\n")
+}
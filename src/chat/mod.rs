@@ -1,7 +1,19 @@
-use crate::game::{Answer, Callback, ChatGroup, Vote};
+use crate::chat::discord::Discord;
+use crate::chat::telegram::error::ApiError;
+use crate::chat::telegram::Telegram;
+use crate::config::ChatBackend;
+use crate::game;
+use crate::game::pack::Pack;
+use crate::game::round::Round;
+use crate::game::settings::{Command, Role, Settings};
+use crate::game::suggestion::Suggestion;
+use crate::game::timer::Phase;
+use crate::game::{Answer, Callback, ChatGroup, Score, State, User, Vote};
 use crate::game::{Choice, FullUser};
 use crate::http::client::ClientError;
 
+pub mod discord;
+pub mod message;
 pub mod telegram;
 
 #[derive(Debug)]
@@ -9,6 +21,18 @@ pub enum ChatError {
     ClientError(ClientError),
     ServerError,
     Deserialize,
+    /// An error telegram answered with
+    Telegram(ApiError),
+}
+
+impl ChatError {
+    /// The user cannot be sent private messages, having blocked the bot or never started it
+    pub fn is_blocked(&self) -> bool {
+        matches!(
+            self,
+            ChatError::Telegram(ApiError::BotBlocked | ApiError::ChatNotFound)
+        )
+    }
 }
 
 impl From<ClientError> for ChatError {
@@ -19,17 +43,218 @@ impl From<ClientError> for ChatError {
 
 pub type Result<T> = std::result::Result<T, ChatError>;
 
+/// Something a user did in a chat, independent of the backend it came from
+#[derive(Debug)]
+pub enum Event {
+    /// A command such as `/top global`, the text including the command
+    Command {
+        user: FullUser,
+        chat_group: ChatGroup,
+        text: String,
+    },
+    /// A button was pressed, the data being the callback such as `/vote_callback <token>`
+    Callback {
+        user: FullUser,
+        chat_group: ChatGroup,
+        callback: Callback,
+        data: String,
+    },
+    /// A player opened the app to enter their answers
+    LaunchGame {
+        user: FullUser,
+        chat_group: ChatGroup,
+        callback: Callback,
+    },
+    /// A message sent to the bot in a private chat that is not a command, answering the user's
+    /// next prompt
+    Answer {
+        user: FullUser,
+        chat_group: ChatGroup,
+        text: String,
+    },
+    /// The user typed the bot's name in any chat, followed by the text of the query
+    InlineQuery {
+        user: FullUser,
+        id: String,
+        text: String,
+    },
+    /// The user picked the prompt offered for their inline query, answering it with the query
+    InlineAnswer {
+        user: FullUser,
+        token: String,
+        text: String,
+    },
+    /// The user picked one of the answers offered for their inline query to vote for
+    InlineVote { user: FullUser, choice: Choice },
+}
+
+impl Event {
+    /// Text following the command or callback name, e.g. `global` for `/top global`
+    pub fn argument(&self) -> Option<String> {
+        let text = match self {
+            Event::Command { text, .. } => text,
+            Event::Callback { data, .. } => data,
+            _ => return None,
+        };
+        text.split_once(' ')
+            .map(|(_, argument)| argument.trim().to_string())
+            .filter(|argument| !argument.is_empty())
+    }
+
+    /// The chat the event happened in, which inline queries are not tied to
+    pub fn chat_group(&self) -> Option<&ChatGroup> {
+        match self {
+            Event::Command { chat_group, .. }
+            | Event::Callback { chat_group, .. }
+            | Event::LaunchGame { chat_group, .. }
+            | Event::Answer { chat_group, .. } => Some(chat_group),
+            Event::InlineQuery { .. } | Event::InlineAnswer { .. } | Event::InlineVote { .. } => {
+                None
+            }
+        }
+    }
+}
+
+/// What the message kept up to date for each game shows, depending on what the game waits for
+#[derive(Debug)]
+pub enum Board<'a> {
+    Joining {
+        users: &'a [FullUser],
+    },
+    /// Latecomers can still join to vote when `late_join` holds
+    Answering {
+        round: i64,
+        rounds: &'a [Round],
+        remaining: Vec<&'a FullUser>,
+        late_join: bool,
+    },
+    /// The answers to the question being voted on
+    Voting {
+        round: i64,
+        rounds: &'a [Round],
+        current: &'a [Answer],
+        remaining: Vec<&'a FullUser>,
+        late_join: bool,
+    },
+    /// Nothing left to press once the game is over
+    Ended,
+}
+
+impl<'a> Board<'a> {
+    pub fn new(state: &'a State, rounds: &'a [Round], late_join: bool) -> game::Result<Self> {
+        let board = match state {
+            State::GatherUsers { users, .. } => Board::Joining { users },
+            State::GatherAnswers { round, .. } => Board::Answering {
+                round: *round,
+                rounds,
+                remaining: state.remaining_answerers()?,
+                late_join,
+            },
+            State::GatherVotes { round, current, .. } => Board::Voting {
+                round: *round,
+                rounds,
+                current,
+                remaining: state.remaining_voters()?,
+                late_join,
+            },
+            State::New { .. } | State::End { .. } => Board::Ended,
+        };
+        Ok(board)
+    }
+}
+
+/// What the bot offers a user who typed its name in a chat, to pick one of
+#[derive(Debug)]
+pub enum Inline<'a> {
+    /// The user's next prompt, answered with the text of the query when picked
+    Prompt {
+        answer: &'a Answer,
+        text: &'a str,
+    },
+    /// The answers to the question being voted on, other than the user's own
+    Vote {
+        current: &'a [Answer],
+        user: &'a User,
+    },
+    Nothing,
+}
+
+/// Creates the client for the configured chat backend
+pub fn new_client(backend: &ChatBackend) -> Result<Box<dyn ChatClient + '_>> {
+    match backend {
+        ChatBackend::Telegram {
+            hostname,
+            token,
+            gamename,
+            ..
+        } => Ok(Box::new(Telegram::new(hostname, token, gamename)?)),
+        ChatBackend::Discord {
+            hostname,
+            token,
+            application_id,
+            ..
+        } => Ok(Box::new(Discord::new(hostname, token, application_id)?)),
+    }
+}
+
 pub trait ChatClient {
+    fn is_admin(&self, chat_group: &ChatGroup, user: &User) -> Result<bool>;
+    fn permission_denied_error(
+        &self,
+        chat_group: &ChatGroup,
+        command: Command,
+        role: Role,
+    ) -> Result<()>;
+    fn admin_required_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn permissions_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()>;
+    fn audience_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()>;
+    fn late_join_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()>;
+    fn packs_message(&self, chat_group: &ChatGroup, packs: &[Pack]) -> Result<()>;
+    fn not_enough_questions_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn invalid_suggestion_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    /// Asks the admins to approve or reject the suggested prompt
+    fn suggestion_message(&self, chat_group: &ChatGroup, suggestion: &Suggestion) -> Result<()>;
+    fn update_suggestion_message(
+        &self,
+        chat_group: &ChatGroup,
+        suggestion: &Suggestion,
+        approved: bool,
+        callback: &Callback,
+    ) -> Result<()>;
     fn already_in_game_error(&self, callback: &Callback) -> Result<()>;
     fn game_already_exists_error(&self, chat_group: &ChatGroup) -> Result<()>;
     fn game_does_not_exist_error(&self, chat_group: &ChatGroup) -> Result<()>;
     fn require_at_least_three_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn game_expired_message(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn left_game_message(&self, chat_group: &ChatGroup, user: &FullUser) -> Result<()>;
+    /// The game ended because a player left, leaving fewer than three
+    fn too_few_players_message(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn not_playing_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn player_not_found_error(&self, chat_group: &ChatGroup) -> Result<()>;
+    fn time_warning_message(
+        &self,
+        chat_group: &ChatGroup,
+        phase: Phase,
+        remaining: i64,
+    ) -> Result<()>;
     fn start_message(&self, chat_group: &ChatGroup) -> Result<()>;
-    fn join_game_message(&self, chat_group: &ChatGroup, users: &[FullUser]) -> Result<()>;
+    /// Lets the user know their prompts will be sent to the private chat they started the bot in
+    fn private_start_message(&self, user: &User) -> Result<()>;
+    /// Sends the prompt to the user privately, the reply being taken as their answer
+    fn prompt_message(&self, user: &User, prompt: &str) -> Result<()>;
+    fn answers_done_message(&self, user: &User) -> Result<()>;
+    fn no_prompts_error(&self, user: &User) -> Result<()>;
+    fn inline_results(&self, query_id: &str, inline: &Inline) -> Result<()>;
+    /// Sends a new board for the game, returning the id of the message to update it with
+    fn board_message(&self, chat_group: &ChatGroup, board: &Board) -> Result<i64>;
+    /// Edits the board in place rather than sending another message
+    fn update_board_message(
+        &self,
+        chat_group: &ChatGroup,
+        message_id: i64,
+        board: &Board,
+    ) -> Result<()>;
     fn enter_prompts_message(&self, chat_group: &ChatGroup) -> Result<()>;
-    fn remaining_voters_message(&self, chat_group: &ChatGroup, users: &[&FullUser]) -> Result<()>;
-    fn remaining_answers_message(&self, chat_group: &ChatGroup, users: &[&FullUser]) -> Result<()>;
-    fn vote_message(&self, chat_group: &ChatGroup, answers: (&Answer, &Answer)) -> Result<()>;
     fn round_results_message(
         &self,
         choice: &Choice,
@@ -37,6 +262,7 @@ pub trait ChatClient {
         votes: &[Vote],
         answers: &[Answer],
         users: &[FullUser],
+        rounds: &[Round],
     ) -> Result<()>;
     fn game_over_message(
         &self,
@@ -44,18 +270,23 @@ pub trait ChatClient {
         votes: &[Vote],
         answers: &[Answer],
         users: &[FullUser],
+        rounds: &[Round],
     ) -> Result<()>;
-    fn join_game_callback(&self, callback: &Callback) -> Result<()>;
-    fn update_join_message(
+    fn top_scores_message(
         &self,
         chat_group: &ChatGroup,
-        users: &[FullUser],
-        callback: &Callback,
+        scores: &[Score],
+        global: bool,
     ) -> Result<()>;
+    fn join_game_callback(&self, callback: &Callback) -> Result<()>;
     fn game_does_not_exist_callback(&self, callback: &Callback) -> Result<()>;
     fn launch_game_callback(&self, token: &str, callback: &Callback) -> Result<()>;
     fn cannot_vote_own_question_callback(&self, callback: &Callback) -> Result<()>;
     fn only_vote_once_callback(&self, callback: &Callback) -> Result<()>;
     fn not_in_game_callback(&self, callback: &Callback) -> Result<()>;
     fn only_current_question_callback(&self, callback: &Callback) -> Result<()>;
+    fn vote_callback(&self, callback: &Callback) -> Result<()>;
+    fn review_admin_required_callback(&self, callback: &Callback) -> Result<()>;
+    fn suggestion_reviewed_callback(&self, callback: &Callback, approved: bool) -> Result<()>;
+    fn suggestion_already_reviewed_callback(&self, callback: &Callback) -> Result<()>;
 }
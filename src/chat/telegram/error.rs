@@ -0,0 +1,102 @@
+use serde_json::Value;
+
+/// An error telegram answered a method with
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    /// Too many requests were sent, the seconds to wait being given before trying again
+    FloodWait(u64),
+    /// The bot was blocked by the user, or removed from the chat
+    BotBlocked,
+    ChatNotFound,
+    /// An edit that would leave the message as it was
+    MessageNotModified,
+    Other {
+        code: i64,
+        description: String,
+    },
+}
+
+impl ApiError {
+    /// Decodes the error from a response that is not `ok`
+    pub fn from_response(status: u16, response: &Value) -> ApiError {
+        let code = response
+            .get("error_code")
+            .and_then(Value::as_i64)
+            .unwrap_or_else(|| i64::from(status));
+        let description = response
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string();
+        let retry_after = response
+            .pointer("/parameters/retry_after")
+            .and_then(Value::as_u64);
+        let lowercase = description.to_ascii_lowercase();
+
+        match (code, retry_after) {
+            (429, Some(retry_after)) => ApiError::FloodWait(retry_after),
+            (403, _) => ApiError::BotBlocked,
+            (400, _) if lowercase.contains("chat not found") => ApiError::ChatNotFound,
+            (400, _) if lowercase.contains("message is not modified") => {
+                ApiError::MessageNotModified
+            }
+            _ => ApiError::Other { code, description },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chat::telegram::error::ApiError;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_response() {
+        let error = |status, response| ApiError::from_response(status, &response);
+
+        assert_eq!(
+            error(
+                429,
+                json!({
+                    "ok": false,
+                    "error_code": 429,
+                    "description": "Too Many Requests: retry after 7",
+                    "parameters": {"retry_after": 7}
+                })
+            ),
+            ApiError::FloodWait(7)
+        );
+        assert_eq!(
+            error(
+                403,
+                json!({"error_code": 403, "description": "Forbidden: bot was blocked by the user"})
+            ),
+            ApiError::BotBlocked
+        );
+        assert_eq!(
+            error(
+                400,
+                json!({"error_code": 400, "description": "Bad Request: chat not found"})
+            ),
+            ApiError::ChatNotFound
+        );
+        assert_eq!(
+            error(
+                400,
+                json!({
+                    "error_code": 400,
+                    "description": "Bad Request: message is not modified: specified new message \
+                        content and reply markup are exactly the same"
+                })
+            ),
+            ApiError::MessageNotModified
+        );
+        assert_eq!(
+            error(502, json!(null)),
+            ApiError::Other {
+                code: 502,
+                description: "".to_string()
+            }
+        );
+    }
+}
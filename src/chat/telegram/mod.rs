@@ -1,16 +1,131 @@
-use crate::chat::ChatClient;
-use crate::chat::ChatError::{Deserialize, ServerError};
+use crate::chat::message;
+use crate::chat::telegram::error::ApiError;
+use crate::chat::telegram::update::{ANSWER_RESULT, VOTE_RESULT};
+use crate::chat::ChatError::{self, Deserialize, ServerError};
 use crate::chat::Result;
-use crate::game::{Answer, Callback, ChatGroup, Vote};
+use crate::chat::{Board, ChatClient, Inline};
+use crate::game::pack::Pack;
+use crate::game::round::Round;
+use crate::game::settings::{Command, Role, Settings};
+use crate::game::suggestion::Suggestion;
+use crate::game::timer::Phase;
+use crate::game::{Answer, Callback, ChatGroup, Score, User, Vote};
 use crate::game::{Choice, FullUser};
 use crate::http::client::Client;
 use httparse::EMPTY_HEADER;
-use log::error;
+use log::{error, warn};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
 
+pub mod error;
 pub mod update;
 
+/// Telegram allows at most 8 buttons in a row
+const VOTE_BUTTONS_PER_ROW: usize = 4;
+/// Sent again at most this many times when telegram asks to wait
+const MAX_RETRIES: usize = 2;
+/// Longer waits are not worth holding up the chat group for, and fail instead
+const MAX_RETRY_AFTER: u64 = 10;
+/// The updates the bot acts on, with inline results only sent once inline feedback is enabled
+/// with BotFather
+const ALLOWED_UPDATES: [&str; 4] = [
+    "message",
+    "callback_query",
+    "inline_query",
+    "chosen_inline_result",
+];
+
+/// Players whose client cannot open the game are pointed to answering in a private chat
+fn board_text(board: &Board) -> String {
+    match board {
+        Board::Answering { .. } => {
+            format!("{}\n\n{}", message::board(board), message::PRIVATE_HINT)
+        }
+        _ => message::board(board),
+    }
+}
+
+/// The results offered for an inline query, which send a message that does not give away the
+/// answer when picked
+fn inline_articles(inline: &Inline) -> Vec<Value> {
+    let article = |id: String, title: &str, description: &str, message: &str| {
+        json!({
+            "type": "article",
+            "id": id,
+            "title": title,
+            "description": description,
+            "input_message_content": { "message_text": message }
+        })
+    };
+    match inline {
+        Inline::Prompt { answer, text } => {
+            let description = if text.is_empty() {
+                message::INLINE_HINT
+            } else {
+                text
+            };
+            vec![article(
+                format!("{} {}", ANSWER_RESULT, answer.token),
+                &answer.question.text,
+                description,
+                message::INLINE_ANSWERED,
+            )]
+        }
+        Inline::Vote { current, user } => current
+            .iter()
+            .enumerate()
+            .filter(|(_, answer)| answer.user.id != user.id)
+            .map(|(i, answer)| {
+                article(
+                    format!("{} {}", VOTE_RESULT, answer.token),
+                    &format!("{}: {}", message::label(i), answer.response_or_forfeit()),
+                    &answer.question.text,
+                    message::INLINE_VOTED,
+                )
+            })
+            .collect(),
+        Inline::Nothing => vec![],
+    }
+}
+
+/// The buttons under the board, which are taken away once the game has ended. Joining late has
+/// its own row below the votes
+fn board_markup(board: &Board) -> Value {
+    let join = json!({
+        "text": "Join",
+        "callback_data": "/join_callback"
+    });
+    let buttons: Vec<Value> = match board {
+        Board::Joining { .. } => vec![join.clone()],
+        Board::Voting { current, .. } => current
+            .iter()
+            .enumerate()
+            .map(|(i, answer)| {
+                json!({
+                    "text": message::label(i),
+                    "callback_data": format!("/vote_callback {}", answer.token)
+                })
+            })
+            .collect(),
+        Board::Answering { .. } | Board::Ended => vec![],
+    };
+    let mut rows: Vec<Vec<Value>> = buttons
+        .chunks(VOTE_BUTTONS_PER_ROW)
+        .map(|row| row.to_vec())
+        .collect();
+    match board {
+        Board::Answering {
+            late_join: true, ..
+        }
+        | Board::Voting {
+            late_join: true, ..
+        } => rows.push(vec![join]),
+        _ => {}
+    }
+    json!({ "inline_keyboard": rows })
+}
+
 pub struct Telegram<'a> {
     client: Client<'a>,
     token: &'a str,
@@ -21,12 +136,61 @@ pub struct Telegram<'a> {
 impl<'a> Telegram<'a> {
     pub fn new(hostname: &'a str, token: &'a str, gamename: &'a str) -> Result<Self> {
         let client = Client::new(hostname)?;
-        Ok(Telegram {
+        Ok(Self::with_client(client, hostname, token, gamename))
+    }
+
+    pub fn with_client(
+        client: Client<'a>,
+        hostname: &'a str,
+        token: &'a str,
+        gamename: &'a str,
+    ) -> Self {
+        Telegram {
             client,
             token,
             hostname,
             gamename,
-        })
+        }
+    }
+
+    /// Waits up to `timeout` seconds for updates from `offset` on, which also confirms the updates
+    /// before it so they are not sent again
+    pub fn get_updates(&self, offset: Option<i64>, timeout: u64) -> Result<Vec<Value>> {
+        let body = json!({
+            "offset": offset,
+            "timeout": timeout,
+            "allowed_updates": ALLOWED_UPDATES
+        });
+        let response = self.call_method("getUpdates", body)?;
+        match response.as_array() {
+            Some(updates) => Ok(updates.clone()),
+            None => {
+                error!("Unexpected response for updates: {}", response);
+                Err(Deserialize)
+            }
+        }
+    }
+
+    /// Has updates sent to `url`, with the secret in the header the router checks for it
+    pub fn set_webhook(&self, url: &str, secret: &str) -> Result<()> {
+        let body = json!({
+            "url": url,
+            "secret_token": secret,
+            "allowed_updates": ALLOWED_UPDATES
+        });
+        let _body = self.call_method("setWebhook", body)?;
+        Ok(())
+    }
+
+    /// Stops sending updates to the webhook, which telegram requires before polling for them
+    pub fn delete_webhook(&self) -> Result<()> {
+        let _body = self.call_method("deleteWebhook", json!({}))?;
+        Ok(())
+    }
+
+    /// Telegram gives the private chat with a user the id of the user
+    fn send_private(&self, User { id }: &User, message: &str) -> Result<()> {
+        self.send_message(&ChatGroup(*id), message)
     }
 
     fn send_message(&self, ChatGroup(id): &ChatGroup, message: &str) -> Result<()> {
@@ -47,11 +211,32 @@ impl<'a> Telegram<'a> {
         Ok(())
     }
 
+    /// Edits a message, which is already done when it would be left as it was
+    fn edit_message(&self, body: Value) -> Result<()> {
+        match self.call_method("editMessageText", body) {
+            Ok(_) | Err(ChatError::Telegram(ApiError::MessageNotModified)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The result of the method, sent again when telegram asks to wait a short while
     fn call_method(&self, method: &'static str, body: Value) -> Result<Value> {
-        self.request("POST", method, body)
+        let mut retries = 0;
+        loop {
+            match self.request("POST", method, &body) {
+                Err(ChatError::Telegram(ApiError::FloodWait(retry_after)))
+                    if retries < MAX_RETRIES && retry_after <= MAX_RETRY_AFTER =>
+                {
+                    warn!("Retrying {} after {}s", method, retry_after);
+                    thread::sleep(Duration::from_secs(retry_after));
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
     }
 
-    fn request(&self, verb: &'static str, path: &'a str, req_body: Value) -> Result<Value> {
+    fn request(&self, verb: &'static str, path: &'a str, req_body: &Value) -> Result<Value> {
         let uri = format!("https://{}/bot{}/{}", self.hostname, self.token, path);
 
         let req = http::Request::builder()
@@ -71,67 +256,112 @@ impl<'a> Telegram<'a> {
         let mut buf = Vec::new();
         // todo move content type header setting up here
         let (res, body) = self.client.send(req, &mut buf, &mut res)?;
-        if res.code != Some(200) {
-            error!(
-                "Error response from telegram: {:?} {:?}, {:?}",
-                res.code,
-                res.reason,
-                String::from_utf8(body.to_vec())
-            );
-            return Err(ServerError);
-        }
+        let status = res.code.unwrap_or(0);
+        let response: Value = match serde_json::from_slice(body) {
+            Ok(response) => response,
+            Err(err) if status == 200 => {
+                error!("Could not parse response from telegram: {}", err);
+                return Err(Deserialize);
+            }
+            Err(_) => {
+                error!(
+                    "Error response from telegram: {:?} {:?}, {:?}",
+                    res.code,
+                    res.reason,
+                    String::from_utf8(body.to_vec())
+                );
+                return Err(ServerError);
+            }
+        };
 
-        Ok(req_body)
+        match (
+            response.get("ok").and_then(Value::as_bool),
+            response.get("result"),
+        ) {
+            (Some(true), Some(result)) => Ok(result.clone()),
+            (Some(true), None) => {
+                error!("Response from telegram without a result: {}", response);
+                Err(Deserialize)
+            }
+            _ => {
+                let err = ApiError::from_response(status, &response);
+                match err {
+                    ApiError::MessageNotModified => {}
+                    _ => error!("Error from telegram for {}: {:?}", path, err),
+                }
+                Err(ChatError::Telegram(err))
+            }
+        }
     }
 }
 
 impl<'a> ChatClient for Telegram<'a> {
-    fn already_in_game_error(&self, callback: &Callback) -> Result<()> {
-        self.answer_callback_query(callback, "You are already in this game")
+    fn is_admin(&self, ChatGroup(id): &ChatGroup, User { id: user_id }: &User) -> Result<bool> {
+        let body = json!({
+            "chat_id": id,
+            "user_id": user_id
+        });
+        let response = self.call_method("getChatMember", body)?;
+        match response.get("status").and_then(|status| status.as_str()) {
+            Some(status) => Ok(status == "creator" || status == "administrator"),
+            None => {
+                error!("Unexpected response for chat member: {}", response);
+                Err(Deserialize)
+            }
+        }
     }
 
-    fn game_already_exists_error(&self, chat_group: &ChatGroup) -> Result<()> {
-        self.send_message(
-            chat_group,
-            "Cannot start a new game, game already exists. Type /end to end current the game",
-        )
+    fn permission_denied_error(
+        &self,
+        chat_group: &ChatGroup,
+        command: Command,
+        role: Role,
+    ) -> Result<()> {
+        self.send_message(chat_group, &message::permission_denied(command, role))
     }
 
-    fn game_does_not_exist_error(&self, chat_group: &ChatGroup) -> Result<()> {
-        self.send_message(chat_group, "There is no game currently running")
+    fn admin_required_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::ADMIN_REQUIRED)
     }
 
-    fn require_at_least_three_error(&self, chat_group: &ChatGroup) -> Result<()> {
-        self.send_message(chat_group, "You need at least 3 players to start the game")
+    fn permissions_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::permissions(settings))
     }
 
-    fn start_message(&self, chat_group: &ChatGroup) -> Result<()> {
-        self.send_message(chat_group, "Add this bot to a group chat and send /new@QuiplashModeratorBot to start a new game in the chat!")
+    fn audience_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::audience(settings))
     }
 
-    fn join_game_message(&self, chat_group: &ChatGroup, users: &[FullUser]) -> Result<()> {
-        let user_list: String = users
-            .iter()
-            .map(|user| format!("{}", user))
-            .collect::<Vec<String>>()
-            .join("\n");
-        let message = format!(
-            "Click the Join button below \n\
-        Once everyone has joined, then type /begin to start the game\n\
-        Players:\n\
-        {}",
-            user_list
-        );
+    fn late_join_message(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()> {
+        self.send_message(chat_group, &message::late_join(settings))
+    }
+
+    fn packs_message(&self, chat_group: &ChatGroup, packs: &[Pack]) -> Result<()> {
+        self.send_message(chat_group, &message::packs(packs))
+    }
 
+    fn not_enough_questions_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::NOT_ENOUGH_QUESTIONS)
+    }
+
+    fn invalid_suggestion_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, &message::invalid_suggestion())
+    }
+
+    fn suggestion_message(&self, ChatGroup(id): &ChatGroup, suggestion: &Suggestion) -> Result<()> {
         let body = json!({
-            "chat_id": chat_group.0,
-            "text": message,
+            "chat_id": id,
+            "text": message::suggestion(suggestion),
             "reply_markup": {
                 "inline_keyboard": [
                     [
                         {
-                            "text": "Join",
-                            "callback_data": "/join_callback"
+                            "text": "Approve",
+                            "callback_data": format!("/approve_callback {}", suggestion.id)
+                        },
+                        {
+                            "text": "Reject",
+                            "callback_data": format!("/reject_callback {}", suggestion.id)
                         }
                     ]
                 ]
@@ -141,83 +371,153 @@ impl<'a> ChatClient for Telegram<'a> {
         Ok(())
     }
 
-    fn enter_prompts_message(&self, ChatGroup(id): &ChatGroup) -> Result<()> {
+    fn update_suggestion_message(
+        &self,
+        ChatGroup(id): &ChatGroup,
+        suggestion: &Suggestion,
+        approved: bool,
+        Callback { message_id, .. }: &Callback,
+    ) -> Result<()> {
+        let message_id = match message_id {
+            None => {
+                error!("Missing message id in callback");
+                return Err(Deserialize);
+            }
+            Some(message_id) => message_id,
+        };
+
         let body = json!({
             "chat_id": id,
-            "game_short_name": self.gamename,
-            "disable_notification": false,
-            "reply_markup": {
-                "inline_keyboard": [
-                    [
-                        {
-                            "text": "Enter your prompts",
-                            "callback_game": true
-                        }
-                    ]
-                ]
-            }
+            "message_id": message_id,
+            "text": message::suggestion_reviewed(suggestion, approved)
         });
-        self.call_method("sendGame", body)?;
-        Ok(())
+        self.edit_message(body)
     }
 
-    fn remaining_voters_message(&self, chat_group: &ChatGroup, users: &[&FullUser]) -> Result<()> {
-        let message = format!(
-            "The following people still need to vote:\n{}",
-            users
-                .iter()
-                .map(|user| format!("{}", user))
-                .collect::<Vec<String>>()
-                .join("\n")
-        );
-        self.send_message(chat_group, &message)?;
-        Ok(())
+    fn already_in_game_error(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::ALREADY_IN_GAME)
     }
 
-    fn remaining_answers_message(&self, chat_group: &ChatGroup, users: &[&FullUser]) -> Result<()> {
-        let message = format!(
-            "The following people still need to answer their prompts:\n{}",
-            users
-                .iter()
-                .map(|user| format!("{}", user))
-                .collect::<Vec<String>>()
-                .join("\n")
-        );
-        self.send_message(chat_group, &message)?;
+    fn game_already_exists_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_ALREADY_EXISTS)
+    }
+
+    fn game_does_not_exist_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_DOES_NOT_EXIST)
+    }
+
+    fn require_at_least_three_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::REQUIRE_AT_LEAST_THREE)
+    }
+
+    fn game_expired_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::GAME_EXPIRED)
+    }
+
+    fn left_game_message(&self, chat_group: &ChatGroup, user: &FullUser) -> Result<()> {
+        self.send_message(chat_group, &message::left_game(user))
+    }
+
+    fn too_few_players_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::TOO_FEW_PLAYERS)
+    }
+
+    fn not_playing_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::NOT_PLAYING)
+    }
+
+    fn player_not_found_error(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::PLAYER_NOT_FOUND)
+    }
+
+    fn time_warning_message(
+        &self,
+        chat_group: &ChatGroup,
+        phase: Phase,
+        remaining: i64,
+    ) -> Result<()> {
+        self.send_message(chat_group, &message::time_warning(phase, remaining))
+    }
+
+    fn start_message(&self, chat_group: &ChatGroup) -> Result<()> {
+        self.send_message(chat_group, message::START)
+    }
+
+    fn private_start_message(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::PRIVATE_START)
+    }
+
+    fn prompt_message(&self, user: &User, prompt: &str) -> Result<()> {
+        self.send_private(user, prompt)
+    }
+
+    fn answers_done_message(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::ANSWERS_DONE)
+    }
+
+    fn no_prompts_error(&self, user: &User) -> Result<()> {
+        self.send_private(user, message::NO_PROMPTS)
+    }
+
+    fn inline_results(&self, query_id: &str, inline: &Inline) -> Result<()> {
+        let body = json!({
+            "inline_query_id": query_id,
+            "results": inline_articles(inline),
+            "cache_time": 0,
+            "is_personal": true
+        });
+        let _body = self.call_method("answerInlineQuery", body)?;
         Ok(())
     }
 
-    fn vote_message(&self, ChatGroup(id): &ChatGroup, answers: (&Answer, &Answer)) -> Result<()> {
-        let (answer_a, answer_b) = answers;
-        let fallback_response = "??".to_string();
-        let response_a = &answer_a.response.as_ref().unwrap_or_else(|| {
-            error!("Missing response for answer [token: {}]", answer_a.token);
-            &fallback_response
+    fn board_message(&self, ChatGroup(id): &ChatGroup, board: &Board) -> Result<i64> {
+        let body = json!({
+            "chat_id": id,
+            "text": board_text(board),
+            "reply_markup": board_markup(board)
         });
-        let response_b = answer_b.response.as_ref().unwrap_or_else(|| {
-            error!("Missing response for answer [token: {}]", answer_b.token);
-            &fallback_response
+        let response = self.call_method("sendMessage", body)?;
+        match response.get("message_id").and_then(Value::as_i64) {
+            Some(message_id) => Ok(message_id),
+            None => {
+                error!("Unexpected response for message: {}", response);
+                Err(Deserialize)
+            }
+        }
+    }
+
+    fn update_board_message(
+        &self,
+        ChatGroup(id): &ChatGroup,
+        message_id: i64,
+        board: &Board,
+    ) -> Result<()> {
+        let body = json!({
+            "chat_id": id,
+            "message_id": message_id,
+            "text": board_text(board),
+            "reply_markup": board_markup(board)
         });
+        self.edit_message(body)
+    }
 
+    fn enter_prompts_message(&self, ChatGroup(id): &ChatGroup) -> Result<()> {
         let body = json!({
             "chat_id": id,
-            "text": format!("{}:\nA: {}\nB: {}", answer_a.question.text, response_a, response_b),
+            "game_short_name": self.gamename,
+            "disable_notification": false,
             "reply_markup": {
                 "inline_keyboard": [
                     [
                         {
-                            "text": "A",
-                            "callback_data": format!("/vote_callback {}", answer_a.token)
-                        },
-                        {
-                            "text": "B",
-                            "callback_data": format!("/vote_callback {}", answer_b.token)
+                            "text": message::ENTER_PROMPTS,
+                            "callback_game": true
                         }
                     ]
                 ]
             }
         });
-        self.call_method("sendMessage", body)?;
+        self.call_method("sendGame", body)?;
         Ok(())
     }
 
@@ -228,48 +528,12 @@ impl<'a> ChatClient for Telegram<'a> {
         votes: &[Vote],
         answers: &[Answer],
         users: &[FullUser],
+        rounds: &[Round],
     ) -> Result<()> {
-        let answer_a = answers.iter().find(|answer| answer.token.eq(&choice.token));
-        let answer_a = match answer_a {
-            None => {
-                error!("answer_a not found for choice: {:?}, {:?}", answers, choice);
-                return Ok(());
-            }
-            Some(answer) => answer,
-        };
-        let answer_b = answers.iter().find(|answer| {
-            answer.question.id.eq(&answer_a.question.id) && !answer.token.eq(&answer_a.token)
-        });
-        let answer_b = match answer_b {
-            None => {
-                error!(
-                    "answer_b not found for question: {:?}, {:?}",
-                    answers, answer_a.question
-                );
-                return Ok(());
-            }
-            Some(answer) => answer,
-        };
-
-        let mut sums: HashMap<&String, usize> = HashMap::new();
-        for vote in votes.iter() {
-            sums.insert(&vote.token, sums.get(&vote.token).unwrap_or(&0) + 1);
+        if let Some(results) = message::round_results(choice, votes, answers, users, rounds) {
+            self.send_message(chat_group, &results)?;
         }
 
-        let users: HashMap<i64, &FullUser> = users.iter().map(|user| (user.id, user)).collect();
-
-        let results = format!(
-            "{} ({} +{})\n{} ({} +{})",
-            answer_a.response.as_ref().unwrap(),
-            users.get(&answer_a.user.id).unwrap(),
-            sums.get(&answer_a.token).unwrap_or(&0),
-            answer_b.response.as_ref().unwrap(),
-            users.get(&answer_b.user.id).unwrap(),
-            sums.get(&answer_b.token).unwrap_or(&0)
-        );
-
-        self.send_message(chat_group, &results)?;
-
         Ok(())
     }
 
@@ -279,90 +543,31 @@ impl<'a> ChatClient for Telegram<'a> {
         votes: &[Vote],
         answers: &[Answer],
         users: &[FullUser],
+        rounds: &[Round],
     ) -> Result<()> {
-        let answers: HashMap<&String, i64> = answers
-            .iter()
-            .map(|answer| (&answer.token, answer.user.id))
-            .collect();
-        let votes: Vec<i64> = votes
-            .iter()
-            .map(|vote| *answers.get(&vote.token).unwrap())
-            .collect();
-        let mut summary: Vec<(&FullUser, usize)> = vec![];
-        for user in users.iter() {
-            let points = votes.iter().filter(|id| **id == user.id).count();
-            summary.push((user, points));
-        }
-
-        summary.sort_by(|(_, points_a), (_, points_b)| points_a.cmp(points_b).reverse());
-
-        let (winner, _) = summary.get(0).unwrap();
-
-        let mut summary: Vec<String> = summary
-            .iter()
-            .map(|(user, points)| format!("{}: {} pts", user, points))
-            .collect();
-
-        summary.insert(0, format!("Game Over! Winner: {}", winner));
-        let summary = summary.join("\n");
-
-        self.send_message(chat_group, &summary)?;
+        self.send_message(
+            chat_group,
+            &message::game_over(votes, answers, users, rounds),
+        )?;
 
         Ok(())
     }
 
-    fn join_game_callback(&self, callback: &Callback) -> Result<()> {
-        self.answer_callback_query(callback, "You have joined the game")
-    }
-
-    fn update_join_message(
+    fn top_scores_message(
         &self,
-        ChatGroup(id): &ChatGroup,
-        users: &[FullUser],
-        Callback { message_id, .. }: &Callback,
+        chat_group: &ChatGroup,
+        scores: &[Score],
+        global: bool,
     ) -> Result<()> {
-        let user_list: String = users
-            .iter()
-            .map(|user| format!("{}", user))
-            .collect::<Vec<String>>()
-            .join("\n");
-        let message = format!(
-            "Click the Join button below \n\
-        Once everyone has joined, then type /begin to start the game\n\
-        Players:\n\
-        {}",
-            user_list
-        );
-
-        let message_id = match message_id {
-            None => {
-                error!("Missing message id in callback");
-                return Err(Deserialize);
-            }
-            Some(message_id) => message_id,
-        };
+        self.send_message(chat_group, &message::top_scores(scores, global))
+    }
 
-        let body = json!({
-            "chat_id": id,
-            "message_id": message_id,
-            "text": message,
-            "reply_markup": {
-                "inline_keyboard": [
-                    [
-                        {
-                            "text": "Join",
-                            "callback_data": "/join_callback"
-                        }
-                    ]
-                ]
-            }
-        });
-        let _body = self.call_method("editMessageText", body)?;
-        Ok(())
+    fn join_game_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::JOINED_GAME)
     }
 
     fn game_does_not_exist_callback(&self, callback: &Callback) -> Result<()> {
-        self.answer_callback_query(callback, "This game is no longer valid")
+        self.answer_callback_query(callback, message::GAME_NO_LONGER_VALID)
     }
 
     fn launch_game_callback(&self, url: &str, Callback { id, .. }: &Callback) -> Result<()> {
@@ -376,43 +581,252 @@ impl<'a> ChatClient for Telegram<'a> {
         Ok(())
     }
 
-    fn cannot_vote_own_question_callback(&self, Callback { id, .. }: &Callback) -> Result<()> {
-        let message = "You cannot vote for your own question";
-        let body = json!({
-            "callback_query_id": id,
-            "text": message,
-        });
-        let _body = self.call_method("answerCallbackQuery", body)?;
-        Ok(())
+    fn cannot_vote_own_question_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::CANNOT_VOTE_OWN_QUESTION)
     }
 
-    fn only_vote_once_callback(&self, Callback { id, .. }: &Callback) -> Result<()> {
-        let message = "You can only vote once per question";
-        let body = json!({
-            "callback_query_id": id,
-            "text": message,
-        });
-        let _body = self.call_method("answerCallbackQuery", body)?;
-        Ok(())
+    fn only_vote_once_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::ONLY_VOTE_ONCE)
     }
 
-    fn not_in_game_callback(&self, Callback { id, .. }: &Callback) -> Result<()> {
-        let message = "You must be a player in the current game to vote";
-        let body = json!({
-            "callback_query_id": id,
-            "text": message,
-        });
-        let _body = self.call_method("answerCallbackQuery", body)?;
-        Ok(())
+    fn not_in_game_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::NOT_IN_GAME)
     }
 
-    fn only_current_question_callback(&self, Callback { id, .. }: &Callback) -> Result<()> {
-        let message = "You can only vote for the current question";
-        let body = json!({
-            "callback_query_id": id,
-            "text": message,
-        });
-        let _body = self.call_method("answerCallbackQuery", body)?;
-        Ok(())
+    fn only_current_question_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::ONLY_CURRENT_QUESTION)
+    }
+
+    fn vote_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::VOTE_COUNTED)
+    }
+
+    fn review_admin_required_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::REVIEW_ADMIN_REQUIRED)
+    }
+
+    fn suggestion_reviewed_callback(&self, callback: &Callback, approved: bool) -> Result<()> {
+        let message = if approved {
+            message::SUGGESTION_APPROVED
+        } else {
+            message::SUGGESTION_REJECTED
+        };
+        self.answer_callback_query(callback, message)
+    }
+
+    fn suggestion_already_reviewed_callback(&self, callback: &Callback) -> Result<()> {
+        self.answer_callback_query(callback, message::SUGGESTION_ALREADY_REVIEWED)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chat::message;
+    use crate::chat::telegram::error::ApiError;
+    use crate::chat::telegram::Telegram;
+    use crate::chat::{Board, ChatClient, ChatError, Inline};
+    use crate::game::round::Round;
+    use crate::game::{Answer, ChatGroup, Question, User};
+    use crate::http::client::Client;
+    use crate::http::test::{client_config, serve};
+    use serde_json::{json, Value};
+
+    fn response(body: Value) -> String {
+        status_response("200 OK", body)
+    }
+
+    fn status_response(status: &str, body: Value) -> String {
+        let body = body.to_string();
+        format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_errors() {
+        let (addr, handle) = serve(vec![
+            status_response(
+                "429 Too Many Requests",
+                json!({
+                    "ok": false,
+                    "error_code": 429,
+                    "description": "Too Many Requests: retry after 0",
+                    "parameters": {"retry_after": 0}
+                }),
+            ),
+            response(json!({"ok": true, "result": {"message_id": 7}})),
+            status_response(
+                "400 Bad Request",
+                json!({
+                    "ok": false,
+                    "error_code": 400,
+                    "description": "Bad Request: message is not modified"
+                }),
+            ),
+            status_response(
+                "403 Forbidden",
+                json!({
+                    "ok": false,
+                    "error_code": 403,
+                    "description": "Forbidden: bot was kicked from the group chat"
+                }),
+            ),
+        ]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let telegram = Telegram::with_client(client, "localhost", "secret", "quiplash");
+
+        let result = telegram.call_method("sendMessage", json!({"chat_id": 1, "text": "a"}));
+        assert_eq!(
+            result.unwrap(),
+            json!({"message_id": 7}),
+            "The message should be sent again after waiting"
+        );
+
+        telegram
+            .update_board_message(&ChatGroup(1), 7, &Board::Joining { users: &[] })
+            .unwrap();
+
+        match telegram.start_message(&ChatGroup(1)) {
+            Err(ChatError::Telegram(ApiError::BotBlocked)) => {}
+            other => panic!("Expected the bot to be blocked: {:?}", other),
+        }
+        assert_eq!(handle.join().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_board_message() {
+        let (addr, handle) = serve(vec![
+            response(json!({"ok": true, "result": {"message_id": 7}})),
+            response(json!({"ok": true, "result": true})),
+        ]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let telegram = Telegram::with_client(client, "localhost", "secret", "quiplash");
+        let answer = |user_id, token: &str| Answer {
+            question: Question {
+                id: 1,
+                text: "Question".to_string(),
+            },
+            user: User { id: user_id },
+            token: token.to_string(),
+            response: Some(format!("Answer {}", token)),
+            round: 1,
+        };
+        let current = [answer(1, "a"), answer(2, "b")];
+        let board = Board::Voting {
+            round: 1,
+            rounds: &[Round::HeadToHead { points: 1 }],
+            current: &current,
+            remaining: vec![],
+            late_join: false,
+        };
+
+        assert_eq!(telegram.board_message(&ChatGroup(1), &board).unwrap(), 7);
+        telegram
+            .update_board_message(&ChatGroup(1), 7, &Board::Ended)
+            .unwrap();
+
+        let requests = handle.join().unwrap();
+        let body = |request: &str| -> Value {
+            let (_, body) = request.split_once("\r\n\r\n").unwrap();
+            serde_json::from_str(body.trim()).unwrap()
+        };
+        let sent = body(&requests[0]);
+        assert!(sent["text"]
+            .as_str()
+            .unwrap()
+            .contains("Question:\nA: Answer a\nB: Answer b"));
+        assert_eq!(
+            sent["reply_markup"]["inline_keyboard"][0][1]["callback_data"],
+            "/vote_callback b"
+        );
+
+        assert!(requests[1].starts_with("POST https://localhost/botsecret/editMessageText "));
+        let edited = body(&requests[1]);
+        assert_eq!(edited["message_id"], 7);
+        assert_eq!(
+            edited["reply_markup"]["inline_keyboard"],
+            json!([]),
+            "The buttons should be taken away once the game has ended"
+        );
+    }
+
+    #[test]
+    fn test_inline_results() {
+        let (addr, handle) = serve(vec![response(json!({"ok": true, "result": true}))]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let telegram = Telegram::with_client(client, "localhost", "secret", "quiplash");
+        let answer = |user_id, token: &str| Answer {
+            question: Question {
+                id: 1,
+                text: "Question".to_string(),
+            },
+            user: User { id: user_id },
+            token: token.to_string(),
+            response: Some(format!("Answer {}", token)),
+            round: 1,
+        };
+        let current = [answer(1, "a"), answer(2, "b")];
+        let inline = Inline::Vote {
+            current: &current,
+            user: &User { id: 1 },
+        };
+
+        telegram.inline_results("5", &inline).unwrap();
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("POST https://localhost/botsecret/answerInlineQuery "));
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body.trim()).unwrap();
+        assert_eq!(body["inline_query_id"], "5");
+        assert_eq!(
+            body["results"],
+            json!([{
+                "type": "article",
+                "id": "vote b",
+                "title": "B: Answer b",
+                "description": "Question",
+                "input_message_content": { "message_text": message::INLINE_VOTED }
+            }]),
+            "Only the answers of others should be offered, keeping their labels on the board"
+        );
+    }
+
+    #[test]
+    fn test_get_updates() {
+        let update = json!({"update_id": 5, "message": {"text": "/new"}});
+        let (addr, handle) = serve(vec![response(json!({"ok": true, "result": [update]}))]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let telegram = Telegram::with_client(client, "localhost", "secret", "quiplash");
+
+        let updates = telegram.get_updates(Some(5), 30).unwrap();
+        assert_eq!(updates, vec![update]);
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("POST https://localhost/botsecret/getUpdates HTTP/1.1"));
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body.trim()).unwrap();
+        assert_eq!(body["offset"], 5);
+        assert_eq!(body["timeout"], 30);
+    }
+
+    #[test]
+    fn test_set_webhook() {
+        let (addr, handle) = serve(vec![response(json!({"ok": true, "result": true}))]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+        let telegram = Telegram::with_client(client, "localhost", "secret", "quiplash");
+
+        telegram
+            .set_webhook("https://localhost/webhook", "s3cret")
+            .unwrap();
+
+        let requests = handle.join().unwrap();
+        assert!(requests[0].starts_with("POST https://localhost/botsecret/setWebhook HTTP/1.1"));
+        let (_, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        let body: Value = serde_json::from_str(body.trim()).unwrap();
+        assert_eq!(body["url"], "https://localhost/webhook");
+        assert_eq!(body["secret_token"], "s3cret");
     }
 }
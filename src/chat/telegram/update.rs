@@ -1,19 +1,105 @@
 use crate::chat::ChatError::Deserialize;
-use crate::chat::Result;
+use crate::chat::{Event, Result};
 use crate::game::FullUser;
 use crate::game::{Callback, ChatGroup, Choice};
 use log::error;
 use serde_json::value::Value::Number;
 use serde_json::Value;
 
+/// Kind of the inline result offering the user's next prompt, followed by its token
+pub const ANSWER_RESULT: &str = "answer";
+/// Kind of the inline result offering an answer to vote for, followed by its token
+pub const VOTE_RESULT: &str = "vote";
+
+/// The objects of the updates the bot acts on, each with the user it is from
+const SENDERS: [&str; 4] = [
+    "/message",
+    "/callback_query",
+    "/inline_query",
+    "/chosen_inline_result",
+];
+
 #[derive(Debug)]
 pub struct Update(pub Value);
 
 impl Update {
+    /// The event for the update, none for updates the bot does not act on
+    pub fn event(&self) -> Result<Option<Event>> {
+        if self.game_short_name()?.is_some() {
+            return Ok(Some(Event::LaunchGame {
+                user: self.user()?,
+                chat_group: self.chat_group()?,
+                callback: self.callback()?,
+            }));
+        }
+
+        if let Some(text) = self.message_text()? {
+            if self.is_private()? && !text.starts_with('/') {
+                return Ok(Some(Event::Answer {
+                    user: self.user()?,
+                    chat_group: self.chat_group()?,
+                    text,
+                }));
+            }
+            return Ok(Some(Event::Command {
+                user: self.user()?,
+                chat_group: self.chat_group()?,
+                text,
+            }));
+        }
+
+        if let Some(data) = self.callback_data()? {
+            return Ok(Some(Event::Callback {
+                user: self.user()?,
+                chat_group: self.chat_group()?,
+                callback: self.callback()?,
+                data,
+            }));
+        }
+
+        if let Some(id) = self.inline_query_id()? {
+            return Ok(Some(Event::InlineQuery {
+                user: self.user()?,
+                id,
+                text: self.get_field("/inline_query/query")?,
+            }));
+        }
+
+        if let Some(result_id) = self.chosen_result_id()? {
+            let user = self.user()?;
+            let text = self.get_field("/chosen_inline_result/query")?;
+            return Ok(match result_id.split_once(' ') {
+                Some((ANSWER_RESULT, token)) => Some(Event::InlineAnswer {
+                    user,
+                    token: token.to_string(),
+                    text,
+                }),
+                Some((VOTE_RESULT, token)) => Some(Event::InlineVote {
+                    user,
+                    choice: Choice {
+                        token: token.to_string(),
+                    },
+                }),
+                _ => {
+                    error!("Unexpected inline result: {}", result_id);
+                    None
+                }
+            });
+        }
+
+        Ok(None)
+    }
+
     pub fn message_text(&self) -> Result<Option<String>> {
         self.get_field("/message/text")
     }
 
+    /// Whether the message was sent in a private chat with the bot rather than a group
+    pub fn is_private(&self) -> Result<bool> {
+        let chat_type: Option<String> = self.get_field("/message/chat/type")?;
+        Ok(chat_type.as_deref() == Some("private"))
+    }
+
     pub fn callback_data(&self) -> Result<Option<String>> {
         self.get_field("/callback_query/data")
     }
@@ -22,6 +108,14 @@ impl Update {
         self.get_field("/callback_query/game_short_name")
     }
 
+    pub fn inline_query_id(&self) -> Result<Option<String>> {
+        self.get_field("/inline_query/id")
+    }
+
+    pub fn chosen_result_id(&self) -> Result<Option<String>> {
+        self.get_field("/chosen_inline_result/result_id")
+    }
+
     pub fn chat_group(&self) -> Result<ChatGroup> {
         let chat_group =
             self.get_field_or("/message/chat/id", "/callback_query/message/chat/id")?;
@@ -31,20 +125,37 @@ impl Update {
     pub fn callback(&self) -> Result<Callback> {
         let id = self.get_field("/callback_query/id")?;
         let message_id = self.get_field("/callback_query/message/message_id")?;
-        Ok(Callback { id, message_id })
+        Ok(Callback {
+            id,
+            message_id,
+            token: None,
+        })
     }
 
     pub fn user(&self) -> Result<FullUser> {
-        let id = self.get_field_or("/message/from/id", "/callback_query/from/id")?;
-        let is_bot = self.get_field_or("/message/from/is_bot", "/callback_query/from/is_bot")?;
-        let first_name = self.get_field_or(
-            "/message/from/first_name",
-            "/callback_query/from/first_name",
-        )?;
-        let last_name =
-            self.get_field_or("/message/from/last_name", "/callback_query/from/last_name")?;
-        let username =
-            self.get_field_or("/message/from/username", "/callback_query/from/username")?;
+        let Update(value) = self;
+        let sender = SENDERS
+            .iter()
+            .find(|sender| value.pointer(&format!("{}/from", sender)).is_some());
+        let from = match sender {
+            None => {
+                error!("Failed to get field: from");
+                return Err(Deserialize);
+            }
+            Some(sender) => format!("{}/from", sender),
+        };
+        let id: Option<i64> = self.get_field(&format!("{}/id", from))?;
+        let id = match id {
+            None => {
+                error!("Expected int: {}/id {:?}", from, value);
+                return Err(Deserialize);
+            }
+            Some(id) => id,
+        };
+        let is_bot = self.get_field(&format!("{}/is_bot", from))?;
+        let first_name = self.get_field(&format!("{}/first_name", from))?;
+        let last_name = self.get_field(&format!("{}/last_name", from))?;
+        let username = self.get_field(&format!("{}/username", from))?;
         Ok(FullUser {
             id,
             is_bot,
@@ -53,24 +164,6 @@ impl Update {
             username,
         })
     }
-
-    pub fn choice(&self) -> Result<Choice> {
-        let data = self.callback_data()?;
-        let data = match data {
-            None => {
-                error!("Choice request is missing callback data");
-                return Err(Deserialize);
-            }
-            Some(choice) => choice,
-        };
-        let token: Vec<&str> = data.split(' ').collect();
-        match token.get(1) {
-            None => Err(Deserialize),
-            Some(token) => Ok(Choice {
-                token: token.to_string(),
-            }),
-        }
-    }
 }
 
 trait Field<T> {
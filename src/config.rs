@@ -1,14 +1,62 @@
+use crate::chat::discord::decode_hex;
+use crate::game::round::{parse_rounds, Round, DEFAULT_ROUNDS};
+use crate::game::timer::Timeouts;
+use crate::persistence::memory::Store;
+use crate::persistence::sqlite;
+use log::warn;
+use ring::rand::{SecureRandom, SystemRandom};
 use std::env;
 use std::env::VarError;
+use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Config {
-    pub db_dsn: String,
+    pub storage: Storage,
     pub bind_addr: String,
-    pub telegram_hostname: String,
-    pub telegram_gamename: String,
-    pub telegram_token: String,
+    pub chat_backend: ChatBackend,
     pub app_url: String,
+    /// Signs the links to the prompt page
+    pub app_secret: Vec<u8>,
+    pub timer_enabled: bool,
+    pub timer_interval: u64,
+    pub timeouts: Timeouts,
+    pub rounds: Vec<Round>,
+}
+
+/// Where games are kept
+#[derive(Clone)]
+pub enum Storage {
+    Postgres {
+        db_dsn: String,
+    },
+    Sqlite {
+        path: String,
+    },
+    /// Shared by every thread and lost on restart, for playing locally without a database
+    Memory(Arc<Store>),
+}
+
+/// The chat service the bot is played through
+#[derive(Clone)]
+pub enum ChatBackend {
+    Telegram {
+        hostname: String,
+        gamename: String,
+        token: String,
+        /// Polls for updates instead of having them sent to the webhook, which needs a public
+        /// HTTPS address
+        polling: bool,
+        /// Required of updates sent to the webhook, which is registered with it on startup
+        webhook_secret: String,
+    },
+    Discord {
+        hostname: String,
+        token: String,
+        application_id: String,
+        /// Used to verify that interactions were sent by discord
+        public_key: Vec<u8>,
+    },
 }
 
 pub enum ConfigError {
@@ -19,19 +67,143 @@ pub enum ConfigError {
 impl Config {
     pub fn from_env() -> Result<Config, ConfigError> {
         Ok(Config {
-            db_dsn: env_var("DB_DSN")?,
+            storage: Storage::from_env()?,
             bind_addr: env_var("BIND_ADDR")?,
-            telegram_hostname: env_var("TELEGRAM_HOSTNAME")?,
-            telegram_gamename: env_var("TELEGRAM_GAMENAME")?,
-            telegram_token: env_var("TELEGRAM_TOKEN")?,
+            chat_backend: ChatBackend::from_env()?,
             app_url: env_var("APP_URL")?,
+            app_secret: app_secret()?,
+            timer_enabled: parse("TIMER_ENABLED", env_var_or("TIMER_ENABLED", "true")?)?,
+            timer_interval: parse("TIMER_INTERVAL", env_var_or("TIMER_INTERVAL", "5")?)?,
+            timeouts: Timeouts {
+                gather_users: parse(
+                    "TIMEOUT_GATHER_USERS",
+                    env_var_or("TIMEOUT_GATHER_USERS", "600")?,
+                )?,
+                gather_answers: parse(
+                    "TIMEOUT_GATHER_ANSWERS",
+                    env_var_or("TIMEOUT_GATHER_ANSWERS", "300")?,
+                )?,
+                gather_votes: parse(
+                    "TIMEOUT_GATHER_VOTES",
+                    env_var_or("TIMEOUT_GATHER_VOTES", "90")?,
+                )?,
+                warnings: env_var_or("TIMER_WARNINGS", "60,15")?
+                    .split(',')
+                    .map(|warning| parse("TIMER_WARNINGS", warning.trim().to_string()))
+                    .collect::<Result<Vec<i64>, ConfigError>>()?,
+            },
+            rounds: parse_rounds(&env_var_or("ROUNDS", DEFAULT_ROUNDS)?)
+                .ok_or(ConfigError::InvalidEnvValue("ROUNDS"))?,
         })
     }
 }
 
+impl Storage {
+    /// SQLite when the DSN starts with `sqlite://`, otherwise Postgres
+    pub fn from_dsn(db_dsn: String) -> Storage {
+        match db_dsn.strip_prefix(sqlite::SCHEME) {
+            Some(path) => Storage::Sqlite {
+                path: path.to_string(),
+            },
+            None => Storage::Postgres { db_dsn },
+        }
+    }
+
+    fn from_env() -> Result<Storage, ConfigError> {
+        match env_var_or("STORAGE", "database")?.as_str() {
+            "database" => Ok(Storage::from_dsn(db_dsn()?)),
+            "memory" => Ok(Storage::Memory(Arc::new(Store::default()))),
+            _ => Err(ConfigError::InvalidEnvValue("STORAGE")),
+        }
+    }
+}
+
+impl ChatBackend {
+    fn from_env() -> Result<ChatBackend, ConfigError> {
+        match env_var_or("CHAT_BACKEND", "telegram")?.as_str() {
+            "telegram" => Ok(ChatBackend::Telegram {
+                hostname: env_var("TELEGRAM_HOSTNAME")?,
+                gamename: env_var("TELEGRAM_GAMENAME")?,
+                token: env_var("TELEGRAM_TOKEN")?,
+                polling: parse("TELEGRAM_POLLING", env_var_or("TELEGRAM_POLLING", "false")?)?,
+                webhook_secret: webhook_secret()?,
+            }),
+            "discord" => Ok(ChatBackend::Discord {
+                hostname: env_var_or("DISCORD_HOSTNAME", "discord.com")?,
+                token: env_var("DISCORD_TOKEN")?,
+                application_id: env_var("DISCORD_APPLICATION_ID")?,
+                public_key: decode_hex(&env_var("DISCORD_PUBLIC_KEY")?)
+                    .ok_or(ConfigError::InvalidEnvValue("DISCORD_PUBLIC_KEY"))?,
+            }),
+            _ => Err(ConfigError::InvalidEnvValue("CHAT_BACKEND")),
+        }
+    }
+}
+
+/// Without a secret configured one is generated, so links stop working when the app restarts
+fn app_secret() -> Result<Vec<u8>, ConfigError> {
+    match env_var("APP_SECRET") {
+        Ok(secret) if !secret.is_empty() => Ok(secret.into_bytes()),
+        Ok(_) => Err(ConfigError::InvalidEnvValue("APP_SECRET")),
+        Err(ConfigError::MissingEnv(_)) => {
+            warn!("APP_SECRET is not set, links to the prompt page will not survive a restart");
+            let mut secret = vec![0; 32];
+            SystemRandom::new()
+                .fill(&mut secret)
+                .map_err(|_err| ConfigError::InvalidEnvValue("APP_SECRET"))?;
+            Ok(secret)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Telegram only accepts 1 to 256 letters, digits, `_` and `-`. Without a secret configured one
+/// is generated, registered with the webhook again on every start
+fn webhook_secret() -> Result<String, ConfigError> {
+    let key = "TELEGRAM_WEBHOOK_SECRET";
+    match env_var(key) {
+        Ok(secret)
+            if (1..=256).contains(&secret.len())
+                && secret
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') =>
+        {
+            Ok(secret)
+        }
+        Ok(_) => Err(ConfigError::InvalidEnvValue(key)),
+        Err(ConfigError::MissingEnv(_)) => {
+            let mut secret = [0; 32];
+            SystemRandom::new()
+                .fill(&mut secret)
+                .map_err(|_err| ConfigError::InvalidEnvValue(key))?;
+            Ok(secret.iter().map(|byte| format!("{:02x}", byte)).collect())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The only setting needed by commands that do not run the server
+pub fn db_dsn() -> Result<String, ConfigError> {
+    env_var("DB_DSN")
+}
+
 fn env_var(key: &'static str) -> Result<String, ConfigError> {
     env::var(key).map_err(|err| match err {
         VarError::NotPresent => ConfigError::MissingEnv(key),
         VarError::NotUnicode(_) => ConfigError::InvalidEnvValue(key),
     })
 }
+
+fn env_var_or(key: &'static str, default: &str) -> Result<String, ConfigError> {
+    match env::var(key) {
+        Ok(value) => Ok(value),
+        Err(VarError::NotPresent) => Ok(default.to_string()),
+        Err(VarError::NotUnicode(_)) => Err(ConfigError::InvalidEnvValue(key)),
+    }
+}
+
+fn parse<T: FromStr>(key: &'static str, value: String) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_err| ConfigError::InvalidEnvValue(key))
+}
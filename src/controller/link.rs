@@ -0,0 +1,128 @@
+//! Links to the prompt page, carrying a token signed for one player in one game of a chat group
+//! that stops working once it expires
+
+use crate::chat::discord::{decode_hex, encode_hex};
+use crate::game::{ChatGroup, User};
+use ring::hmac;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Long enough to answer every prompt of a game without a timer, after which the game has to be
+/// launched again
+const LIFETIME: u64 = 2 * 60 * 60;
+
+/// What a verified token was signed for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub user: User,
+    pub game_id: i64,
+    pub chat_group: ChatGroup,
+    /// Seconds since the epoch
+    pub expires: u64,
+}
+
+pub struct Links {
+    app_url: String,
+    key: hmac::Key,
+}
+
+impl Links {
+    pub fn new(app_url: &str, secret: &[u8]) -> Self {
+        Links {
+            app_url: app_url.to_string(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    pub fn url(&self, chat_group: &ChatGroup, user: &User, game_id: i64) -> String {
+        let link = Link {
+            user: user.clone(),
+            game_id,
+            chat_group: chat_group.clone(),
+            expires: now() + LIFETIME,
+        };
+        format!("{}/?token={}", self.app_url, self.sign(&link))
+    }
+
+    /// The token is the user, game, chat group and expiry followed by their signature, separated
+    /// by dots
+    pub fn sign(&self, link: &Link) -> String {
+        let ChatGroup(chat_group) = link.chat_group;
+        let claims = format!(
+            "{}.{}.{}.{}",
+            link.user.id, link.game_id, chat_group, link.expires
+        );
+        let signature = hmac::sign(&self.key, claims.as_bytes());
+        format!("{}.{}", claims, encode_hex(signature.as_ref()))
+    }
+
+    /// The link the token was signed for, unless it was not signed with this key or has expired
+    pub fn verify(&self, token: &str) -> Option<Link> {
+        self.verify_at(token, now())
+    }
+
+    fn verify_at(&self, token: &str, now: u64) -> Option<Link> {
+        let (claims, signature) = token.rsplit_once('.')?;
+        hmac::verify(&self.key, claims.as_bytes(), &decode_hex(signature)?).ok()?;
+
+        let mut claims = claims.split('.');
+        let link = Link {
+            user: User {
+                id: claims.next()?.parse().ok()?,
+            },
+            game_id: claims.next()?.parse().ok()?,
+            chat_group: ChatGroup(claims.next()?.parse().ok()?),
+            expires: claims.next()?.parse().ok()?,
+        };
+        if claims.next().is_some() || link.expires <= now {
+            return None;
+        }
+        Some(link)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::controller::link::{Link, Links};
+    use crate::game::{ChatGroup, User};
+
+    #[test]
+    fn test_verify() {
+        let links = Links::new("http://localhost", b"secret");
+        let link = Link {
+            user: User { id: 1 },
+            game_id: 2,
+            chat_group: ChatGroup(-5),
+            expires: 100,
+        };
+        let token = links.sign(&link);
+
+        assert_eq!(links.verify_at(&token, 99), Some(link));
+        assert_eq!(links.verify_at(&token, 100), None, "The link has expired");
+        assert_eq!(
+            Links::new("http://localhost", b"other").verify_at(&token, 99),
+            None,
+            "Tokens signed with another key should be rejected"
+        );
+
+        let forged = token.replacen("1.2.", "1.3.", 1);
+        assert_eq!(
+            links.verify_at(&forged, 99),
+            None,
+            "The token should not be moved to another game"
+        );
+        let forged = token.replacen(".-5.", ".-6.", 1);
+        assert_eq!(
+            links.verify_at(&forged, 99),
+            None,
+            "The token should not be moved to another chat group"
+        );
+        assert_eq!(links.verify_at("u1q1", 99), None);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::game::ChatGroup;
+use std::collections::HashSet;
+use std::sync::{Condvar, Mutex, PoisonError};
+
+/// Lets one update at a time through for each chat group, shared by every worker so updates to a
+/// game are applied in the order they are handled while other chat groups carry on
+#[derive(Default)]
+pub struct ChatLocks {
+    locked: Mutex<HashSet<i64>>,
+    released: Condvar,
+}
+
+/// Held while an update for the chat group is handled, letting the next one through when dropped
+pub struct ChatGuard<'s> {
+    locks: &'s ChatLocks,
+    chat_group: i64,
+}
+
+impl ChatLocks {
+    /// Waits until no other update for the chat group is being handled. Not reentrant, so it is
+    /// only taken where updates come in
+    pub fn lock(&self, ChatGroup(chat_group): &ChatGroup) -> ChatGuard<'_> {
+        let mut locked = self.locked.lock().unwrap_or_else(PoisonError::into_inner);
+        while locked.contains(chat_group) {
+            locked = self
+                .released
+                .wait(locked)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        locked.insert(*chat_group);
+
+        ChatGuard {
+            locks: self,
+            chat_group: *chat_group,
+        }
+    }
+}
+
+impl Drop for ChatGuard<'_> {
+    fn drop(&mut self) {
+        let mut locked = self
+            .locks
+            .locked
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        locked.remove(&self.chat_group);
+        self.locks.released.notify_all();
+    }
+}
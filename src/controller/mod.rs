@@ -1,13 +1,23 @@
-use crate::chat::ChatClient;
 use crate::chat::ChatError;
-use crate::game::AnswerError::AlreadyAnswered;
+use crate::chat::{Board, ChatClient, Inline};
+use crate::controller::link::{Link, Links};
+use crate::controller::lock::{ChatGuard, ChatLocks};
+use crate::game::AnswerError::{AlreadyAnswered, NoneWithToken};
 
+use crate::game::round;
+use crate::game::round::Round;
+use crate::game::settings::{Command, Role};
+use crate::game::suggestion;
+use crate::game::timer::{Action, Phase, Timeouts, Timer};
 use crate::game::{Callback, ChatGroup, Choice, DomainError, FullUser, State, User, VoteError};
 
-use crate::persistence::DaoError;
+use crate::persistence::{transaction, DaoError, Daos};
 use crate::{game, persistence};
 use log::{error, info, warn};
+use std::sync::Arc;
 
+pub mod link;
+pub mod lock;
 #[cfg(test)]
 mod test;
 
@@ -18,6 +28,12 @@ pub enum ClientErrorReason {
     #[allow(dead_code)]
     InvalidCommand,
     InvalidQueryParams,
+    /// The request could not be verified as coming from the chat backend, or the link to the
+    /// prompt page as one sent to the player
+    Unauthorized,
+    NotFound,
+    /// The methods the path does allow
+    MethodNotAllowed(&'static str),
 }
 
 #[derive(Debug)]
@@ -31,6 +47,8 @@ pub enum ControllerError {
 
 pub type Result<T> = std::result::Result<T, ControllerError>;
 
+const TOP_SCORES_LIMIT: i64 = 10;
+
 impl From<ChatError> for ControllerError {
     fn from(_: ChatError) -> Self {
         error!("Failed due to telegram");
@@ -58,67 +76,88 @@ pub struct Controller<'s> {
     answer_dao: Box<dyn persistence::answer::Dao + 's>,
     user_dao: Box<dyn persistence::user::Dao + 's>,
     vote_dao: Box<dyn persistence::vote::Dao + 's>,
+    score_dao: Box<dyn persistence::score::Dao + 's>,
+    settings_dao: Box<dyn persistence::settings::Dao + 's>,
+    pack_dao: Box<dyn persistence::pack::Dao + 's>,
+    suggestion_dao: Box<dyn persistence::suggestion::Dao + 's>,
+    transaction_dao: Box<dyn persistence::transaction::Dao + 's>,
     chat_client: Box<dyn ChatClient + 's>,
-    app_url: String,
+    locks: Arc<ChatLocks>,
+    links: Links,
+    timer: bool,
+    rounds: Vec<Round>,
 }
 
 impl<'s> Controller<'s> {
     pub fn new(
-        connection: &'s libpq::Connection,
+        daos: Daos<'s>,
         chat_client: Box<dyn ChatClient + 's>,
-        app_url: &str,
+        locks: Arc<ChatLocks>,
+        links: Links,
+        timer: bool,
+        rounds: &[Round],
     ) -> Self {
-        // Maybe not box?
-        let game_dao = Box::new(persistence::game::PqDao::new(connection));
-        let question_dao = Box::new(persistence::question::PqDao::new(connection));
-        let answer_dao = Box::new(persistence::answer::PqDao::new(connection));
-        let user_dao = Box::new(persistence::user::PqDao::new(connection));
-        let vote_dao = Box::new(persistence::vote::PqDao::new(connection));
-
         Controller {
-            game_dao,
-            question_dao,
-            answer_dao,
-            user_dao,
+            game_dao: daos.game,
+            question_dao: daos.question,
+            answer_dao: daos.answer,
+            user_dao: daos.user,
             chat_client,
-            app_url: String::from(app_url),
-            vote_dao,
+            locks,
+            links,
+            vote_dao: daos.vote,
+            score_dao: daos.score,
+            settings_dao: daos.settings,
+            pack_dao: daos.pack,
+            suggestion_dao: daos.suggestion,
+            transaction_dao: daos.transaction,
+            timer,
+            rounds: rounds.to_vec(),
         }
     }
 
-    pub fn top_scores(&self) -> Result<()> {
-        warn!("Top scores invoked however not implemented");
+    /// Waits for any other update to the chat group to finish, to be held while handling one
+    pub fn lock(&self, chat_group: &ChatGroup) -> ChatGuard<'_> {
+        self.locks.lock(chat_group)
+    }
+
+    pub fn top_scores(&self, chat_group: ChatGroup, global: bool) -> Result<()> {
+        let scores = if global {
+            self.score_dao
+                .find_top(None, TOP_SCORES_LIMIT, &self.rounds)?
+        } else {
+            self.score_dao
+                .find_top(Some(&chat_group), TOP_SCORES_LIMIT, &self.rounds)?
+        };
+
+        self.chat_client
+            .top_scores_message(&chat_group, &scores, global)?;
         Ok(())
     }
 
+    /// Sends the board of the running game again, which is the one kept up to date from then on
     pub fn status(&self, chat_group: ChatGroup) -> Result<()> {
         if let Some(state) = self.game_dao.find_running(&chat_group)? {
             match state {
                 State::New { .. } => unreachable!(),
-                State::GatherUsers { users, .. } => {
-                    self.chat_client.join_game_message(&chat_group, &users)?;
+                State::GatherUsers { .. } | State::GatherVotes { .. } => {
+                    self.send_board(&chat_group, &state)?;
                 }
                 State::GatherAnswers { .. } => {
                     self.chat_client.enter_prompts_message(&chat_group)?;
-                    let users = state.remaining_answerers()?;
-                    self.chat_client
-                        .remaining_answers_message(&chat_group, &users)?;
-                }
-                State::GatherVotes {
-                    current: (ref a, ref b),
-                    ..
-                } => {
-                    let users = state.remaining_voters()?;
-                    self.chat_client
-                        .remaining_answers_message(&chat_group, &users)?;
-                    self.chat_client.vote_message(&chat_group, (&a, &b))?;
+                    self.send_board(&chat_group, &state)?;
                 }
                 State::End { id, .. } => {
                     let votes = self.vote_dao.find(id)?;
                     let answers = self.answer_dao.find(id)?;
                     let users = self.user_dao.find(id)?;
-                    self.chat_client
-                        .game_over_message(&chat_group, &votes, &answers, &users)?;
+                    self.chat_client.game_over_message(
+                        &chat_group,
+                        &votes,
+                        &answers,
+                        &users,
+                        &self.rounds,
+                    )?;
                 }
             };
         } else {
@@ -128,12 +167,29 @@ impl<'s> Controller<'s> {
         Ok(())
     }
 
-    pub fn start(&self, chat_group: ChatGroup) -> Result<()> {
-        self.chat_client.start_message(&chat_group)?;
+    /// Welcomes the user, who is sent their prompts from then on when starting a private chat
+    pub fn start(&self, user: FullUser, chat_group: ChatGroup) -> Result<()> {
+        // Telegram gives the private chat with a user the id of the user
+        if chat_group.0 != user.id {
+            self.chat_client.start_message(&chat_group)?;
+            return Ok(());
+        }
+
+        self.user_dao.save(&user)?;
+        let user = User::from(&user);
+        self.user_dao.save_private(&user, true)?;
+        self.chat_client.private_start_message(&user)?;
+
+        // Already playing, in which case the prompt is waiting to be answered
+        if let Some((_, chat_group)) = self.answer_dao.find_unanswered(&user)? {
+            if let Some(state) = self.game_dao.find_running(&chat_group)? {
+                self.send_prompt(&user, &state)?;
+            }
+        }
         Ok(())
     }
 
-    pub fn new_game(&self, user: FullUser, chat_group: ChatGroup) -> Result<()> {
+    pub fn new_game(&self, user: FullUser, chat_group: ChatGroup, timer: bool) -> Result<()> {
         self.user_dao.save(&user)?;
 
         if self.game_dao.find_running(&chat_group)?.is_some() {
@@ -145,12 +201,15 @@ impl<'s> Controller<'s> {
             return Ok(());
         }
 
-        let game_state = game::State::new(User { id: user.id }, &chat_group, false);
+        let game_state = game::State::new(User { id: user.id }, &chat_group, self.timer && timer);
 
         self.game_dao.save(&game_state)?;
 
         info!("Game started {:?}", chat_group);
-        self.chat_client.join_game_message(&chat_group, &[user])?;
+        // Read back for the id the game was saved with, which its board is kept under
+        if let Some(state) = self.game_dao.find_running(&chat_group)? {
+            self.send_board(&chat_group, &state)?;
+        }
 
         Ok(())
     }
@@ -162,27 +221,41 @@ impl<'s> Controller<'s> {
         callback: Callback,
     ) -> Result<()> {
         self.user_dao.save(&user)?;
+        let late_join = self.settings_dao.find(&chat_group)?.late_join;
 
         let user_id = user.id;
-        let mut game_state = match self.game_dao.find_running(&chat_group)? {
+        let joined = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let mut game_state = match self.game_dao.find_running(&chat_group)? {
+                None => return Ok(None),
+                Some(game) => game,
+            };
+
+            info!("Joining game: {:?} {}", &user, game_state.id());
+            if let Err(err) = game_state.join_game(user, late_join) {
+                return Ok(Some(Err(err)));
+            }
+            match &game_state {
+                State::GatherUsers { .. } => self.game_dao.save(&game_state)?,
+                // Saving a game that has begun leaves its players as they are
+                _ => self.user_dao.join(game_state.id(), &User { id: user_id })?,
+            }
+            Ok(Some(Ok(game_state)))
+        })?;
+
+        match joined {
             None => {
                 warn!(
                     "Attempted to join a non-existent game: user {} group {:?}",
                     user_id, chat_group
                 );
                 self.chat_client.game_does_not_exist_callback(&callback)?;
-                return Ok(());
+                Ok(())
             }
-            Some(game) => game,
-        };
-
-        info!("Joining game: {:?} {}", &user, game_state.id());
-        match game_state.join_game(user) {
-            Err(DomainError::AlreadyInGame) => {
+            Some(Err(DomainError::AlreadyInGame)) => {
                 self.chat_client.already_in_game_error(&callback)?;
                 Ok(())
             }
-            Err(DomainError::InvalidTransition) => {
+            Some(Err(DomainError::InvalidTransition)) => {
                 warn!(
                     "Attempted to join a game in an invalid state: user {} group {:?}",
                     user_id, chat_group
@@ -190,55 +263,142 @@ impl<'s> Controller<'s> {
                 self.chat_client.game_does_not_exist_callback(&callback)?;
                 Ok(())
             }
-            Err(_) => Ok(()),
-            Ok(()) => {
-                self.game_dao.save(&game_state)?;
+            Some(Err(_)) => Ok(()),
+            Some(Ok(game_state)) => {
                 self.chat_client.join_game_callback(&callback)?;
-                let users = self.user_dao.find(game_state.id())?;
-                self.chat_client
-                    .update_join_message(&chat_group, &users, &callback)?;
+                self.update_board(&chat_group, &game_state)?;
                 Ok(())
             }
         }
     }
 
-    pub fn begin_game(&self, chat_group: ChatGroup) -> Result<()> {
-        let state = match self.game_dao.find_running(&chat_group)? {
-            None => {
-                warn!(
-                    "Attempted to begin a non-existent game: group {:?}",
-                    chat_group
-                );
-                self.chat_client.game_does_not_exist_error(&chat_group)?;
-                return Ok(());
+    pub fn begin_game(&self, user: User, chat_group: ChatGroup) -> Result<()> {
+        // None once the reason nothing happened has been sent
+        let begun = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let state = match self.game_dao.find_running(&chat_group)? {
+                None => {
+                    warn!(
+                        "Attempted to begin a non-existent game: group {:?}",
+                        chat_group
+                    );
+                    self.chat_client.game_does_not_exist_error(&chat_group)?;
+                    return Ok(None);
+                }
+                Some(state) => state,
+            };
+
+            if !self.authorize(&user, &chat_group, &state, Command::Begin)? {
+                return Ok(None);
             }
-            Some(state) => state,
-        };
 
-        let questions = self.question_dao.find_random(&state).map_err(|err| {
-            warn!("Failed to get new set of questions: {:?}", chat_group);
-            err
+            let state = self.begun(&chat_group, &state)?;
+            if let Ok(state) = &state {
+                self.game_dao.save(state)?;
+            }
+            Ok(Some(state))
         })?;
 
-        match state.begin_game(&questions) {
-            Ok(state) => {
-                self.game_dao.save(&state)?;
-                self.chat_client.enter_prompts_message(&chat_group)?;
+        match begun {
+            None => Ok(()),
+            Some(Ok(state)) => self.start_round(&chat_group, &state),
+            Some(Err(DomainError::AtLeastThreePlayers)) => {
+                self.chat_client.require_at_least_three_error(&chat_group)?;
                 Ok(())
             }
-            Err(DomainError::AtLeastThreePlayers) => {
-                self.chat_client.require_at_least_three_error(&chat_group)?;
+            Some(Err(DomainError::NotEnoughQuestions)) => {
+                self.chat_client.not_enough_questions_error(&chat_group)?;
                 Ok(())
             }
-            Err(err) => {
+            Some(Err(err)) => {
                 error!("Unexpected error when beginning game: {:?}", err);
                 Ok(())
             }
         }
     }
 
+    /// The game begun with new questions, not saved yet
+    fn begun(
+        &self,
+        chat_group: &ChatGroup,
+        state: &State,
+    ) -> Result<std::result::Result<State, DomainError>> {
+        let players = match state {
+            State::GatherUsers { users, .. } => users.len(),
+            _ => 0,
+        };
+        let count = round::question_count(&self.rounds, players);
+        let questions = self
+            .question_dao
+            .find_random(chat_group, count)
+            .map_err(|err| {
+                warn!("Failed to get new set of questions: {:?}", chat_group);
+                err
+            })?;
+
+        Ok(state.begin_game(&questions, &self.rounds))
+    }
+
+    fn start_round(&self, chat_group: &ChatGroup, state: &State) -> Result<()> {
+        self.update_board(chat_group, state)?;
+        self.chat_client.enter_prompts_message(chat_group)?;
+        for user in self.user_dao.find_private(state.id())? {
+            if let Err(err) = self.send_prompt(&user, state) {
+                error!("Failed to send prompt to user {}: {:?}", user.id, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the user their next prompt privately, no longer trying once they can not be reached,
+    /// having blocked the bot or never started it
+    fn send_prompt(&self, user: &User, state: &State) -> Result<()> {
+        let prompt = match state.next_prompt(user) {
+            None => return Ok(()),
+            Some(answer) => &answer.question.text,
+        };
+        match self.chat_client.prompt_message(user, prompt) {
+            Ok(()) => Ok(()),
+            Err(err) if err.is_blocked() => {
+                info!(
+                    "User {} can not be sent private prompts: {:?}",
+                    user.id, err
+                );
+                self.user_dao.save_private(user, false)?;
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// The board for the game, with a button to join late when the chat group allows it
+    fn board<'a>(&'a self, chat_group: &ChatGroup, state: &'a State) -> Result<Board<'a>> {
+        let late_join = self.settings_dao.find(chat_group)?.late_join;
+        Ok(Board::new(state, &self.rounds, late_join)?)
+    }
+
+    /// Sends a new board for the game, which is the one edited from then on
+    fn send_board(&self, chat_group: &ChatGroup, state: &State) -> Result<()> {
+        let board = self.board(chat_group, state)?;
+        let message_id = self.chat_client.board_message(chat_group, &board)?;
+        self.game_dao.save_board(state.id(), message_id)?;
+        Ok(())
+    }
+
+    /// Edits the board of the game to show what it is waiting for, sending one when it has none
+    fn update_board(&self, chat_group: &ChatGroup, state: &State) -> Result<()> {
+        match self.game_dao.find_board(state.id())? {
+            None => self.send_board(chat_group, state),
+            Some(message_id) => {
+                let board = self.board(chat_group, state)?;
+                self.chat_client
+                    .update_board_message(chat_group, message_id, &board)?;
+                Ok(())
+            }
+        }
+    }
+
     pub fn launch_game(&self, user: User, chat_group: ChatGroup, callback: Callback) -> Result<()> {
-        match self.answer_dao.find_token(&user, &chat_group)? {
+        match self.answer_dao.find_game(&user, &chat_group)? {
             None => {
                 warn!(
                     "Attempted to begin launch game in invalid state: user {:?} group {:?}",
@@ -247,16 +407,24 @@ impl<'s> Controller<'s> {
                 self.chat_client.game_does_not_exist_callback(&callback)?;
                 Ok(())
             }
-            Some(token) => {
-                let url = self.generate_url(&chat_group, &token);
+            Some(game_id) => {
+                let url = self.links.url(&chat_group, &user, game_id);
                 self.chat_client.launch_game_callback(&url, &callback)?;
                 Ok(())
             }
         }
     }
 
-    pub fn get_prompt(&self, token: String) -> Result<String> {
-        match self.question_dao.find_with_token(&token)? {
+    /// What the token of a prompt page link was signed for, rejecting forged and expired links
+    pub fn verify_link(&self, token: &str) -> Result<Link> {
+        self.links.verify(token).ok_or_else(|| {
+            info!("Rejected an invalid or expired link");
+            ControllerError::ClientError(ClientErrorReason::Unauthorized)
+        })
+    }
+
+    pub fn get_prompt(&self, link: &Link) -> Result<String> {
+        match self.question_dao.find_prompt(&link.user, link.game_id)? {
             None => Err(ControllerError::Domain(DomainError::AnswerError(
                 AlreadyAnswered,
             ))),
@@ -264,48 +432,147 @@ impl<'s> Controller<'s> {
         }
     }
 
-    pub fn post_prompt(&self, token: String, answer: String, chat_group: ChatGroup) -> Result<()> {
-        let state = match self.game_dao.find_running(&chat_group)? {
+    /// Answers the next prompt of the link's player, as long as the link is for the game running
+    /// in its chat group
+    pub fn post_prompt(&self, link: &Link, answer: String) -> Result<()> {
+        self.answer(&link.user, link.game_id, &answer, None, &link.chat_group)?;
+        Ok(())
+    }
+
+    /// Answers the user's next prompt with a message sent to the bot privately, then sends them
+    /// the prompt after it
+    pub fn answer_privately(&self, user: FullUser, answer: String) -> Result<()> {
+        let user = User::from(&user);
+        let (game_id, chat_group) = match self.answer_dao.find_unanswered(&user)? {
             None => {
+                info!("No prompts to answer privately for user {}", user.id);
+                self.chat_client.no_prompts_error(&user)?;
                 return Ok(());
             }
-            Some(mut state) => {
-                state.answer_prompt(&token, &answer)?;
-                state
-            }
+            Some(game) => game,
         };
 
-        self.game_dao.save(&state)?;
+        // Taken here rather than by the router, which only knows the private chat
+        let _guard = self.lock(&chat_group);
+        let state = self.answer(&user, game_id, &answer, None, &chat_group)?;
+        match &state {
+            Some(state) if state.next_prompt(&user).is_some() => self.send_prompt(&user, state)?,
+            _ => self.chat_client.answers_done_message(&user)?,
+        }
+        Ok(())
+    }
 
-        if let State::GatherVotes {
-            id,
-            current: (answer_a, answer_b),
-            answers,
-            ..
-        } = &state
-        {
-            self.answer_dao.save_all(*id, answers)?;
-            self.chat_client
-                .vote_message(&chat_group, (answer_a, answer_b))?;
+    /// Offers the user their next prompt to answer with the text of the query, or the answers to
+    /// vote for, from a game they play in
+    pub fn inline_query(&self, user: User, query_id: &str, text: &str) -> Result<()> {
+        let chat_group = match self.answer_dao.find_unanswered(&user)? {
+            Some((_, chat_group)) => Some(chat_group),
+            None => self.game_dao.find_voting(&user)?,
+        };
+        let state = match chat_group {
+            None => None,
+            Some(chat_group) => self.game_dao.find_running(&chat_group)?,
+        };
+
+        let text = text.trim();
+        let inline = match &state {
+            None => Inline::Nothing,
+            Some(State::GatherVotes { current, .. }) => Inline::Vote {
+                current,
+                user: &user,
+            },
+            Some(state) => match state.next_prompt(&user) {
+                None => Inline::Nothing,
+                Some(answer) => Inline::Prompt { answer, text },
+            },
+        };
+        self.chat_client.inline_results(query_id, &inline)?;
+        Ok(())
+    }
+
+    /// Answers the prompt the user picked from their inline query with the text of the query, as
+    /// long as it is still their next one. Telegram only sends the pick once inline feedback is
+    /// enabled with BotFather
+    pub fn answer_inline(&self, user: User, token: &str, answer: String) -> Result<()> {
+        let answer = answer.trim();
+        if answer.is_empty() {
+            info!("Picked a prompt without an answer: user {}", user.id);
+            return Ok(());
         }
+        let (game_id, chat_group) = match self.answer_dao.find_unanswered(&user)? {
+            None => {
+                info!("No prompts to answer inline for user {}", user.id);
+                return Ok(());
+            }
+            Some(game) => game,
+        };
+
+        // Taken here rather than by the router, which does not know the chat group
+        let _guard = self.lock(&chat_group);
+        self.answer(&user, game_id, answer, Some(token), &chat_group)?;
         Ok(())
     }
 
+    /// Answers the user's next prompt in the game running in the chat group, returning the game as
+    /// it is left. The token of `prompt` has to be the next prompt's when given, since it was
+    /// picked by the user some time before
+    fn answer(
+        &self,
+        user: &User,
+        game_id: i64,
+        answer: &str,
+        prompt: Option<&str>,
+        chat_group: &ChatGroup,
+    ) -> Result<Option<State>> {
+        let state = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let state = match self.game_dao.find_running(chat_group)? {
+                None => {
+                    return Ok(None);
+                }
+                Some(mut state) => {
+                    let token = match &state {
+                        State::GatherAnswers { id, answers, .. } if *id == game_id => answers
+                            .iter()
+                            .find(|answer| answer.user == *user)
+                            .map(|answer| answer.token.clone()),
+                        _ => None,
+                    };
+                    let token = token.ok_or(DomainError::AnswerError(NoneWithToken))?;
+                    if let Some(prompt) = prompt {
+                        let next = state.next_prompt(user).map(|answer| answer.token.as_str());
+                        if next != Some(prompt) {
+                            return Err(DomainError::AnswerError(AlreadyAnswered).into());
+                        }
+                    }
+                    state.answer_prompt(&token, answer)?;
+                    state
+                }
+            };
+
+            self.game_dao.save(&state)?;
+            if let State::GatherVotes { id, answers, .. } = &state {
+                self.answer_dao.save_all(*id, answers)?;
+            }
+            Ok(Some(state))
+        })?;
+
+        if let Some(state) = &state {
+            self.update_board(chat_group, state)?;
+        }
+        Ok(state)
+    }
+
     pub fn vote(
         &self,
-        user: User,
+        user: FullUser,
         choice: Choice,
         chat_group: ChatGroup,
         callback: Callback,
     ) -> Result<()> {
-        let state = match self.game_dao.find_running(&chat_group)? {
+        let state = match self.count_vote(&user, &choice, &chat_group)? {
             None => return Ok(()),
-            Some(mut state) => state.vote(&user, &choice).map(|_| state),
-        };
-
-        let state = match state {
-            Ok(state) => state,
-            Err(err @ DomainError::VoteError(VoteError::NotInGame)) => {
+            Some(Ok(state)) => state,
+            Some(Err(err @ DomainError::VoteError(VoteError::NotInGame))) => {
                 info!(
                     "User not in game (user {:?}, chat_group {:?})",
                     &user, &chat_group
@@ -313,7 +580,7 @@ impl<'s> Controller<'s> {
                 self.chat_client.not_in_game_callback(&callback)?;
                 return Err(ControllerError::Domain(err));
             }
-            Err(err @ DomainError::VoteError(VoteError::OnlyOnce)) => {
+            Some(Err(err @ DomainError::VoteError(VoteError::OnlyOnce))) => {
                 info!(
                     "Can only vote once (user {:?}, chat_group {:?})",
                     &user, &chat_group
@@ -321,7 +588,7 @@ impl<'s> Controller<'s> {
                 self.chat_client.only_vote_once_callback(&callback)?;
                 return Err(ControllerError::Domain(err));
             }
-            Err(err @ DomainError::VoteError(VoteError::Current)) => {
+            Some(Err(err @ DomainError::VoteError(VoteError::Current))) => {
                 info!(
                     "Can only vote for current question (user {:?}, chat_group {:?})",
                     &user, &chat_group
@@ -329,7 +596,7 @@ impl<'s> Controller<'s> {
                 self.chat_client.only_current_question_callback(&callback)?;
                 return Err(ControllerError::Domain(err));
             }
-            Err(err @ DomainError::VoteError(VoteError::OwnQuestion)) => {
+            Some(Err(err @ DomainError::VoteError(VoteError::OwnQuestion))) => {
                 info!(
                     "Cannot vote for own question (user {:?}, chat_group {:?})",
                     &user, &chat_group
@@ -338,13 +605,79 @@ impl<'s> Controller<'s> {
                     .cannot_vote_own_question_callback(&callback)?;
                 return Err(ControllerError::Domain(err));
             }
-            Err(err) => return Err(ControllerError::Domain(err)),
+            Some(Err(err)) => return Err(ControllerError::Domain(err)),
         };
 
-        match &state {
+        self.chat_client.vote_callback(&callback)?;
+        self.announce_votes(&chat_group, &choice, &state)?;
+
+        Ok(())
+    }
+
+    /// Votes for the answer the user picked from their inline query, in the game being voted on
+    /// that they play in
+    pub fn vote_inline(&self, user: FullUser, choice: Choice) -> Result<()> {
+        let chat_group = match self.game_dao.find_voting(&User::from(&user))? {
+            None => {
+                info!("No game to vote in inline for user {}", user.id);
+                return Ok(());
+            }
+            Some(chat_group) => chat_group,
+        };
+
+        // Taken here rather than by the router, which does not know the chat group
+        let _guard = self.lock(&chat_group);
+        match self.count_vote(&user, &choice, &chat_group)? {
+            None => Ok(()),
+            Some(Ok(state)) => self.announce_votes(&chat_group, &choice, &state),
+            Some(Err(err)) => {
+                info!(
+                    "Inline vote not counted (user {:?}, chat_group {:?}): {:?}",
+                    &user, &chat_group, err
+                );
+                Err(ControllerError::Domain(err))
+            }
+        }
+    }
+
+    /// Votes for the answer of `choice` in the game running in the chat group, returning the game
+    /// as it is left or why the vote does not count
+    fn count_vote(
+        &self,
+        user: &FullUser,
+        choice: &Choice,
+        chat_group: &ChatGroup,
+    ) -> Result<Option<std::result::Result<State, DomainError>>> {
+        let audience = self.settings_dao.find(chat_group)?.audience;
+        // Votes arriving together each see the others, the game being locked until saved
+        transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let mut state = match self.game_dao.find_running(chat_group)? {
+                None => return Ok(None),
+                Some(state) => state,
+            };
+            let vote = match state.vote(&User::from(user), choice, audience) {
+                Ok(vote) => vote,
+                Err(err) => return Ok(Some(Err(err))),
+            };
+
+            if audience {
+                // Votes reference the user, who is not saved yet when they are not playing
+                self.user_dao.save(user)?;
+            }
+            // Not kept in the state once it moves on to the next round or the end of the game
+            self.vote_dao.save(&vote)?;
+            self.game_dao.save(&state)?;
+            Ok(Some(Ok(state)))
+        })
+    }
+
+    /// Shows who still needs to vote on the board, or sends the results once voting on the
+    /// question of `choice` has finished and moves the board on to what comes next
+    fn announce_votes(&self, chat_group: &ChatGroup, choice: &Choice, state: &State) -> Result<()> {
+        match state {
             State::GatherVotes {
                 id,
-                current: current_vote_options,
+                current,
                 votes,
                 answers,
                 ..
@@ -357,54 +690,577 @@ impl<'s> Controller<'s> {
                     );
                     err
                 })?;
-                let (answer_a, answer_b) = current_vote_options;
-                if answer_a.token != choice.token {
+                if !current.iter().any(|answer| answer.token == choice.token) {
                     self.chat_client.round_results_message(
-                        &choice,
-                        &chat_group,
-                        &votes,
-                        &answers,
+                        choice,
+                        chat_group,
+                        votes,
+                        answers,
                         &users,
+                        &self.rounds,
                     )?;
-                    self.chat_client
-                        .vote_message(&chat_group, (answer_a, answer_b))?;
                 }
+                self.update_board(chat_group, state)?;
+            }
+            State::GatherAnswers { id, answers, .. } => {
+                let votes = self.vote_dao.find(*id)?;
+                let users = self.user_dao.find(*id)?;
+                self.chat_client.round_results_message(
+                    choice,
+                    chat_group,
+                    &votes,
+                    answers,
+                    &users,
+                    &self.rounds,
+                )?;
+                self.start_round(chat_group, state)?;
             }
             State::End { id, votes, .. } => {
                 let answers = self.answer_dao.find(*id)?;
                 let users = self.user_dao.find(*id)?;
                 self.chat_client.round_results_message(
-                    &choice,
-                    &chat_group,
-                    &votes,
+                    choice,
+                    chat_group,
+                    votes,
                     &answers,
                     &users,
+                    &self.rounds,
+                )?;
+                self.update_board(chat_group, state)?;
+                self.chat_client.game_over_message(
+                    chat_group,
+                    votes,
+                    &answers,
+                    &users,
+                    &self.rounds,
                 )?;
-                self.chat_client
-                    .game_over_message(&chat_group, &votes, &answers, &users)?;
             }
             _ => {}
         }
 
-        self.game_dao.save(&state)?;
+        Ok(())
+    }
 
+    /// Warns chat groups whose games are running out of time and moves on the ones that have
+    pub fn check_timers(&self, timeouts: &Timeouts) -> Result<()> {
+        for timer in self.game_dao.find_timers()? {
+            let _guard = self.lock(&timer.chat_group);
+            if let Err(err) = self.check_timer(timer.id, timeouts) {
+                error!("Failed to handle timer for game {}: {:?}", timer.id, err);
+            }
+        }
         Ok(())
     }
 
-    pub fn end(&self, chat_group: ChatGroup) -> Result<()> {
-        let state = match self.game_dao.find_running(&chat_group)? {
+    fn check_timer(&self, id: i64, timeouts: &Timeouts) -> Result<()> {
+        // The game may have moved on while waiting for the lock
+        let timer = match self.game_dao.find_timer(id)? {
             None => return Ok(()),
-            Some(mut state) => {
-                state.end()?;
-                state
+            Some(timer) => timer,
+        };
+        match timeouts.action(&timer) {
+            Action::Wait => Ok(()),
+            Action::Warn(threshold) => {
+                self.warn_timer(&timer, timeouts.remaining(&timer), threshold)
+            }
+            Action::Expire => self.expire_timer(&timer),
+        }
+    }
+
+    fn warn_timer(&self, timer: &Timer, remaining: i64, threshold: i64) -> Result<()> {
+        let Timer {
+            id,
+            chat_group,
+            phase,
+            ..
+        } = timer;
+        info!("Warning game {} with {}s remaining", id, remaining);
+        self.game_dao.save_warning(*id, threshold)?;
+        // Who still has to answer or vote is already on the board
+        self.chat_client
+            .time_warning_message(chat_group, *phase, remaining)?;
+        Ok(())
+    }
+
+    fn expire_timer(&self, timer: &Timer) -> Result<()> {
+        let Timer {
+            id,
+            chat_group,
+            phase,
+            ..
+        } = timer;
+        // Saved before telling the chat, so that a failure there does not expire it again
+        let expired = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let mut state = match self.game_dao.find_running(chat_group)? {
+                Some(state) if state.id() == *id => state,
+                _ => return Ok(None),
+            };
+
+            info!("Time is up for game {} in {:?}", id, phase);
+            let choice = match (phase, &state) {
+                (Phase::Joining, State::GatherUsers { .. }) => {
+                    match self.begun(chat_group, &state)? {
+                        Ok(begun) => state = begun,
+                        Err(err) => {
+                            info!("Could not begin expired game {}: {:?}", id, err);
+                            state.end()?;
+                        }
+                    }
+                    None
+                }
+                (Phase::Answering, State::GatherAnswers { .. }) => {
+                    // The answers given so far are already saved
+                    state.force_answers()?;
+                    None
+                }
+                (Phase::Voting, State::GatherVotes { current, .. }) => {
+                    let choice = Choice {
+                        token: current[0].token.clone(),
+                    };
+                    state.force_votes()?;
+                    Some(choice)
+                }
+                _ => {
+                    warn!("Timer for game {} does not match its state", id);
+                    return Ok(None);
+                }
+            };
+            self.game_dao.save(&state)?;
+            Ok(Some((state, choice)))
+        })?;
+
+        match expired {
+            None => {}
+            Some((state, Some(choice))) => self.announce_votes(chat_group, &choice, &state)?,
+            Some((state @ State::GatherAnswers { .. }, None)) => {
+                self.start_round(chat_group, &state)?
+            }
+            Some((state @ State::End { .. }, None)) => {
+                self.update_board(chat_group, &state)?;
+                self.chat_client.game_expired_message(chat_group)?;
+            }
+            Some((state, None)) => self.update_board(chat_group, &state)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn end(&self, user: User, chat_group: ChatGroup) -> Result<()> {
+        let ended = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let mut state = match self.game_dao.find_running(&chat_group)? {
+                None => return Ok(None),
+                Some(state) => state,
+            };
+
+            if !self.authorize(&user, &chat_group, &state, Command::End)? {
+                return Ok(None);
+            }
+
+            state.end()?;
+            self.game_dao.save(&state)?;
+            Ok(Some(state))
+        })?;
+
+        if let Some(state) = ended {
+            self.update_board(&chat_group, &state)?;
+        }
+        Ok(())
+    }
+
+    /// Takes the user out of the running game
+    pub fn leave(&self, user: FullUser, chat_group: ChatGroup) -> Result<()> {
+        self.remove_player(&chat_group, &user)
+    }
+
+    /// Takes the player the argument names out of the running game
+    pub fn kick(&self, user: User, chat_group: ChatGroup, argument: Option<String>) -> Result<()> {
+        let state = match self.game_dao.find_running(&chat_group)? {
+            None => {
+                self.chat_client.game_does_not_exist_error(&chat_group)?;
+                return Ok(());
+            }
+            Some(state) => state,
+        };
+
+        if !self.authorize(&user, &chat_group, &state, Command::Kick)? {
+            return Ok(());
+        }
+
+        let player = match argument.as_deref().and_then(|name| state.find_player(name)) {
+            None => {
+                info!(
+                    "No player to kick for {:?} (chat_group {:?})",
+                    argument, &chat_group
+                );
+                self.chat_client.player_not_found_error(&chat_group)?;
+                return Ok(());
+            }
+            Some(player) => player.clone(),
+        };
+        self.remove_player(&chat_group, &player)
+    }
+
+    /// Takes the player out of the game, moving it on when it was only waiting for them
+    fn remove_player(&self, chat_group: &ChatGroup, player: &FullUser) -> Result<()> {
+        let user = User::from(player);
+        // The game stays locked until saved, so updates arriving meanwhile see the player gone
+        let left = transaction::run(self.transaction_dao.as_ref(), || -> Result<_> {
+            let mut state = match self.game_dao.find_running(chat_group)? {
+                None => return Ok(None),
+                Some(state) => state,
+            };
+            // Players answering privately who were done are sent the prompts handed to them
+            let done: Vec<User> = self
+                .user_dao
+                .find_private(state.id())?
+                .into_iter()
+                .filter(|other| state.next_prompt(other).is_none())
+                .collect();
+            let current = match &state {
+                State::GatherVotes { current, .. } => Some(Choice {
+                    token: current[0].token.clone(),
+                }),
+                _ => None,
+            };
+            if let Err(err) = state.leave_game(&user) {
+                return Ok(Some(Err(err)));
+            }
+
+            self.user_dao.leave(state.id(), &user)?;
+            self.game_dao.save(&state)?;
+            if let State::GatherAnswers { id, answers, .. }
+            | State::GatherVotes { id, answers, .. } = &state
+            {
+                self.answer_dao.save_all(*id, answers)?;
+            }
+            Ok(Some(Ok((state, done, current))))
+        })?;
+
+        let (state, done, current) = match left {
+            None => {
+                self.chat_client.game_does_not_exist_error(chat_group)?;
+                return Ok(());
+            }
+            Some(Ok(left)) => left,
+            Some(Err(DomainError::NotPlaying)) => {
+                info!(
+                    "Not playing in the game (user {:?}, chat_group {:?})",
+                    &user, chat_group
+                );
+                self.chat_client.not_playing_error(chat_group)?;
+                return Ok(());
+            }
+            Some(Err(err)) => return Err(ControllerError::Domain(err)),
+        };
+
+        info!("User {} left game {}", user.id, state.id());
+        self.chat_client.left_game_message(chat_group, player)?;
+        match &current {
+            Some(choice) => self.announce_votes(chat_group, choice, &state)?,
+            None => self.update_board(chat_group, &state)?,
+        }
+
+        match &state {
+            // The results were announced with the votes when the game was being voted on
+            State::End { id, .. } if current.is_none() => {
+                self.chat_client.too_few_players_message(chat_group)?;
+                let votes = self.vote_dao.find(*id)?;
+                if !votes.is_empty() {
+                    let answers = self.answer_dao.find(*id)?;
+                    let users = self.user_dao.find(*id)?;
+                    self.chat_client.game_over_message(
+                        chat_group,
+                        &votes,
+                        &answers,
+                        &users,
+                        &self.rounds,
+                    )?;
+                }
+            }
+            State::End { id, .. }
+                if game::players(&self.user_dao.find(*id)?, &self.answer_dao.find(*id)?)
+                    .count()
+                    < 3 =>
+            {
+                self.chat_client.too_few_players_message(chat_group)?;
+            }
+            State::GatherAnswers { .. } if current.is_none() => {
+                for other in done
+                    .iter()
+                    .filter(|other| state.next_prompt(other).is_some())
+                {
+                    if let Err(err) = self.send_prompt(other, &state) {
+                        error!("Failed to send prompt to user {}: {:?}", other.id, err);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn permission(
+        &self,
+        user: User,
+        chat_group: ChatGroup,
+        argument: Option<String>,
+    ) -> Result<()> {
+        let mut settings = self.settings_dao.find(&chat_group)?;
+
+        let argument = match argument {
+            None => {
+                self.chat_client
+                    .permissions_message(&chat_group, &settings)?;
+                return Ok(());
+            }
+            Some(argument) => argument,
+        };
+
+        if !self.chat_client.is_admin(&chat_group, &user)? {
+            info!(
+                "Non-admin attempted to change permissions (user {:?}, chat_group {:?})",
+                &user, &chat_group
+            );
+            self.chat_client.admin_required_error(&chat_group)?;
+            return Ok(());
+        }
+
+        let mut arguments = argument.split_whitespace();
+        let command = arguments.next().and_then(Command::parse);
+        let role = arguments.next().and_then(Role::parse);
+        match (command, role) {
+            (Some(command), Some(role)) => {
+                settings.set_role(command, role);
+                self.settings_dao.save(&chat_group, &settings)?;
+            }
+            _ => info!("Invalid permission argument: {}", argument),
+        }
+
+        self.chat_client
+            .permissions_message(&chat_group, &settings)?;
+        Ok(())
+    }
+
+    /// Shows or changes whether people not playing can vote, the argument being `on` or `off`
+    pub fn audience(
+        &self,
+        user: User,
+        chat_group: ChatGroup,
+        argument: Option<String>,
+    ) -> Result<()> {
+        let mut settings = self.settings_dao.find(&chat_group)?;
+
+        let argument = match argument {
+            None => {
+                self.chat_client.audience_message(&chat_group, &settings)?;
+                return Ok(());
             }
+            Some(argument) => argument,
         };
 
-        self.game_dao.save(&state)?;
+        if !self.chat_client.is_admin(&chat_group, &user)? {
+            info!(
+                "Non-admin attempted to change audience voting (user {:?}, chat_group {:?})",
+                &user, &chat_group
+            );
+            self.chat_client.admin_required_error(&chat_group)?;
+            return Ok(());
+        }
+
+        match argument.as_str() {
+            "on" | "off" => {
+                settings.audience = argument == "on";
+                self.settings_dao.save(&chat_group, &settings)?;
+            }
+            _ => info!("Invalid audience argument: {}", argument),
+        }
+
+        self.chat_client.audience_message(&chat_group, &settings)?;
         Ok(())
     }
 
-    pub fn generate_url(&self, ChatGroup(chat_group): &ChatGroup, token: &str) -> String {
-        format!("{}/?group_id={}&token={}", self.app_url, chat_group, token)
+    /// Shows or changes whether people can join a game that has begun, the argument being `on` or
+    /// `off`
+    pub fn late_join(
+        &self,
+        user: User,
+        chat_group: ChatGroup,
+        argument: Option<String>,
+    ) -> Result<()> {
+        let mut settings = self.settings_dao.find(&chat_group)?;
+
+        let argument = match argument {
+            None => {
+                self.chat_client.late_join_message(&chat_group, &settings)?;
+                return Ok(());
+            }
+            Some(argument) => argument,
+        };
+
+        if !self.chat_client.is_admin(&chat_group, &user)? {
+            info!(
+                "Non-admin attempted to change late joining (user {:?}, chat_group {:?})",
+                &user, &chat_group
+            );
+            self.chat_client.admin_required_error(&chat_group)?;
+            return Ok(());
+        }
+
+        match argument.as_str() {
+            "on" | "off" => {
+                settings.late_join = argument == "on";
+                self.settings_dao.save(&chat_group, &settings)?;
+            }
+            _ => info!("Invalid late join argument: {}", argument),
+        }
+
+        self.chat_client.late_join_message(&chat_group, &settings)?;
+        Ok(())
+    }
+
+    pub fn packs(&self, chat_group: ChatGroup) -> Result<()> {
+        let packs = self.pack_dao.find_all(&chat_group)?;
+        self.chat_client.packs_message(&chat_group, &packs)?;
+        Ok(())
+    }
+
+    /// Turns a question pack on or off for the chat group, the argument being `<name> <on|off>`
+    pub fn pack(&self, user: User, chat_group: ChatGroup, argument: Option<String>) -> Result<()> {
+        let argument = match argument {
+            None => return self.packs(chat_group),
+            Some(argument) => argument,
+        };
+
+        if !self.chat_client.is_admin(&chat_group, &user)? {
+            info!(
+                "Non-admin attempted to change packs (user {:?}, chat_group {:?})",
+                &user, &chat_group
+            );
+            self.chat_client.admin_required_error(&chat_group)?;
+            return Ok(());
+        }
+
+        // Pack names may contain spaces so the setting is the last word
+        let enabled = match argument.rsplit_once(' ') {
+            Some((name, "on")) => Some((name.trim(), true)),
+            Some((name, "off")) => Some((name.trim(), false)),
+            _ => None,
+        };
+        let packs = self.pack_dao.find_all(&chat_group)?;
+        let pack = enabled.and_then(|(name, enabled)| {
+            packs
+                .iter()
+                .find(|pack| pack.name.eq_ignore_ascii_case(name))
+                .map(|pack| (pack, enabled))
+        });
+        match pack {
+            Some((pack, enabled)) => {
+                self.pack_dao.save_enabled(&chat_group, pack.id, enabled)?;
+            }
+            None => info!("Invalid pack argument: {}", argument),
+        }
+
+        self.packs(chat_group)
+    }
+
+    /// Queues a prompt suggested by a player for the admins of the chat group to review
+    pub fn suggest(
+        &self,
+        user: FullUser,
+        chat_group: ChatGroup,
+        argument: Option<String>,
+    ) -> Result<()> {
+        let text = match argument.as_deref().and_then(suggestion::prompt) {
+            None => {
+                self.chat_client.invalid_suggestion_error(&chat_group)?;
+                return Ok(());
+            }
+            Some(text) => text,
+        };
+
+        self.user_dao.save(&user)?;
+        let suggestion = self.suggestion_dao.save(&chat_group, &user, &text)?;
+        self.chat_client
+            .suggestion_message(&chat_group, &suggestion)?;
+        Ok(())
+    }
+
+    /// Approves or rejects a suggested prompt, the argument being the id of the suggestion
+    pub fn review_suggestion(
+        &self,
+        user: User,
+        chat_group: ChatGroup,
+        callback: Callback,
+        argument: &str,
+        approve: bool,
+    ) -> Result<()> {
+        let id = match argument.parse::<i64>() {
+            Ok(id) => id,
+            Err(err) => {
+                error!("Invalid suggestion id: {}, {}", argument, err);
+                return Ok(());
+            }
+        };
+
+        if !self.chat_client.is_admin(&chat_group, &user)? {
+            info!(
+                "Non-admin attempted to review suggestion {} (user {:?}, chat_group {:?})",
+                id, &user, &chat_group
+            );
+            self.chat_client.review_admin_required_callback(&callback)?;
+            return Ok(());
+        }
+
+        let suggestion = if approve {
+            self.suggestion_dao.approve(&chat_group, id)?
+        } else {
+            self.suggestion_dao.reject(&chat_group, id)?
+        };
+        match suggestion {
+            None => self
+                .chat_client
+                .suggestion_already_reviewed_callback(&callback)?,
+            Some(suggestion) => {
+                self.chat_client.update_suggestion_message(
+                    &chat_group,
+                    &suggestion,
+                    approve,
+                    &callback,
+                )?;
+                self.chat_client
+                    .suggestion_reviewed_callback(&callback, approve)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the user may use the command, telling the chat group when they may not
+    fn authorize(
+        &self,
+        user: &User,
+        chat_group: &ChatGroup,
+        state: &State,
+        command: Command,
+    ) -> Result<bool> {
+        let role = self.settings_dao.find(chat_group)?.role(command);
+        if role == Role::Anyone {
+            return Ok(true);
+        }
+
+        let is_host = self.game_dao.find_host(state.id())?.as_ref() == Some(user);
+        let is_admin =
+            !(role == Role::Host && is_host) && self.chat_client.is_admin(chat_group, user)?;
+
+        if role.is_permitted(is_host, is_admin) {
+            return Ok(true);
+        }
+
+        info!(
+            "Permission denied for {:?} (user {:?}, chat_group {:?})",
+            command, user, chat_group
+        );
+        self.chat_client
+            .permission_denied_error(chat_group, command, role)?;
+        Ok(false)
     }
 }
@@ -1,12 +1,26 @@
-use crate::game::DomainError::{AlreadyInGame, AtLeastThreePlayers, InvalidTransition};
+use crate::game::DomainError::{
+    AlreadyInGame, AtLeastThreePlayers, InvalidTransition, NotEnoughQuestions, NotPlaying,
+};
 use crate::game::VoteError::{Current, NotInGame, OnlyOnce, OwnQuestion};
 use log::error;
 use uuid::Uuid;
 
+use crate::game::round::Round;
 use crate::game::AnswerError::{AlreadyAnswered, NoneWithToken};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 
+pub mod pack;
+pub mod round;
+pub mod scoring;
+pub mod selection;
+pub mod settings;
+pub mod suggestion;
+pub mod timer;
+
+/// Shown in place of a response for players that ran out of time
+pub const FORFEIT_RESPONSE: &str = "(no answer)";
+
 #[derive(Debug)]
 pub enum VoteError {
     NotInGame,
@@ -26,6 +40,10 @@ pub enum DomainError {
     AtLeastThreePlayers,
     InvalidTransition,
     AlreadyInGame,
+    /// The user is not one of the players of the game
+    NotPlaying,
+    /// The enabled question packs do not have enough questions for every round
+    NotEnoughQuestions,
     AnswerError(AnswerError),
     VoteError(VoteError),
 }
@@ -42,15 +60,19 @@ pub enum State {
         id: i64,
         users: Vec<FullUser>,
     },
+    /// Players answer the prompts of `round`, the answers holding the prompts of every round
     GatherAnswers {
         id: i64,
+        round: i64,
         answers: Vec<Answer>,
         users: Vec<FullUser>,
     },
+    /// Players vote between the answers to the current prompt of `round`
     GatherVotes {
         id: i64,
+        round: i64,
         answers: Vec<Answer>,
-        current: (Answer, Answer),
+        current: Vec<Answer>,
         votes: Vec<Vote>,
         users: Vec<FullUser>,
     },
@@ -72,6 +94,14 @@ pub struct Answer {
     pub question: Question,
     pub token: String,
     pub response: Option<String>,
+    /// The round the prompt is played in, numbered from 1
+    pub round: i64,
+}
+
+impl Answer {
+    pub fn response_or_forfeit(&self) -> &str {
+        self.response.as_deref().unwrap_or(FORFEIT_RESPONSE)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -83,15 +113,19 @@ pub struct Choice {
 pub struct Vote {
     pub token: String,
     pub user: User,
+    /// Cast by someone watching the game rather than a player
+    pub audience: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ChatGroup(pub i64);
 
 #[derive(Debug)]
 pub struct Callback {
     pub id: i64,
     pub message_id: Option<i64>,
+    /// Needed by backends that answer a callback through a url rather than its id
+    pub token: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -111,6 +145,14 @@ impl PartialEq for FullUser {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Score {
+    pub user: FullUser,
+    pub points: i64,
+    pub wins: i64,
+    pub games: i64,
+}
+
 #[derive(Debug, Clone)]
 pub struct FullUser {
     pub id: i64,
@@ -153,7 +195,9 @@ impl State {
         }
     }
 
-    pub fn begin_game(&self, questions: &[Question]) -> Result<State> {
+    /// Hands out the prompts for every round, `questions` being drawn in the order they are
+    /// played
+    pub fn begin_game(&self, questions: &[Question], rounds: &[Round]) -> Result<State> {
         let (id, users) = match self {
             State::GatherUsers { id, users } => (id, users),
             _ => return Err(InvalidTransition),
@@ -163,36 +207,53 @@ impl State {
             return Err(AtLeastThreePlayers);
         }
 
+        if rounds.is_empty() || questions.len() < round::question_count(rounds, users.len()) {
+            error!(
+                "Not enough questions for game {}: {} for {:?}",
+                id,
+                questions.len(),
+                rounds
+            );
+            return Err(NotEnoughQuestions);
+        }
+
+        let mut questions = questions.iter();
         let mut answers = vec![];
-        for (i, question) in questions.iter().enumerate() {
-            let user = users.get(i).unwrap();
-            answers.push(Answer {
-                user: user.into(),
-                question: question.clone(),
-                token: generate_token(),
-                response: None,
-            });
-            let user = match users.get(i + 1) {
-                None => users.get(0).unwrap(),
-                Some(user) => user,
-            };
-            answers.push(Answer {
-                user: user.into(),
-                question: question.clone(),
-                token: generate_token(),
-                response: None,
-            });
+        for (i, round) in rounds.iter().enumerate() {
+            let number = i as i64 + 1;
+            match round {
+                Round::HeadToHead { .. } => {
+                    // Shifting the pairs each round gives players a new opponent when there are
+                    // enough of them
+                    let offset = 1 + i % (users.len() - 1);
+                    for (j, user) in users.iter().enumerate() {
+                        let question = questions.next().unwrap();
+                        let opponent = &users[(j + offset) % users.len()];
+                        answers.push(new_answer(user, question, number));
+                        answers.push(new_answer(opponent, question, number));
+                    }
+                }
+                Round::LastLash { .. } => {
+                    let question = questions.next().unwrap();
+                    for user in users {
+                        answers.push(new_answer(user, question, number));
+                    }
+                }
+            }
         }
 
         let state = State::GatherAnswers {
             id: *id,
+            round: 1,
             answers,
             users: users.to_owned(),
         };
         Ok(state)
     }
 
-    pub fn join_game(&mut self, user: FullUser) -> Result<()> {
+    /// Adds the player to the game, or once it has begun to the players who vote when `late` is
+    /// allowed
+    pub fn join_game(&mut self, user: FullUser, late: bool) -> Result<()> {
         match self {
             State::GatherUsers { users, .. } => {
                 if users.contains(&user) {
@@ -201,30 +262,141 @@ impl State {
                 users.push(user);
                 Ok(())
             }
+            State::GatherAnswers { users, .. } | State::GatherVotes { users, .. } if late => {
+                if users.contains(&user) {
+                    return Err(AlreadyInGame);
+                }
+                users.push(user);
+                Ok(())
+            }
             _ => Err(InvalidTransition),
         }
     }
 
+    /// Takes the player out of the game. The prompts they have not answered are handed to the
+    /// other players and the answers they gave are kept, unless voting has begun on their round,
+    /// when those still to be voted on are forfeited. The game ends once fewer than three players
+    /// with prompts are left, however many joined late, without the votes of earlier rounds when
+    /// it was gathering answers as they are not kept in that state
+    pub fn leave_game(&mut self, user: &User) -> Result<()> {
+        let users = match self {
+            State::GatherUsers { users, .. }
+            | State::GatherAnswers { users, .. }
+            | State::GatherVotes { users, .. } => users,
+            _ => return Err(InvalidTransition),
+        };
+        if !is_playing(user, users) {
+            return Err(NotPlaying);
+        }
+        users.retain(|player| player.id != user.id);
+
+        let too_few = match self {
+            State::GatherAnswers { users, answers, .. }
+            | State::GatherVotes { users, answers, .. } => players(users, answers).count() < 3,
+            State::GatherUsers { users, .. } => users.len() < 3,
+            _ => false,
+        };
+        match self {
+            State::GatherAnswers { id, .. } if too_few => {
+                *self = State::End {
+                    id: *id,
+                    votes: vec![],
+                };
+            }
+            State::GatherVotes { id, votes, .. } if too_few => {
+                *self = State::End {
+                    id: *id,
+                    votes: votes.clone(),
+                };
+            }
+            State::GatherAnswers {
+                id,
+                round,
+                answers,
+                users,
+            } => {
+                hand_over(user, answers, users, *round);
+
+                if all_answers_are_in(answers, users, *round) {
+                    *self = State::GatherVotes {
+                        id: *id,
+                        round: *round,
+                        answers: answers.clone(),
+                        users: users.to_owned(),
+                        votes: vec![],
+                        current: next(answers, *round, None)
+                            .expect("A round should have at least one question"),
+                    };
+                }
+            }
+            State::GatherVotes {
+                id,
+                round,
+                answers,
+                current,
+                votes,
+                users,
+            } => {
+                // Questions already voted on keep their scores
+                let remaining: Vec<i64> = questions(answers, *round)
+                    .into_iter()
+                    .skip_while(|question_id| *question_id != current[0].question.id)
+                    .collect();
+                for answer in answers.iter_mut().chain(current.iter_mut()) {
+                    if answer.user == *user
+                        && answer.round == *round
+                        && remaining.contains(&answer.question.id)
+                    {
+                        answer.response = None;
+                    }
+                }
+                hand_over(user, answers, users, *round + 1);
+
+                if current_votes_are_in(current, users, votes) {
+                    *self = next_question(*id, *round, answers, &current[0], votes, users);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub fn answer_prompt(&mut self, token: &str, answer: &str) -> Result<()> {
-        let (id, answers, users) = match self {
-            State::GatherAnswers { id, answers, users } => (id, answers, users),
+        let (id, round, answers, users) = match self {
+            State::GatherAnswers {
+                id,
+                round,
+                answers,
+                users,
+            } => (id, round, answers, users),
             _ => return Err(InvalidTransition),
         };
 
-        answer_prompt(token, answer, answers)?;
+        // Players who left cannot answer the prompts they forfeited
+        if !answers
+            .iter()
+            .any(|answer| answer.token == token && is_playing(&answer.user, users))
+        {
+            error!("No answer of a player found for token: {}", token);
+            return Err(DomainError::AnswerError(NoneWithToken));
+        }
+        answer_prompt(token, answer, answers, *round)?;
 
-        if all_answers_are_in(&answers) {
+        if all_answers_are_in(answers, users, *round) {
             *self = State::GatherVotes {
                 id: *id,
+                round: *round,
                 answers: answers.clone(),
                 users: users.to_owned(),
                 votes: vec![],
-                current: next(answers, &[]),
+                current: next(answers, *round, None)
+                    .expect("A round should have at least one question"),
             };
             Ok(())
         } else {
             *self = State::GatherAnswers {
                 id: *id,
+                round: *round,
                 answers: answers.clone(),
                 users: users.to_owned(),
             };
@@ -232,54 +404,84 @@ impl State {
         }
     }
 
-    pub fn vote(&mut self, user: &User, choice: &Choice) -> Result<()> {
-        let (id, answers, current, votes, users) = match self {
+    /// Counts the vote of a player, or of someone watching when `audience` voting is allowed
+    /// Returns the vote cast, which has to be saved on its own as the state no longer holds it
+    /// once the last vote of a round is in
+    pub fn vote(&mut self, user: &User, choice: &Choice, audience: bool) -> Result<Vote> {
+        let (id, round, answers, current, votes, users) = match self {
             State::GatherVotes {
                 id,
+                round,
                 answers,
                 current,
                 votes,
                 users,
-            } => (id, answers, current, votes, users),
+            } => (id, round, answers, current, votes, users),
             _ => return Err(InvalidTransition),
         };
 
-        let (answer_a, answer_b) = current;
-
-        if answer_a.token != choice.token && answer_b.token != choice.token {
+        if !current.iter().any(|answer| answer.token == choice.token) {
             return Err(DomainError::VoteError(Current));
         }
-        if already_voted(user, (&answer_a, &answer_b), votes) {
+        if already_voted(user, current, votes) {
             return Err(DomainError::VoteError(OnlyOnce));
         }
-        if not_in_game(user, answers) {
+        let spectator = !is_playing(user, users);
+        if spectator && !audience {
             return Err(DomainError::VoteError(NotInGame));
         }
-        if own_question(user, (&answer_a, &answer_b)) {
+        if own_question(user, current, choice) {
             return Err(DomainError::VoteError(OwnQuestion));
         }
 
-        vote(user, choice, votes);
+        let vote = vote(user, choice, spectator, votes);
 
-        if all_votes_are_in(answers, votes) {
-            *self = State::End {
-                id: *id,
-                votes: votes.to_owned(),
-            };
-            return Ok(());
+        if current_votes_are_in(current, users, votes) {
+            *self = next_question(*id, *round, answers, &current[0], votes, users);
         }
 
-        if current_votes_are_in(&(answer_a, answer_b), answers, votes) {
-            *self = State::GatherVotes {
-                id: *id,
-                answers: answers.to_owned(),
-                current: next(answers, votes),
-                votes: votes.to_owned(),
-                users: users.to_owned(),
-            };
-            return Ok(());
-        }
+        Ok(vote)
+    }
+
+    /// Moves on to voting, leaving the prompts that have not been answered without a response
+    pub fn force_answers(&mut self) -> Result<()> {
+        let (id, round, answers, users) = match self {
+            State::GatherAnswers {
+                id,
+                round,
+                answers,
+                users,
+            } => (id, round, answers, users),
+            _ => return Err(InvalidTransition),
+        };
 
+        *self = State::GatherVotes {
+            id: *id,
+            round: *round,
+            answers: answers.clone(),
+            users: users.to_owned(),
+            votes: vec![],
+            current: next(answers, *round, None)
+                .expect("A round should have at least one question"),
+        };
+        Ok(())
+    }
+
+    /// Closes the current question with the votes it has received so far
+    pub fn force_votes(&mut self) -> Result<()> {
+        let (id, round, answers, current, votes, users) = match self {
+            State::GatherVotes {
+                id,
+                round,
+                answers,
+                current,
+                votes,
+                users,
+            } => (id, round, answers, current, votes, users),
+            _ => return Err(InvalidTransition),
+        };
+
+        *self = next_question(*id, *round, answers, &current[0], votes, users);
         Ok(())
     }
 
@@ -304,13 +506,12 @@ impl State {
     pub fn remaining_voters(&self) -> Result<Vec<&FullUser>> {
         match self {
             State::GatherVotes {
-                current: (answer_a, answer_b),
-                answers,
+                current,
                 votes,
                 users,
                 ..
             } => {
-                let user_ids = remaining_votes(&(answer_a, answer_b), answers, votes);
+                let user_ids = remaining_votes(current, users, votes);
                 let users = user_ids
                     .iter()
                     .map(|user_id| users.iter().find(|user| user.id.eq(user_id)))
@@ -325,12 +526,64 @@ impl State {
         }
     }
 
+    /// The user's next prompt to answer in the current round, if any are left
+    pub fn next_prompt(&self, user: &User) -> Option<&Answer> {
+        match self {
+            State::GatherAnswers {
+                round,
+                answers,
+                users,
+                ..
+            } if is_playing(user, users) => answers.iter().find(|answer| {
+                answer.user == *user && answer.round == *round && answer.response.is_none()
+            }),
+            _ => None,
+        }
+    }
+
+    /// The player the name refers to, being their username with or without the @, a mention
+    /// holding their id or else their first name
+    pub fn find_player(&self, name: &str) -> Option<&FullUser> {
+        let users = match self {
+            State::GatherUsers { users, .. }
+            | State::GatherAnswers { users, .. }
+            | State::GatherVotes { users, .. } => users,
+            _ => return None,
+        };
+        let name = name.trim();
+        let mention = name
+            .trim_start_matches("<@")
+            .trim_start_matches('!')
+            .trim_end_matches('>');
+        let username = name.trim_start_matches('@');
+        users
+            .iter()
+            .find(|user| {
+                user.id.to_string() == mention
+                    || user
+                        .username
+                        .as_ref()
+                        .is_some_and(|other| other.eq_ignore_ascii_case(username))
+            })
+            .or_else(|| {
+                users
+                    .iter()
+                    .find(|user| user.first_name.as_deref() == Some(name))
+            })
+    }
+
     pub fn remaining_answerers(&self) -> Result<Vec<&FullUser>> {
         match self {
-            State::GatherAnswers { answers, users, .. } => {
+            State::GatherAnswers {
+                round,
+                answers,
+                users,
+                ..
+            } => {
                 let user_ids: HashSet<i64> = answers
                     .iter()
-                    .filter(|answer| answer.response.is_none())
+                    .filter(|answer| answer.round == *round && answer.response.is_none())
+                    .filter(|answer| is_playing(&answer.user, users))
                     .map(|answer| answer.user.id)
                     .collect();
                 let users = user_ids
@@ -348,116 +601,184 @@ impl State {
     }
 }
 
-fn next(answers: &[Answer], votes: &[Vote]) -> (Answer, Answer) {
-    let mut voted = HashMap::new();
-    for answer in answers {
-        voted.insert(&answer.token, answer.question.id);
+/// Moves on to the question after `current`, the next round once every question of the round
+/// has been voted on and the end of the game after the last round
+fn next_question(
+    id: i64,
+    round: i64,
+    answers: &[Answer],
+    current: &Answer,
+    votes: &[Vote],
+    users: &[FullUser],
+) -> State {
+    if let Some(current) = next(answers, round, Some(current.question.id)) {
+        return State::GatherVotes {
+            id,
+            round,
+            answers: answers.to_owned(),
+            current,
+            votes: votes.to_owned(),
+            users: users.to_owned(),
+        };
+    }
+
+    if answers.iter().any(|answer| answer.round == round + 1) {
+        return State::GatherAnswers {
+            id,
+            round: round + 1,
+            answers: answers.to_owned(),
+            users: users.to_owned(),
+        };
+    }
+
+    State::End {
+        id,
+        votes: votes.to_owned(),
+    }
+}
+
+/// The questions of a round in the order they were handed out, which is the order they are voted
+/// on
+fn questions(answers: &[Answer], round: i64) -> Vec<i64> {
+    let mut questions: Vec<i64> = vec![];
+    for answer in answers.iter().filter(|answer| answer.round == round) {
+        if !questions.contains(&answer.question.id) {
+            questions.push(answer.question.id);
+        }
     }
-    let voted: HashSet<i64> = votes
+    questions
+}
+
+/// The answers to the question that follows `current` in the round, or to its first question
+fn next(answers: &[Answer], round: i64, current: Option<i64>) -> Option<Vec<Answer>> {
+    let questions = questions(answers, round);
+    let answers: Vec<&Answer> = answers
         .iter()
-        .map(|vote| {
-            *voted
-                .get(&vote.token)
-                .expect("vote should be mapped to answer")
-        })
+        .filter(|answer| answer.round == round)
         .collect();
 
-    let answer_a = answers
-        .iter()
-        .find(|answer| !voted.contains(&answer.question.id))
-        .expect("There should be an answer that hasn't been voted yet");
-    let answer_b = answers
-        .iter()
-        .find(|answer| answer.question.id == answer_a.question.id && answer.token != answer_a.token)
-        .expect("Every answer should have an answer for the other player");
-
-    (answer_a.clone(), answer_b.clone())
-}
-
-fn remaining_votes(current: &(&Answer, &Answer), answers: &[Answer], votes: &[Vote]) -> Vec<i64> {
-    let (
-        Answer {
-            token: token_a,
-            user: User { id: user_id_a },
-            ..
-        },
-        Answer {
-            token: token_b,
-            user: User { id: user_id_b },
-            ..
-        },
-    ) = current;
-    let users: HashSet<i64> = answers.iter().map(|answer| answer.user.id).collect();
+    let question_id = match current {
+        None => questions.first(),
+        Some(current) => questions
+            .iter()
+            .skip_while(|question_id| **question_id != current)
+            .nth(1),
+    }?;
+
+    Some(
+        answers
+            .into_iter()
+            .filter(|answer| answer.question.id == *question_id)
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Only a last lash has more than two answers to a prompt
+fn is_last_lash(current: &[Answer]) -> bool {
+    current.len() > 2
+}
+
+/// The players yet to vote on the current question, which includes those who joined late
+fn remaining_votes(current: &[Answer], users: &[FullUser], votes: &[Vote]) -> Vec<i64> {
+    let users: HashSet<i64> = users.iter().map(|user| user.id).collect();
     let voters: HashSet<i64> = votes
         .iter()
-        .filter(|vote| vote.token.eq(token_a) || vote.token.eq(token_b))
+        .filter(|vote| current.iter().any(|answer| answer.token == vote.token))
         .map(|vote| vote.user.id)
         .collect();
+    // Everyone answers a last lash so no one sits out its vote
+    let answerers: HashSet<i64> = if is_last_lash(current) {
+        HashSet::new()
+    } else {
+        current.iter().map(|answer| answer.user.id).collect()
+    };
 
     users
         .difference(&voters)
-        .filter(|user_id| !user_id_a.eq(*user_id) && !user_id_b.eq(*user_id))
+        .filter(|user_id| !answerers.contains(user_id))
         .map(|user_id| user_id.to_owned())
         .collect()
 }
 
-fn current_votes_are_in(current: &(&Answer, &Answer), answers: &[Answer], votes: &[Vote]) -> bool {
-    remaining_votes(current, answers, votes).is_empty()
+fn current_votes_are_in(current: &[Answer], users: &[FullUser], votes: &[Vote]) -> bool {
+    remaining_votes(current, users, votes).is_empty()
 }
 
-fn all_votes_are_in(answers: &[Answer], votes: &[Vote]) -> bool {
-    let mut map = HashMap::new();
-    for answer in answers {
-        map.insert(&answer.token, answer.question.id);
-    }
-
-    let voted_questions: HashSet<i64> = votes
+/// Players cannot vote on a head to head they answered, nor for their own answer in a last lash
+fn own_question(user: &User, current: &[Answer], choice: &Choice) -> bool {
+    current
         .iter()
-        .map(|vote| {
-            let message = &format!("vote found that does not match any answers: {:?}", &vote);
-            *map.get(&vote.token).expect(message)
-        })
-        .collect();
-    let all_questions: HashSet<i64> = answers.iter().map(|answer| answer.question.id).collect();
+        .filter(|answer| !is_last_lash(current) || answer.token == choice.token)
+        .any(|answer| answer.user.id == user.id)
+}
 
-    voted_questions.len() == all_questions.len()
+/// The players who were handed prompts, leaving out those who joined late to only vote
+pub fn players<'a>(
+    users: &'a [FullUser],
+    answers: &'a [Answer],
+) -> impl Iterator<Item = &'a FullUser> {
+    users
+        .iter()
+        .filter(move |user| answers.iter().any(|answer| answer.user.id == user.id))
 }
 
-fn own_question(user: &User, current: (&Answer, &Answer)) -> bool {
-    let (
-        Answer {
-            user: User { id: user_id_a },
-            ..
-        },
-        Answer {
-            user: User { id: user_id_b },
-            ..
-        },
-    ) = current;
-    user.id.eq(user_id_a) || user.id.eq(user_id_b)
+/// Whether the user is still in the game, answering prompts or, having joined late, voting, the
+/// ones the game waits for
+fn is_playing(user: &User, users: &[FullUser]) -> bool {
+    users.iter().any(|player| player.id == user.id)
 }
 
-fn not_in_game(user: &User, answers: &[Answer]) -> bool {
-    for answer in answers {
-        if answer.user.id == user.id {
-            return false;
-        }
-    }
-    true
+fn already_voted(user: &User, current: &[Answer], votes: &[Vote]) -> bool {
+    votes.iter().any(|vote| {
+        user.id == vote.user.id && current.iter().any(|answer| answer.token == vote.token)
+    })
 }
 
-fn already_voted(user: &User, current: (&Answer, &Answer), votes: &[Vote]) -> bool {
-    let (Answer { token: token_a, .. }, Answer { token: token_b, .. }) = current;
-    votes
+/// Whether every player still in the game has answered their prompts of the round
+fn all_answers_are_in(answers: &[Answer], users: &[FullUser], round: i64) -> bool {
+    answers
         .iter()
-        .any(|vote| user.id == vote.user.id && (vote.token.eq(token_a) || vote.token.eq(token_b)))
+        .filter(|answer| answer.round == round && is_playing(&answer.user, users))
+        .all(|answer| answer.response.is_some())
 }
 
-fn all_answers_are_in(answers: &[Answer]) -> bool {
-    answers.iter().all(|answer| answer.response.is_some())
+/// Hands the unanswered prompts of the player who left from `round` on to the players answering
+/// the fewest prompts of that round who are not answering the same one. Prompts everyone answers
+/// stay with the player, forfeited
+fn hand_over(user: &User, answers: &mut [Answer], users: &[FullUser], round: i64) {
+    for i in 0..answers.len() {
+        if answers[i].user != *user || answers[i].round < round || answers[i].response.is_some() {
+            continue;
+        }
+        let (question_id, prompt_round) = (answers[i].question.id, answers[i].round);
+        let count = |player: &FullUser| {
+            answers
+                .iter()
+                .filter(|answer| answer.round == prompt_round && answer.user.id == player.id)
+                .count()
+        };
+        // Players who joined late only vote, having no prompts of their own
+        let receiver = users
+            .iter()
+            .filter(|player| answers.iter().any(|answer| answer.user.id == player.id))
+            .filter(|player| {
+                !answers.iter().any(|answer| {
+                    answer.round == prompt_round
+                        && answer.question.id == question_id
+                        && answer.user.id == player.id
+                })
+            })
+            .min_by_key(|player| count(player));
+        if let Some(receiver) = receiver {
+            answers[i].user = receiver.into();
+        }
+    }
 }
 
-fn answer_prompt(token: &str, response: &str, answers: &mut Vec<Answer>) -> Result<()> {
+/// Answers the prompt of the token, or the next unanswered prompt of the token's player in the
+/// round when that one has already been answered or belongs to another round
+fn answer_prompt(token: &str, response: &str, answers: &mut Vec<Answer>, round: i64) -> Result<()> {
     let answer = answers.iter_mut().find(|answer| answer.token.eq(token));
 
     let user_id = match answer {
@@ -465,20 +786,16 @@ fn answer_prompt(token: &str, response: &str, answers: &mut Vec<Answer>) -> Resu
             error!("No answer found for token: {}", token);
             return Err(DomainError::AnswerError(NoneWithToken));
         }
-        Some(answer @ Answer { response: None, .. }) => {
+        Some(answer) if answer.response.is_none() && answer.round == round => {
             answer.response = Some(response.to_string());
             return Ok(());
         }
-        Some(Answer {
-            response: Some(_),
-            user,
-            ..
-        }) => user.id,
+        Some(Answer { user, .. }) => user.id,
     };
 
-    let answer = answers
-        .iter_mut()
-        .find(|answer| user_id.eq(&answer.user.id) && answer.response.is_none());
+    let answer = answers.iter_mut().find(|answer| {
+        user_id.eq(&answer.user.id) && answer.round == round && answer.response.is_none()
+    });
 
     match answer {
         None => {
@@ -492,15 +809,347 @@ fn answer_prompt(token: &str, response: &str, answers: &mut Vec<Answer>) -> Resu
     }
 }
 
-fn vote(user: &User, choice: &Choice, votes: &mut Vec<Vote>) {
+fn vote(user: &User, choice: &Choice, audience: bool, votes: &mut Vec<Vote>) -> Vote {
     let vote = Vote {
         token: choice.token.clone(),
         user: User { id: user.id },
+        audience,
     };
 
-    votes.push(vote);
+    votes.push(vote.clone());
+    vote
+}
+
+fn new_answer(user: &FullUser, question: &Question, round: i64) -> Answer {
+    Answer {
+        user: user.into(),
+        question: question.clone(),
+        token: generate_token(),
+        response: None,
+        round,
+    }
 }
 
 fn generate_token() -> String {
     Uuid::new_v4().to_string()
 }
+
+#[cfg(test)]
+mod test {
+    use crate::game::round::Round;
+    use crate::game::VoteError::{NotInGame, OnlyOnce, OwnQuestion};
+    use crate::game::{Answer, Choice, DomainError, FullUser, Question, State, User};
+    use std::collections::HashSet;
+
+    fn users(count: i64) -> Vec<FullUser> {
+        (1..=count)
+            .map(|id| FullUser {
+                id,
+                is_bot: false,
+                first_name: None,
+                last_name: None,
+                username: None,
+            })
+            .collect()
+    }
+
+    fn questions(count: i64) -> Vec<Question> {
+        (1..=count)
+            .map(|id| Question {
+                id,
+                text: format!("q{}", id),
+            })
+            .collect()
+    }
+
+    fn answer_all(state: &mut State) {
+        let tokens: Vec<String> = match state {
+            State::GatherAnswers { round, answers, .. } => answers
+                .iter()
+                .filter(|answer| answer.round == *round)
+                .map(|answer| answer.token.clone())
+                .collect(),
+            _ => panic!("Expected to gather answers"),
+        };
+        for token in tokens {
+            state.answer_prompt(&token, "answer").unwrap();
+        }
+    }
+
+    fn current(state: &State) -> Vec<Answer> {
+        match state {
+            State::GatherVotes { current, .. } => current.clone(),
+            _ => panic!("Expected to gather votes"),
+        }
+    }
+
+    #[test]
+    fn test_next_prompt() {
+        let rounds = [Round::HeadToHead { points: 1 }];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(3),
+        };
+        let mut state = state.begin_game(&questions(3), &rounds).unwrap();
+        let user = User { id: 1 };
+
+        let first = state.next_prompt(&user).unwrap().clone();
+        state.answer_prompt(&first.token, "answer").unwrap();
+        let second = state.next_prompt(&user).unwrap().clone();
+        assert_ne!(first.token, second.token);
+        state.answer_prompt(&second.token, "answer").unwrap();
+        assert!(
+            state.next_prompt(&user).is_none(),
+            "Each player answers two prompts in a head to head round"
+        );
+    }
+
+    #[test]
+    fn test_rounds() {
+        let rounds = [
+            Round::HeadToHead { points: 1 },
+            Round::HeadToHead { points: 2 },
+            Round::LastLash { points: 3 },
+        ];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(4),
+        };
+        assert!(matches!(
+            state.begin_game(&questions(8), &rounds),
+            Err(DomainError::NotEnoughQuestions)
+        ));
+
+        let mut state = state.begin_game(&questions(9), &rounds).unwrap();
+        match &state {
+            State::GatherAnswers { round, answers, .. } => {
+                assert_eq!(*round, 1);
+                assert_eq!(answers.len(), 4 * 2 + 4 * 2 + 4);
+                assert!(answers
+                    .iter()
+                    .filter(|answer| answer.round == 3)
+                    .all(|answer| answer.question.id == 9));
+            }
+            _ => panic!("Expected to gather answers"),
+        }
+
+        for round in 1..=2 {
+            answer_all(&mut state);
+            for _question in 0..4 {
+                let current = current(&state);
+                assert_eq!(current.len(), 2);
+                assert!(current.iter().all(|answer| answer.round == round));
+                let choice = Choice {
+                    token: current[0].token.clone(),
+                };
+                for user in users(4) {
+                    let user = User::from(user);
+                    if current.iter().all(|answer| answer.user != user) {
+                        state.vote(&user, &choice, false).unwrap();
+                    }
+                }
+            }
+            match &state {
+                State::GatherAnswers { round: next, .. } => assert_eq!(*next, round + 1),
+                _ => panic!("Expected the next round"),
+            }
+        }
+
+        answer_all(&mut state);
+        let current = current(&state);
+        assert_eq!(current.len(), 4, "Everyone answers the last lash");
+        let own = Choice {
+            token: current[0].token.clone(),
+        };
+        let other = Choice {
+            token: current[1].token.clone(),
+        };
+        let user = current[0].user.clone();
+        match state.vote(&user, &own, false) {
+            Err(DomainError::VoteError(OwnQuestion)) => {}
+            other => panic!("Expected own question error, got {:?}", other),
+        }
+        state.vote(&user, &other, false).unwrap();
+        for user in users(4).into_iter().skip(1) {
+            state.vote(&User::from(user), &own, false).unwrap();
+        }
+        match state {
+            State::End { votes, .. } => assert!(votes
+                .iter()
+                .any(|vote| vote.user == user && vote.token == other.token)),
+            _ => panic!("Expected the game to end"),
+        }
+    }
+
+    #[test]
+    fn test_audience() {
+        let rounds = [Round::HeadToHead { points: 1 }];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(3),
+        };
+        let mut state = state.begin_game(&questions(3), &rounds).unwrap();
+        answer_all(&mut state);
+
+        let current = current(&state);
+        let choice = Choice {
+            token: current[0].token.clone(),
+        };
+        let spectator = User { id: 100 };
+        assert!(matches!(
+            state.vote(&spectator, &choice, false),
+            Err(DomainError::VoteError(NotInGame))
+        ));
+        state.vote(&spectator, &choice, true).unwrap();
+        assert!(matches!(
+            state.vote(&spectator, &choice, true),
+            Err(DomainError::VoteError(OnlyOnce))
+        ));
+
+        match &state {
+            State::GatherVotes { votes, .. } => assert!(votes[0].audience),
+            _ => panic!("The audience should not close the vote"),
+        }
+        assert_eq!(state.remaining_voters().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_leave_game() {
+        let rounds = [
+            Round::HeadToHead { points: 1 },
+            Round::HeadToHead { points: 2 },
+        ];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(5),
+        };
+        let mut state = state.begin_game(&questions(10), &rounds).unwrap();
+        let leaver = User { id: 5 };
+        let first = state.next_prompt(&leaver).unwrap().token.clone();
+        state.answer_prompt(&first, "answer").unwrap();
+
+        state.leave_game(&leaver).unwrap();
+        match &state {
+            State::GatherAnswers { answers, .. } => {
+                let kept: Vec<_> = answers
+                    .iter()
+                    .filter(|answer| answer.user == leaver)
+                    .collect();
+                assert_eq!(kept.len(), 1, "Unanswered prompts should be handed over");
+                assert_eq!(kept[0].token, first);
+                assert_eq!(
+                    kept[0].response.as_deref(),
+                    Some("answer"),
+                    "The answer given should be kept"
+                );
+                for question in questions(10) {
+                    let players: HashSet<i64> = answers
+                        .iter()
+                        .filter(|answer| answer.question.id == question.id)
+                        .map(|answer| answer.user.id)
+                        .collect();
+                    assert_eq!(players.len(), 2, "No one should answer a prompt twice");
+                }
+            }
+            _ => panic!("Expected to gather answers"),
+        }
+        assert!(matches!(
+            state.leave_game(&leaver),
+            Err(DomainError::NotPlaying)
+        ));
+
+        // The prompt the first player answered is kept, theirs to be voted on
+        while let Some(user) = state
+            .remaining_answerers()
+            .ok()
+            .and_then(|users| users.first().map(|user| User::from(*user)))
+        {
+            let token = state.next_prompt(&user).unwrap().token.clone();
+            state.answer_prompt(&token, "answer").unwrap();
+        }
+        let position = current(&state)
+            .iter()
+            .position(|answer| answer.user != User { id: 5 })
+            .unwrap();
+        let leaver = current(&state)[position].user.clone();
+        state.leave_game(&leaver).unwrap();
+        let current = current(&state);
+        assert!(
+            current[position].response.is_none(),
+            "The answer being voted on should be forfeited"
+        );
+        assert!(state
+            .remaining_voters()
+            .unwrap()
+            .iter()
+            .all(|user| user.id != leaver.id));
+        match &state {
+            State::GatherVotes { answers, .. } => assert!(answers
+                .iter()
+                .filter(|answer| answer.round == 2)
+                .all(|answer| answer.user != leaver)),
+            _ => panic!("Expected to gather votes"),
+        }
+
+        let choice = Choice {
+            token: current[1 - position].token.clone(),
+        };
+        let voter = User::from(state.remaining_voters().unwrap()[0]);
+        state.vote(&voter, &choice, false).unwrap();
+        let other = (1..=4)
+            .map(|id| User { id })
+            .find(|user| *user != leaver && *user != voter)
+            .unwrap();
+        state.leave_game(&other).unwrap();
+        match &state {
+            State::End { votes, .. } => assert_eq!(votes.len(), 1, "Votes should be kept"),
+            _ => panic!("The game should end with fewer than three players"),
+        }
+    }
+
+    #[test]
+    fn test_late_join() {
+        let rounds = [Round::HeadToHead { points: 1 }];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(3),
+        };
+        let mut state = state.begin_game(&questions(3), &rounds).unwrap();
+        let latecomer = users(4).pop().unwrap();
+        assert!(matches!(
+            state.join_game(latecomer.clone(), false),
+            Err(DomainError::InvalidTransition)
+        ));
+        state.join_game(latecomer.clone(), true).unwrap();
+        assert!(state.next_prompt(&User::from(&latecomer)).is_none());
+
+        answer_all(&mut state);
+        assert!(
+            state.remaining_voters().unwrap().contains(&&latecomer),
+            "Latecomers should vote"
+        );
+        let choice = Choice {
+            token: current(&state)[0].token.clone(),
+        };
+        let vote = state.vote(&User::from(&latecomer), &choice, false).unwrap();
+        assert!(!vote.audience);
+    }
+
+    #[test]
+    fn test_leave_with_latecomer() {
+        let rounds = [Round::HeadToHead { points: 1 }];
+        let state = State::GatherUsers {
+            id: 1,
+            users: users(3),
+        };
+        let mut state = state.begin_game(&questions(3), &rounds).unwrap();
+        let latecomer = users(4).pop().unwrap();
+        state.join_game(latecomer, true).unwrap();
+
+        state.leave_game(&User { id: 1 }).unwrap();
+        assert!(
+            matches!(state, State::End { .. }),
+            "Latecomers should not count toward the players the game needs"
+        );
+    }
+}
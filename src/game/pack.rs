@@ -0,0 +1,16 @@
+/// Packs with this tag are left out of games until a chat group turns them on
+pub const NSFW_TAG: &str = "nsfw";
+
+/// A named collection of questions and whether it is played in a chat group
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub id: i64,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub questions: i64,
+    pub enabled: bool,
+}
+
+pub fn enabled_by_default(tags: &[String]) -> bool {
+    !tags.iter().any(|tag| tag.eq_ignore_ascii_case(NSFW_TAG))
+}
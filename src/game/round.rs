@@ -0,0 +1,97 @@
+/// How the prompts of a round are handed out and what each vote in it is worth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Round {
+    /// Each prompt goes to two players and everyone else votes between their answers
+    HeadToHead { points: i64 },
+    /// Every player answers the same prompt and everyone votes across all of the answers
+    LastLash { points: i64 },
+}
+
+/// Two rounds of head to head voting, the second worth double, followed by a last lash
+pub const DEFAULT_ROUNDS: &str = "head_to_head:1,head_to_head:2,last_lash:3";
+
+impl Round {
+    /// Parses a round such as `head_to_head:2`, the number being the points per vote
+    pub fn parse(round: &str) -> Option<Round> {
+        let (kind, points) = round.trim().split_once(':')?;
+        let points = points.trim().parse().ok().filter(|points| *points > 0)?;
+        match kind.trim() {
+            "head_to_head" => Some(Round::HeadToHead { points }),
+            "last_lash" => Some(Round::LastLash { points }),
+            _ => None,
+        }
+    }
+
+    pub fn points(&self) -> i64 {
+        match self {
+            Round::HeadToHead { points } => *points,
+            Round::LastLash { points } => *points,
+        }
+    }
+
+    /// The number of prompts handed out in the round
+    pub fn question_count(&self, players: usize) -> usize {
+        match self {
+            Round::HeadToHead { .. } => players,
+            Round::LastLash { .. } => 1,
+        }
+    }
+}
+
+/// Parses a comma separated list of rounds, none when any of them is invalid or there are none
+pub fn parse_rounds(rounds: &str) -> Option<Vec<Round>> {
+    let rounds = rounds
+        .split(',')
+        .map(Round::parse)
+        .collect::<Option<Vec<Round>>>()?;
+    if rounds.is_empty() {
+        return None;
+    }
+    Some(rounds)
+}
+
+/// The number of prompts needed to play every round
+pub fn question_count(rounds: &[Round], players: usize) -> usize {
+    rounds
+        .iter()
+        .map(|round| round.question_count(players))
+        .sum()
+}
+
+/// Points each vote is worth in the round, rounds being numbered from 1
+pub fn points(rounds: &[Round], round: i64) -> i64 {
+    rounds
+        .get((round - 1) as usize)
+        .map(Round::points)
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::round::{parse_rounds, points, question_count, Round, DEFAULT_ROUNDS};
+
+    #[test]
+    fn test_parse_rounds() {
+        let rounds = parse_rounds(DEFAULT_ROUNDS).unwrap();
+        assert_eq!(
+            rounds,
+            vec![
+                Round::HeadToHead { points: 1 },
+                Round::HeadToHead { points: 2 },
+                Round::LastLash { points: 3 },
+            ]
+        );
+        assert_eq!(question_count(&rounds, 4), 9);
+        assert_eq!(points(&rounds, 2), 2);
+        assert_eq!(points(&rounds, 3), 3);
+
+        assert_eq!(
+            parse_rounds(" last_lash : 5 "),
+            Some(vec![Round::LastLash { points: 5 }])
+        );
+        assert_eq!(parse_rounds(""), None);
+        assert_eq!(parse_rounds("head_to_head:1,last_lash"), None);
+        assert_eq!(parse_rounds("head_to_head:0"), None);
+        assert_eq!(parse_rounds("lightning:1"), None);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::game::round;
+use crate::game::round::Round;
+use crate::game::{Answer, User, Vote};
+use std::collections::HashMap;
+
+/// Points for each vote an answer receives, multiplied by the points of its round
+pub const VOTE_POINTS: i64 = 100;
+/// Bonus for an answer that takes every vote on its prompt, multiplied by the points of its round
+pub const QUIPLASH_BONUS: i64 = 250;
+/// The audience as a whole is worth this many player votes, shared between the answers by how it
+/// voted
+pub const AUDIENCE_VOTES: i64 = 2;
+
+/// How an answer scored on its prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnswerScore {
+    pub user: User,
+    pub points: i64,
+    /// The answer took every player vote on its prompt
+    pub quiplash: bool,
+    /// Percentage of the audience votes on the prompt the answer took, none without an audience
+    pub audience: Option<i64>,
+}
+
+/// The answers given to the same prompt as `answer`
+pub fn prompt<'a>(answers: &'a [Answer], answer: &Answer) -> Vec<&'a Answer> {
+    answers
+        .iter()
+        .filter(|other| other.round == answer.round && other.question.id == answer.question.id)
+        .collect()
+}
+
+/// Scores the answers to one prompt in the same order. A forfeited answer scores nothing, the opponent of a head
+/// to head taking every vote on the prompt instead
+pub fn score_prompt(prompt: &[&Answer], votes: &[Vote], rounds: &[Round]) -> Vec<AnswerScore> {
+    let received = |answer: &Answer, audience: bool| {
+        votes
+            .iter()
+            .filter(|vote| vote.token == answer.token && vote.audience == audience)
+            .count() as i64
+    };
+    let total =
+        |audience: bool| -> i64 { prompt.iter().map(|answer| received(answer, audience)).sum() };
+    let (total, audience_total) = (total(false), total(true));
+    let forfeits = prompt
+        .iter()
+        .filter(|answer| answer.response.is_none())
+        .count();
+    let credited = |answer: &Answer, audience: bool, total: i64| {
+        if answer.response.is_none() {
+            0
+        } else if prompt.len() == 2 && forfeits == 1 {
+            total
+        } else {
+            received(answer, audience)
+        }
+    };
+
+    prompt
+        .iter()
+        .map(|answer| {
+            let credited_votes = credited(answer, false, total);
+            let quiplash = credited_votes > 0 && credited_votes == total;
+            let bonus = if quiplash { QUIPLASH_BONUS } else { 0 };
+            let (audience, audience_points) = if audience_total == 0 {
+                (None, 0)
+            } else {
+                let share = credited(answer, true, audience_total);
+                (
+                    Some(100 * share / audience_total),
+                    VOTE_POINTS * AUDIENCE_VOTES * share / audience_total,
+                )
+            };
+            AnswerScore {
+                user: answer.user.clone(),
+                points: round::points(rounds, answer.round)
+                    * (VOTE_POINTS * credited_votes + bonus + audience_points),
+                quiplash,
+                audience,
+            }
+        })
+        .collect()
+}
+
+/// Total points of each player over every prompt of the game, keyed by user id
+pub fn score_game(answers: &[Answer], votes: &[Vote], rounds: &[Round]) -> HashMap<i64, i64> {
+    let mut prompts: Vec<(i64, i64)> = vec![];
+    for answer in answers {
+        if !prompts.contains(&(answer.round, answer.question.id)) {
+            prompts.push((answer.round, answer.question.id));
+        }
+    }
+
+    let mut totals = HashMap::new();
+    for (round, question_id) in prompts {
+        let prompt: Vec<&Answer> = answers
+            .iter()
+            .filter(|answer| answer.round == round && answer.question.id == question_id)
+            .collect();
+        for score in score_prompt(&prompt, votes, rounds) {
+            *totals.entry(score.user.id).or_insert(0) += score.points;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::round::Round;
+    use crate::game::scoring::{
+        prompt, score_game, score_prompt, AUDIENCE_VOTES, QUIPLASH_BONUS, VOTE_POINTS,
+    };
+    use crate::game::{Answer, Question, User, Vote};
+
+    const ROUNDS: &[Round] = &[
+        Round::HeadToHead { points: 1 },
+        Round::HeadToHead { points: 2 },
+    ];
+
+    fn answer(user_id: i64, question_id: i64, round: i64, response: Option<&str>) -> Answer {
+        Answer {
+            user: User { id: user_id },
+            question: Question {
+                id: question_id,
+                text: String::new(),
+            },
+            token: format!("u{}q{}r{}", user_id, question_id, round),
+            response: response.map(String::from),
+            round,
+        }
+    }
+
+    fn votes(token: &str, count: i64) -> Vec<Vote> {
+        (0..count)
+            .map(|id| Vote {
+                token: token.to_string(),
+                user: User { id: 100 + id },
+                audience: false,
+            })
+            .collect()
+    }
+
+    fn audience_votes(token: &str, count: i64) -> Vec<Vote> {
+        (0..count)
+            .map(|id| Vote {
+                token: token.to_string(),
+                user: User { id: 200 + id },
+                audience: true,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_score_prompt() {
+        let a = answer(1, 1, 1, Some("a"));
+        let b = answer(2, 1, 1, Some("b"));
+
+        let mut split = votes(&a.token, 2);
+        split.extend(votes(&b.token, 1));
+        let scores = score_prompt(&[&a, &b], &split, ROUNDS);
+        assert_eq!(scores[0].points, 2 * VOTE_POINTS);
+        assert_eq!(scores[1].points, VOTE_POINTS);
+        assert!(!scores[0].quiplash && !scores[1].quiplash);
+
+        let scores = score_prompt(&[&a, &b], &votes(&a.token, 3), ROUNDS);
+        assert_eq!(scores[0].points, 3 * VOTE_POINTS + QUIPLASH_BONUS);
+        assert!(scores[0].quiplash);
+        assert_eq!(scores[1].points, 0);
+
+        let scores = score_prompt(&[&a, &b], &[], ROUNDS);
+        assert!(!scores[0].quiplash, "No votes is not a sweep");
+    }
+
+    #[test]
+    fn test_audience() {
+        let a = answer(1, 1, 1, Some("a"));
+        let b = answer(2, 1, 1, Some("b"));
+
+        let mut prompt_votes = votes(&a.token, 1);
+        prompt_votes.extend(audience_votes(&a.token, 1));
+        prompt_votes.extend(audience_votes(&b.token, 3));
+        let scores = score_prompt(&[&a, &b], &prompt_votes, ROUNDS);
+        assert_eq!(
+            scores[0].points,
+            VOTE_POINTS + QUIPLASH_BONUS + VOTE_POINTS * AUDIENCE_VOTES / 4
+        );
+        assert!(
+            scores[0].quiplash,
+            "Audience votes should not count against a sweep"
+        );
+        assert_eq!(scores[0].audience, Some(25));
+        assert_eq!(scores[1].points, VOTE_POINTS * AUDIENCE_VOTES * 3 / 4);
+        assert_eq!(scores[1].audience, Some(75));
+
+        let scores = score_prompt(&[&a, &b], &votes(&a.token, 1), ROUNDS);
+        assert_eq!(scores[0].audience, None);
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let a = answer(1, 1, 2, Some("a"));
+        let b = answer(2, 1, 2, None);
+
+        let scores = score_prompt(&[&a, &b], &votes(&b.token, 2), ROUNDS);
+        assert_eq!(scores[0].points, 2 * (2 * VOTE_POINTS + QUIPLASH_BONUS));
+        assert!(scores[0].quiplash);
+        assert_eq!(scores[1].points, 0);
+
+        let c = answer(1, 2, 2, None);
+        let d = answer(2, 2, 2, None);
+        let scores = score_prompt(&[&c, &d], &votes(&c.token, 2), ROUNDS);
+        assert!(scores.iter().all(|score| score.points == 0));
+    }
+
+    #[test]
+    fn test_score_game() {
+        let answers = vec![
+            answer(1, 1, 1, Some("a")),
+            answer(2, 1, 1, Some("b")),
+            answer(1, 1, 2, Some("c")),
+            answer(3, 1, 2, Some("d")),
+        ];
+        assert_eq!(prompt(&answers, &answers[2]).len(), 2);
+
+        let mut game_votes = votes(&answers[0].token, 1);
+        game_votes.extend(votes(&answers[1].token, 1));
+        game_votes.extend(votes(&answers[3].token, 1));
+        let totals = score_game(&answers, &game_votes, ROUNDS);
+
+        assert_eq!(totals[&1], VOTE_POINTS);
+        assert_eq!(totals[&2], VOTE_POINTS);
+        assert_eq!(totals[&3], 2 * (VOTE_POINTS + QUIPLASH_BONUS));
+    }
+}
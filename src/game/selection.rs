@@ -0,0 +1,102 @@
+use crate::game::Question;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// A question that can be drawn for a chat group
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub question: Question,
+    /// The most recent game of the chat group the question was played in
+    pub last_game: Option<i64>,
+}
+
+/// Draws up to `count` distinct questions, those never played by the chat group first and then
+/// those played the longest ago. Fewer are returned only when there are fewer candidates
+pub fn select<R: Rng>(candidates: &[Candidate], count: usize, rng: &mut R) -> Vec<Question> {
+    let mut candidates: Vec<&Candidate> = candidates.iter().collect();
+    candidates.shuffle(rng);
+    // Stable, so candidates played in the same game or never stay shuffled
+    candidates.sort_by_key(|candidate| candidate.last_game);
+
+    let mut questions: Vec<Question> = vec![];
+    for candidate in candidates {
+        if questions.len() == count {
+            break;
+        }
+        if !questions
+            .iter()
+            .any(|question| question.id == candidate.question.id)
+        {
+            questions.push(candidate.question.clone());
+        }
+    }
+    questions
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::selection::{select, Candidate};
+    use crate::game::Question;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn candidate(id: i64, last_game: Option<i64>) -> Candidate {
+        Candidate {
+            question: Question {
+                id,
+                text: format!("q{}", id),
+            },
+            last_game,
+        }
+    }
+
+    fn ids(questions: &[Question]) -> Vec<i64> {
+        questions.iter().map(|question| question.id).collect()
+    }
+
+    #[test]
+    fn test_select_unplayed_first() {
+        let candidates: Vec<Candidate> = (1..=10)
+            .map(|id| candidate(id, if id <= 5 { Some(id) } else { None }))
+            .collect();
+
+        for seed in 0..20 {
+            let mut selected = ids(&select(&candidates, 5, &mut StdRng::seed_from_u64(seed)));
+            selected.sort_unstable();
+            assert_eq!(selected, vec![6, 7, 8, 9, 10]);
+        }
+
+        let first = ids(&select(&candidates, 5, &mut StdRng::seed_from_u64(1)));
+        let again = ids(&select(&candidates, 5, &mut StdRng::seed_from_u64(1)));
+        assert_eq!(first, again, "The same seed should draw the same questions");
+    }
+
+    #[test]
+    fn test_select_least_recently_played() {
+        let candidates = vec![
+            candidate(1, Some(9)),
+            candidate(2, Some(3)),
+            candidate(3, None),
+            candidate(4, Some(7)),
+        ];
+
+        let selected = select(&candidates, 3, &mut StdRng::seed_from_u64(42));
+        assert_eq!(ids(&selected), vec![3, 2, 4]);
+    }
+
+    #[test]
+    fn test_select_never_repeats() {
+        let candidates = vec![
+            candidate(1, None),
+            candidate(1, None),
+            candidate(2, Some(1)),
+        ];
+
+        let selected = select(&candidates, 5, &mut StdRng::seed_from_u64(7));
+        let mut selected = ids(&selected);
+        selected.sort_unstable();
+        assert_eq!(selected, vec![1, 2], "Should draw every question once");
+
+        assert!(select(&[], 3, &mut StdRng::seed_from_u64(7)).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::{Display, Formatter};
+
+/// Who is allowed to use a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Anyone,
+    /// The host of the game or an admin of the chat
+    Host,
+    Admin,
+}
+
+/// Commands that can be restricted to a role
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Begin,
+    End,
+    Kick,
+}
+
+/// Per chat group settings
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub begin: Role,
+    pub end: Role,
+    pub kick: Role,
+    /// Whether people watching the game can vote alongside the players
+    pub audience: bool,
+    /// Whether people can still join the game once it has begun, voting without prompts to answer
+    pub late_join: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            begin: Role::Host,
+            end: Role::Host,
+            kick: Role::Host,
+            audience: false,
+            late_join: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn role(&self, command: Command) -> Role {
+        match command {
+            Command::Begin => self.begin,
+            Command::End => self.end,
+            Command::Kick => self.kick,
+        }
+    }
+
+    pub fn set_role(&mut self, command: Command, role: Role) {
+        match command {
+            Command::Begin => self.begin = role,
+            Command::End => self.end = role,
+            Command::Kick => self.kick = role,
+        }
+    }
+}
+
+impl Role {
+    pub fn parse(role: &str) -> Option<Role> {
+        match role {
+            "anyone" => Some(Role::Anyone),
+            "host" => Some(Role::Host),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Anyone => "anyone",
+            Role::Host => "host",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn is_permitted(&self, is_host: bool, is_admin: bool) -> bool {
+        match self {
+            Role::Anyone => true,
+            Role::Host => is_host || is_admin,
+            Role::Admin => is_admin,
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(command: &str) -> Option<Command> {
+        match command {
+            "begin" => Some(Command::Begin),
+            "end" => Some(Command::End),
+            "kick" => Some(Command::Kick),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Command::Begin => "begin",
+            Command::End => "end",
+            Command::Kick => "kick",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,40 @@
+use crate::game::FullUser;
+
+/// Longest prompt that can be suggested, prompts being shown above the answers when voting
+pub const MAX_SUGGESTION_LENGTH: usize = 200;
+
+/// Suggested prompts are added to this pack once approved, only being played in their chat group
+pub const SUGGESTIONS_PACK: &str = "suggestions";
+
+/// A prompt suggested by a player, waiting for an admin of the chat group to review it
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub id: i64,
+    pub user: FullUser,
+    pub text: String,
+}
+
+/// The prompt to suggest with surrounding whitespace removed, none when it is empty or too long
+pub fn prompt(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() || text.chars().count() > MAX_SUGGESTION_LENGTH {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::suggestion::{prompt, MAX_SUGGESTION_LENGTH};
+
+    #[test]
+    fn test_prompt() {
+        assert_eq!(
+            prompt("  You should never ____ at a funeral "),
+            Some("You should never ____ at a funeral".to_string())
+        );
+        assert_eq!(prompt("   "), None);
+        assert!(prompt(&"é".repeat(MAX_SUGGESTION_LENGTH)).is_some());
+        assert_eq!(prompt(&"a".repeat(MAX_SUGGESTION_LENGTH + 1)), None);
+    }
+}
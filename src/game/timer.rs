@@ -0,0 +1,111 @@
+use crate::game::ChatGroup;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Joining,
+    Answering,
+    Voting,
+}
+
+/// A running game that has a timer along with how long it has been in its current phase
+#[derive(Debug)]
+pub struct Timer {
+    pub id: i64,
+    pub chat_group: ChatGroup,
+    pub phase: Phase,
+    pub elapsed: i64,
+    /// The last warning threshold sent for the current phase
+    pub warning: Option<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Wait,
+    /// Warn that the phase is about to end, the value being the threshold that was crossed
+    Warn(i64),
+    Expire,
+}
+
+/// Time limits in seconds for each phase, warnings being the number of seconds remaining at
+/// which the chat group is told that time is running out
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    pub gather_users: i64,
+    pub gather_answers: i64,
+    pub gather_votes: i64,
+    pub warnings: Vec<i64>,
+}
+
+impl Timeouts {
+    pub fn limit(&self, phase: Phase) -> i64 {
+        match phase {
+            Phase::Joining => self.gather_users,
+            Phase::Answering => self.gather_answers,
+            Phase::Voting => self.gather_votes,
+        }
+    }
+
+    pub fn remaining(&self, timer: &Timer) -> i64 {
+        self.limit(timer.phase) - timer.elapsed
+    }
+
+    pub fn action(&self, timer: &Timer) -> Action {
+        let remaining = self.remaining(timer);
+        if remaining <= 0 {
+            return Action::Expire;
+        }
+
+        // Only the smallest crossed threshold is sent so that a restart does not flood the chat
+        let threshold = self
+            .warnings
+            .iter()
+            .filter(|threshold| **threshold >= remaining)
+            .filter(|threshold| match timer.warning {
+                None => true,
+                Some(warning) => **threshold < warning,
+            })
+            .min();
+
+        match threshold {
+            None => Action::Wait,
+            Some(threshold) => Action::Warn(*threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::timer::{Action, Phase, Timeouts, Timer};
+    use crate::game::ChatGroup;
+
+    fn timer(elapsed: i64, warning: Option<i64>) -> Timer {
+        Timer {
+            id: 1,
+            chat_group: ChatGroup(1),
+            phase: Phase::Answering,
+            elapsed,
+            warning,
+        }
+    }
+
+    #[test]
+    fn test_action() {
+        let timeouts = Timeouts {
+            gather_users: 300,
+            gather_answers: 120,
+            gather_votes: 60,
+            warnings: vec![60, 30, 10],
+        };
+
+        assert_eq!(timeouts.action(&timer(0, None)), Action::Wait);
+        assert_eq!(timeouts.action(&timer(60, None)), Action::Warn(60));
+        assert_eq!(timeouts.action(&timer(70, Some(60))), Action::Wait);
+        assert_eq!(timeouts.action(&timer(95, Some(60))), Action::Warn(30));
+        assert_eq!(
+            timeouts.action(&timer(115, None)),
+            Action::Warn(10),
+            "Only the most recent threshold should be sent after a restart"
+        );
+        assert_eq!(timeouts.action(&timer(120, Some(10))), Action::Expire);
+    }
+}
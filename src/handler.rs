@@ -1,5 +1,5 @@
-use crate::controller::ControllerError::Domain;
-use crate::controller::{Controller, ControllerError};
+use crate::controller::ControllerError::{ClientError, Domain};
+use crate::controller::{ClientErrorReason, Controller, ControllerError};
 use crate::game::{AnswerError, DomainError};
 use crate::http::server;
 use crate::http::server::{Handler, ServerError};
@@ -28,6 +28,14 @@ impl From<ControllerError> for ServerError {
             Domain(DomainError::AnswerError(AnswerError::NoneWithToken)) => {
                 ServerError::Client("INVALID_TOKEN")
             }
+            ClientError(ClientErrorReason::InvalidQueryParams) => {
+                ServerError::Client("INVALID_QUERY_PARAMS")
+            }
+            ClientError(ClientErrorReason::Unauthorized) => ServerError::Unauthorized,
+            ClientError(ClientErrorReason::NotFound) => ServerError::NotFound,
+            ClientError(ClientErrorReason::MethodNotAllowed(allow)) => {
+                ServerError::MethodNotAllowed(allow)
+            }
             _ => ServerError::Internal,
         }
     }
@@ -40,9 +48,10 @@ impl Handler for DefaultHandler<'_> {
         path: Uri,
         headers: Vec<(String, String)>,
         body: Value,
+        raw_body: &[u8],
     ) -> server::Result<Option<Value>> {
         Ok(self
             .router
-            .route(&self.controller, method, path, headers, body)?)
+            .route(&self.controller, method, path, headers, body, raw_body)?)
     }
 }
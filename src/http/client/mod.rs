@@ -1,16 +1,47 @@
 use http::Request;
-use httparse::Status;
-use log::error;
-use rustls::{ClientConfig, ClientSession, Stream};
+use httparse::{Status, EMPTY_HEADER};
+use log::{error, info};
+use rustls::{ClientConfig, ClientSession, StreamOwned};
 use std::io::{ErrorKind, Read, Write};
 use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use webpki::DNSNameRef;
 
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer than telegram holds a request polling for updates
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// Idle connections are dropped before the server is likely to have closed them
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Connections kept open, each thread having its own client and sending one request at a time
+const MAX_IDLE: usize = 2;
+/// How long the address looked up for the hostname is used before looking it up again
+const DNS_TTL: Duration = Duration::from_secs(300);
+
+type Tls = StreamOwned<ClientSession, TcpStream>;
+
+/// Sends requests over connections that are kept open and reused while the server allows it
 pub struct Client<'a> {
+    hostname: &'a str,
     dns_name: DNSNameRef<'a>,
-    socket_addr: SocketAddr,
+    address: Mutex<Address>,
     client_config: Arc<ClientConfig>,
+    idle: Mutex<Vec<Connection>>,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+}
+
+struct Address {
+    socket_addr: SocketAddr,
+    /// Whether the address is looked up from the hostname, rather than given
+    lookup: bool,
+    /// When the hostname was last looked up, none once the address stopped working
+    resolved: Option<Instant>,
+}
+
+struct Connection {
+    tls: Tls,
+    idle_since: Instant,
 }
 
 #[derive(Debug, PartialEq)]
@@ -20,155 +51,494 @@ pub enum ClientError {
     Write,
     Tls,
     Response,
+    Timeout,
 }
 
 impl<'a> Client<'a> {
     pub fn new(hostname: &'a str) -> Result<Self, ClientError> {
-        let dns_name = webpki::DNSNameRef::try_from_ascii_str(hostname).map_err(|err| {
-            error!("Invalid dns name: {}", err);
-            ClientError::Hostname
-        })?;
         let mut client_config = rustls::ClientConfig::new();
         client_config
             .root_store
             .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
 
-        let socket_addr = (hostname, 443)
-            .to_socket_addrs()
-            .map_err(|err| {
-                error!("Parsing host name error: {}", err);
-                ClientError::Hostname
-            })?
-            .next();
-
-        match socket_addr {
-            None => {
-                error!("Failed lookup: {}", hostname);
-                Err(ClientError::Hostname)
-            }
-            Some(socket_addr) => Ok(Client {
-                dns_name,
+        let socket_addr = lookup(hostname)?;
+        let mut client = Self::with_config(hostname, socket_addr, client_config)?;
+        client.address = Mutex::new(Address {
+            socket_addr,
+            lookup: true,
+            resolved: Some(Instant::now()),
+        });
+        Ok(client)
+    }
+
+    /// A client that connects to the given address rather than looking up the hostname, the
+    /// hostname still being used to verify the certificate
+    pub fn with_config(
+        hostname: &'a str,
+        socket_addr: SocketAddr,
+        client_config: ClientConfig,
+    ) -> Result<Self, ClientError> {
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str(hostname).map_err(|err| {
+            error!("Invalid dns name: {}", err);
+            ClientError::Hostname
+        })?;
+
+        Ok(Client {
+            hostname,
+            dns_name,
+            address: Mutex::new(Address {
                 socket_addr,
-                client_config: Arc::new(client_config),
+                lookup: false,
+                resolved: None,
             }),
-        }
+            client_config: Arc::new(client_config),
+            idle: Mutex::new(vec![]),
+            connect_timeout: CONNECT_TIMEOUT,
+            read_timeout: READ_TIMEOUT,
+        })
     }
 
+    /// Sends the request on an idle connection when there is one. A connection the server closed
+    /// while it was idle is only noticed once the request fails, so the request is sent again on
+    /// a new connection when nothing at all was received
     pub fn send<'h, 'b>(
         &self,
         req: Request<String>,
         buf: &'b mut Vec<u8>,
         dst: &'b mut httparse::Response<'h, 'b>,
     ) -> Result<(&'b httparse::Response<'h, 'b>, &'b [u8]), ClientError> {
-        let sess = &mut self.session()?;
-        let sock = &mut self.socket()?;
-        let mut tls = self.tls(sess, sock)?;
+        let request = self.encode(&req)?;
+
+        let (mut connection, reused) = match self.take_idle() {
+            Some(connection) => (connection, true),
+            None => (self.connect()?, false),
+        };
+        let result = match exchange(&mut connection.tls, &request, buf) {
+            Err(err) if reused && buf.is_empty() && err != ClientError::Timeout => {
+                info!("Idle connection was closed, reconnecting: {:?}", err);
+                connection = self.connect()?;
+                exchange(&mut connection.tls, &request, buf)
+            }
+            result => result,
+        };
+        if result? {
+            self.put_idle(connection);
+        }
+
+        let size = match dst.parse(buf) {
+            Ok(Status::Complete(size)) => size,
+            _ => {
+                error!("Response parsing error");
+                return Err(ClientError::Response);
+            }
+        };
+        Ok((dst, &buf[size..]))
+    }
 
-        let host: &str = DNSNameRef::into(self.dns_name);
-        let path = Self::path(&req)?;
+    fn encode(&self, req: &Request<String>) -> Result<Vec<u8>, ClientError> {
+        let host = self.hostname;
+        let path = match req.uri().path_and_query() {
+            None => return Err(ClientError::Write),
+            Some(path) => path.as_str(),
+        };
         let body = req.body();
+        let headers: String = req
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| match value.to_str() {
+                Ok(value) => Some(format!("{}: {}\r\n", name, value)),
+                Err(err) => {
+                    error!("Skipping header {} that is not ascii: {}", name, err);
+                    None
+                }
+            })
+            .collect();
 
-        tls.write_fmt(format_args!(
+        Ok(format!(
             "{method} https://{host}{path} HTTP/1.1\r\n\
             Host: {host}\r\n\
-            Connection: close\r\n\
             Content-Type: application/json\r\n\
             Content-Length: {length}\r\n\
+            {headers}\
             Accept-Encoding: identity\r\n\r\n\
-            {body}\r\n",
+            {body}",
             method = req.method(),
             path = path,
             host = host,
             length = body.as_bytes().len(),
+            headers = headers,
             body = body,
-        ))
-        .map_err(|err| {
-            error!("Write error: {}", err);
-            ClientError::Write
-        })?;
+        )
+        .into_bytes())
+    }
 
-        Self::read_response(&mut tls, buf, dst)
+    fn take_idle(&self) -> Option<Connection> {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        idle.retain(|connection| connection.idle_since.elapsed() < IDLE_TIMEOUT);
+        idle.pop()
     }
 
-    fn read_response<'s, 'h, 'b>(
-        tls: &'s mut Stream<ClientSession, TcpStream>,
-        buf: &'b mut Vec<u8>,
-        dst: &'b mut httparse::Response<'h, 'b>,
-    ) -> Result<(&'b httparse::Response<'h, 'b>, &'b [u8]), ClientError> {
-        let result = tls.read_to_end(buf);
-        if let Err(err) = result {
-            if err.kind() != ErrorKind::ConnectionAborted
-                && !err.to_string().contains("CloseNotify")
-            {
-                return Err(ClientError::Tls);
+    fn put_idle(&self, mut connection: Connection) {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < MAX_IDLE {
+            connection.idle_since = Instant::now();
+            idle.push(connection);
+        }
+    }
+
+    fn connect(&self) -> Result<Connection, ClientError> {
+        let socket_addr = self.socket_addr();
+        let sock =
+            TcpStream::connect_timeout(&socket_addr, self.connect_timeout).map_err(|err| {
+                error!("Connect error: {}", err);
+                // The address may have changed, so it is looked up again for the next request
+                self.expire_address();
+                match err.kind() {
+                    ErrorKind::TimedOut | ErrorKind::WouldBlock => ClientError::Timeout,
+                    _ => ClientError::Connect,
+                }
+            })?;
+        sock.set_read_timeout(Some(self.read_timeout))
+            .and_then(|_| sock.set_write_timeout(Some(self.read_timeout)))
+            .and_then(|_| sock.set_nodelay(true))
+            .map_err(|err| {
+                error!("Socket error: {}", err);
+                ClientError::Connect
+            })?;
+
+        let sess = ClientSession::new(&self.client_config, self.dns_name);
+        Ok(Connection {
+            tls: StreamOwned::new(sess, sock),
+            idle_since: Instant::now(),
+        })
+    }
+
+    /// The address of the hostname, looked up again once it is older than the DNS TTL or has
+    /// stopped working
+    fn socket_addr(&self) -> SocketAddr {
+        let mut address = self.address.lock().unwrap_or_else(PoisonError::into_inner);
+        let stale = address.lookup
+            && address
+                .resolved
+                .is_none_or(|resolved| resolved.elapsed() >= DNS_TTL);
+        if stale {
+            match lookup(self.hostname) {
+                Ok(socket_addr) => {
+                    address.socket_addr = socket_addr;
+                    address.resolved = Some(Instant::now());
+                }
+                // Keeps using the last address until the lookup works again
+                Err(err) => error!("Failed to look up {} again: {:?}", self.hostname, err),
             }
         }
-        let size = dst.parse(buf).map_err(|err| {
-            error!("Response parsing error: {}", err);
-            ClientError::Response
+        address.socket_addr
+    }
+
+    fn expire_address(&self) {
+        let mut address = self.address.lock().unwrap_or_else(PoisonError::into_inner);
+        address.resolved = None;
+    }
+}
+
+fn lookup(hostname: &str) -> Result<SocketAddr, ClientError> {
+    let socket_addr = (hostname, 443)
+        .to_socket_addrs()
+        .map_err(|err| {
+            error!("Parsing host name error: {}", err);
+            ClientError::Hostname
+        })?
+        .next();
+    socket_addr.ok_or_else(|| {
+        error!("Failed lookup: {}", hostname);
+        ClientError::Hostname
+    })
+}
+
+/// Writes the request and reads the response into `buf`, the head followed by the decoded body.
+/// Returns whether the connection can be used again
+fn exchange(tls: &mut Tls, request: &[u8], buf: &mut Vec<u8>) -> Result<bool, ClientError> {
+    tls.write_all(request)
+        .and_then(|_| tls.flush())
+        .map_err(|err| {
+            error!("Write error: {}", err);
+            match err.kind() {
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => ClientError::Timeout,
+                _ => ClientError::Write,
+            }
         })?;
 
-        let headers: Vec<(String, String)> = dst
-            .headers
-            .iter()
-            .map(|header| match String::from_utf8(header.value.to_vec()) {
-                Ok(value) => Ok((String::from(header.name), value)),
-                Err(_) => Err(ClientError::Response),
-            })
-            .collect::<Result<Vec<(String, String)>, ClientError>>()?;
+    let mut incoming = Incoming {
+        tls,
+        pending: vec![],
+    };
+    let head = loop {
+        if let Some(head) = parse_head(&incoming.pending)? {
+            break head;
+        }
+        if incoming.fill()? == 0 {
+            if !incoming.pending.is_empty() {
+                error!("Response parsing error: Partially parsed");
+            }
+            buf.append(&mut incoming.pending);
+            return Err(ClientError::Response);
+        }
+    };
+    buf.extend(incoming.pending.drain(..head.length));
+
+    let body = if head.code < 200 || head.code == 204 || head.code == 304 {
+        vec![]
+    } else if head.chunked {
+        incoming.read_chunked()?
+    } else if let Some(length) = head.content_length {
+        incoming.read_exact(length)?
+    } else {
+        // The body is the rest of the connection
+        incoming.read_to_end()?;
+        buf.append(&mut incoming.pending);
+        return Ok(false);
+    };
+    buf.extend(body);
+
+    let reusable = !head.close && incoming.pending.is_empty();
+    Ok(reusable)
+}
+
+/// What is needed from the head of a response to read its body
+struct Head {
+    length: usize,
+    code: u16,
+    content_length: Option<usize>,
+    chunked: bool,
+    /// Whether the server closes the connection after the response
+    close: bool,
+}
 
-        let content_length = headers
+fn parse_head(buf: &[u8]) -> Result<Option<Head>, ClientError> {
+    let mut header_buf = [EMPTY_HEADER; 100];
+    let mut res = httparse::Response::new(&mut header_buf);
+    let length = match res.parse(buf) {
+        Ok(Status::Complete(length)) => length,
+        Ok(Status::Partial) => return Ok(None),
+        Err(err) => {
+            error!("Response parsing error: {}", err);
+            return Err(ClientError::Response);
+        }
+    };
+    let header = |name: &str| {
+        res.headers
             .iter()
-            .find(|(name, _)| name.to_ascii_lowercase() == "content-length");
-        let content_length = match content_length {
-            None => {
-                error!("Missing content-length header");
-                Err(ClientError::Response)
-            }
-            Some((_, value)) => Ok(value),
-        }?;
-        let content_length = content_length.parse::<usize>().map_err(|err| {
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .map(|header| String::from_utf8_lossy(header.value).to_ascii_lowercase())
+    };
+
+    let content_length = match header("content-length") {
+        None => None,
+        Some(value) => Some(value.trim().parse::<usize>().map_err(|err| {
             error!("Content-length could not be parsed as int: {}", err);
             ClientError::Response
-        })?;
+        })?),
+    };
+    let close = match (res.version, header("connection")) {
+        (_, Some(connection)) if connection.contains("close") => true,
+        (Some(0), Some(connection)) => !connection.contains("keep-alive"),
+        (Some(0), None) => true,
+        _ => false,
+    };
 
-        let body = match size {
-            Status::Complete(size) => &buf[size..(size + content_length)],
-            Status::Partial => {
-                error!("Response parsing error: Partially parsed");
-                return Err(ClientError::Response);
+    Ok(Some(Head {
+        length,
+        code: res.code.unwrap_or(0),
+        content_length,
+        chunked: header("transfer-encoding").is_some_and(|value| value.ends_with("chunked")),
+        close,
+    }))
+}
+
+/// Reads the response as it arrives, `pending` holding what has been received but not read
+struct Incoming<'t> {
+    tls: &'t mut Tls,
+    pending: Vec<u8>,
+}
+
+impl Incoming<'_> {
+    /// Receives more of the response, 0 meaning the server closed the connection
+    fn fill(&mut self) -> Result<usize, ClientError> {
+        let mut chunk = [0; 8192];
+        let size = match self.tls.read(&mut chunk) {
+            Ok(size) => size,
+            Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => {
+                error!("Timed out waiting for the response");
+                return Err(ClientError::Timeout);
+            }
+            Err(err)
+                if err.kind() == ErrorKind::ConnectionAborted
+                    || err.kind() == ErrorKind::UnexpectedEof
+                    || err.to_string().contains("CloseNotify") =>
+            {
+                0
+            }
+            Err(err) => {
+                error!("Read error: {}", err);
+                return Err(ClientError::Tls);
             }
         };
+        self.pending.extend_from_slice(&chunk[..size]);
+        Ok(size)
+    }
 
-        Ok((dst, body))
+    fn fill_more(&mut self) -> Result<(), ClientError> {
+        match self.fill()? {
+            0 => {
+                error!("Response ended early");
+                Err(ClientError::Response)
+            }
+            _ => Ok(()),
+        }
     }
 
-    fn path(req: &'_ Request<String>) -> Result<&'_ str, ClientError> {
-        match req.uri().path_and_query() {
-            None => Err(ClientError::Write),
-            Some(path) => Ok(path.as_str()),
+    fn read_exact(&mut self, length: usize) -> Result<Vec<u8>, ClientError> {
+        while self.pending.len() < length {
+            self.fill_more()?;
         }
+        Ok(self.pending.drain(..length).collect())
     }
 
-    fn tls<'s>(
-        &self,
-        sess: &'s mut ClientSession,
-        sock: &'s mut TcpStream,
-    ) -> Result<Stream<'s, ClientSession, TcpStream>, ClientError> {
-        Ok(rustls::Stream::new(sess, sock))
+    fn read_to_end(&mut self) -> Result<(), ClientError> {
+        while self.fill()? > 0 {}
+        Ok(())
     }
 
-    fn session(&self) -> Result<ClientSession, ClientError> {
-        Ok(rustls::ClientSession::new(
-            &self.client_config,
-            self.dns_name,
-        ))
+    fn read_chunked(&mut self) -> Result<Vec<u8>, ClientError> {
+        let mut body = vec![];
+        loop {
+            let (offset, size) = loop {
+                match httparse::parse_chunk_size(&self.pending) {
+                    Ok(Status::Complete((offset, size))) => break (offset, size as usize),
+                    Ok(Status::Partial) => self.fill_more()?,
+                    Err(_) => {
+                        error!("Response parsing error: Invalid chunk");
+                        return Err(ClientError::Response);
+                    }
+                }
+            };
+            self.pending.drain(..offset);
+            if size == 0 {
+                // Trailers are not used, but end with an empty line like the headers
+                loop {
+                    if let Some(end) = self.pending.windows(2).position(|w| w == b"\r\n") {
+                        let line: Vec<u8> = self.pending.drain(..end + 2).collect();
+                        if line.len() == 2 {
+                            return Ok(body);
+                        }
+                    } else {
+                        self.fill_more()?;
+                    }
+                }
+            }
+            body.extend(self.read_exact(size)?);
+            if self.read_exact(2)? != b"\r\n" {
+                error!("Response parsing error: Chunk is longer than its size");
+                return Err(ClientError::Response);
+            }
+        }
     }
+}
 
-    fn socket(&self) -> Result<TcpStream, ClientError> {
-        TcpStream::connect(self.socket_addr).map_err(|err| {
-            error!("Connect error: {}", err);
-            ClientError::Connect
-        })
+#[cfg(test)]
+mod test {
+    use crate::http::client::{Client, ClientError};
+    use crate::http::test::{client_config, serve_connections};
+    use httparse::EMPTY_HEADER;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    fn response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    /// The status and body of a POST to `path`
+    fn post(client: &Client, path: &str, body: &str) -> Result<(u16, String), ClientError> {
+        let req = http::Request::builder()
+            .method("POST")
+            .uri(format!("https://localhost{}", path))
+            .body(body.to_string())
+            .unwrap();
+        let mut header_buf = [EMPTY_HEADER; 16];
+        let mut res = httparse::Response::new(&mut header_buf);
+        let mut buf = vec![];
+        let (res, body) = client.send(req, &mut buf, &mut res)?;
+        Ok((res.code.unwrap(), String::from_utf8(body.to_vec()).unwrap()))
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let (addr, handle) = serve_connections(vec![vec![
+            response("{\"a\":1}"),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            3\r\n{\"b\r\n4;ext=1\r\n\":2}\r\n0\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 204 No Content\r\n\r\n".to_string(),
+        ]]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+
+        assert_eq!(
+            post(&client, "/a", "1").unwrap(),
+            (200, "{\"a\":1}".to_string())
+        );
+        assert_eq!(
+            post(&client, "/b", "2").unwrap(),
+            (200, "{\"b\":2}".to_string())
+        );
+        assert_eq!(post(&client, "/c", "").unwrap(), (204, String::new()));
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 1, "Every request should use one connection");
+        assert!(requests[0][1].starts_with("POST https://localhost/b HTTP/1.1\r\n"));
+        assert!(requests[0][1].ends_with("\r\n\r\n2"));
+        assert!(!requests[0][0]
+            .to_ascii_lowercase()
+            .contains("connection: close"));
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let (addr, handle) = serve_connections(vec![
+            vec![response("1")],
+            vec!["HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 1\r\n\r\n2".to_string()],
+            vec!["HTTP/1.1 200 OK\r\n\r\n3 until closed".to_string()],
+            vec![response("4")],
+        ]);
+        let client = Client::with_config("localhost", addr, client_config()).unwrap();
+
+        assert_eq!(post(&client, "/", "").unwrap().1, "1");
+        // The server closed the connection while it was idle
+        assert_eq!(post(&client, "/", "").unwrap().1, "2");
+        assert_eq!(post(&client, "/", "").unwrap().1, "3 until closed");
+        assert_eq!(post(&client, "/", "").unwrap().1, "4");
+
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            // Accepts the connection but never answers
+            let (sock, _) = listener.accept().unwrap();
+            thread::sleep(Duration::from_millis(500));
+            drop(sock);
+        });
+
+        let mut client = Client::with_config("localhost", addr, client_config()).unwrap();
+        client.read_timeout = Duration::from_millis(100);
+        assert_eq!(post(&client, "/", ""), Err(ClientError::Timeout));
+        handle.join().unwrap();
     }
 }
@@ -1,2 +1,5 @@
 pub mod client;
 pub mod server;
+
+#[cfg(test)]
+pub mod test;
@@ -1,17 +1,31 @@
-use crate::http::server::ServerError::Internal;
 use httparse::{Status, EMPTY_HEADER};
 use log::{error, info};
 
 use http::Uri;
 
 use serde_json::{json, Value};
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
 use std::net::TcpStream;
+use std::time::Duration;
+
+/// Bodies larger than this are refused with 413, updates being far smaller
+pub const MAX_BODY_SIZE: usize = 1_000_000;
+/// The request line and headers together, refused with 431 when larger
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+const MAX_HEADERS: usize = 64;
+/// How long a request may take to arrive once it has begun
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a connection is kept open for the next request, which holds up a worker meanwhile
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub enum ServerError {
     Internal,
     Client(&'static str),
+    Unauthorized,
+    NotFound,
+    /// The methods the path does allow
+    MethodNotAllowed(&'static str),
 }
 
 pub type Result<T> = std::result::Result<T, ServerError>;
@@ -23,193 +37,715 @@ pub trait Handler {
         path: Uri,
         headers: Vec<(String, String)>,
         body: Value,
+        raw_body: &[u8],
     ) -> Result<Option<Value>>;
 }
 
 pub struct Server<'s> {
     handler: &'s dyn Handler,
+    read_timeout: Duration,
+    idle_timeout: Duration,
 }
 
 impl<'s> Server<'s> {
     pub fn new(handler: &'s dyn Handler) -> Self {
-        Server { handler }
+        Server {
+            handler,
+            read_timeout: READ_TIMEOUT,
+            idle_timeout: IDLE_TIMEOUT,
+        }
+    }
+
+    /// Serves requests until the client closes the connection, asks for it to be closed or
+    /// leaves it idle, or sends a request that can not be read
+    pub fn handle_connection(&self, stream: TcpStream) {
+        let mut connection = Connection {
+            stream,
+            buf: vec![],
+        };
+        loop {
+            let request = match connection.read_request(self) {
+                Ok(request) => request,
+                Err(RequestError::Closed) | Err(RequestError::Io) => return,
+                Err(err) => {
+                    info!("Rejected request: {:?}", err);
+                    if let Some(status) = err.status() {
+                        let _result = connection.send(&Response::empty(status), false);
+                    }
+                    return;
+                }
+            };
+
+            let keep_alive = request.keep_alive;
+            let response = self.respond(request);
+            if connection.send(&response, keep_alive).is_err() || !keep_alive {
+                return;
+            }
+        }
     }
 
-    pub fn handle_connection(&self, mut stream: TcpStream) -> Result<()> {
-        let Response {
+    fn respond(&self, request: Request) -> Response {
+        let body = match request.json() {
+            Ok(body) => body,
+            Err(err) => {
+                info!("Could not parse body as json: {}", err);
+                return Response::error(400, "INVALID_JSON");
+            }
+        };
+        let Request {
             method,
             path,
             headers,
-            body,
-        } = read_stream(&mut stream)?;
-
-        let result = self.handler.handle(method, path, headers, body);
-        match result {
-            Ok(None) => send_response_empty(&mut stream),
-            Ok(Some(body)) => send_response(&mut stream, body),
-            Err(ServerError::Client(reason)) => send_error_response(&mut stream, reason),
+            body: raw_body,
+            ..
+        } = request;
+
+        match self.handler.handle(method, path, headers, body, &raw_body) {
+            Ok(None) => Response::empty(200),
+            Ok(Some(body)) => Response {
+                status: 200,
+                body: Some(body),
+                allow: None,
+            },
+            Err(ServerError::Client(reason)) => Response::error(400, reason),
+            Err(ServerError::Unauthorized) => Response::empty(401),
+            Err(ServerError::NotFound) => Response::empty(404),
+            Err(ServerError::MethodNotAllowed(allow)) => Response {
+                allow: Some(allow),
+                ..Response::empty(405)
+            },
             Err(err) => {
                 error!("Internal server error: {:?}", err);
-                send_response_empty(&mut stream)
+                Response::empty(500)
             }
         }
     }
 }
 
-fn send_error_response(stream: &mut TcpStream, reason: &'static str) -> Result<()> {
-    let body = json!({ "error": reason }).to_string();
-    let result = stream.write_fmt(format_args!(
-        "HTTP/1.0 400 Bad Request\r\n\
-            Content-Length: {length}\r\n\
-            Content-Type: application/json\r\n\r\n\
-            {body}\r\n",
-        length = body.len(),
-        body = body
-    ));
+/// Why a request could not be read, answered with a status when the client is still listening
+#[derive(Debug, PartialEq)]
+enum RequestError {
+    /// The connection closed or went idle between requests
+    Closed,
+    TimedOut,
+    Malformed,
+    HeadTooLarge,
+    BodyTooLarge,
+    Io,
+}
 
-    if let Err(err) = result {
-        error!("Failed to write to stream for response: {:?}", err);
-        return Ok(());
+impl RequestError {
+    fn status(&self) -> Option<u16> {
+        match self {
+            RequestError::Closed | RequestError::Io => None,
+            RequestError::TimedOut => Some(408),
+            RequestError::Malformed => Some(400),
+            RequestError::HeadTooLarge => Some(431),
+            RequestError::BodyTooLarge => Some(413),
+        }
     }
+}
 
-    let result = stream.flush();
+struct Request {
+    method: String,
+    path: Uri,
+    headers: Vec<(String, String)>,
+    /// The body as it was received, needed to verify signed requests
+    body: Vec<u8>,
+    keep_alive: bool,
+}
 
-    if let Err(err) = result {
-        error!("Failed to flush response: {:?}", err);
-        return Ok(());
+impl Request {
+    /// Only bodies sent as json are parsed, anything else is left to the raw body
+    fn json(&self) -> serde_json::Result<Value> {
+        let is_json = header(&self.headers, "content-type").is_some_and(|content_type| {
+            content_type
+                .to_ascii_lowercase()
+                .starts_with("application/json")
+        });
+        if !is_json || self.body.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&self.body)
     }
+}
+
+/// The request line and headers
+struct Head {
+    method: String,
+    path: Uri,
+    /// The minor version, 0 for HTTP/1.0 and 1 for HTTP/1.1
+    version: u8,
+    headers: Vec<(String, String)>,
+}
 
-    Ok(())
+struct Response {
+    status: u16,
+    body: Option<Value>,
+    /// Sent with 405 responses
+    allow: Option<&'static str>,
 }
 
-fn send_response_empty(stream: &mut TcpStream) -> Result<()> {
-    let result = stream.write(b"HTTP/1.1 200 OK\r\n\r\n");
-    if let Err(err) = result {
-        error!("Failed to write to stream for response: {:?}", err);
-        return Ok(());
+impl Response {
+    fn empty(status: u16) -> Self {
+        Response {
+            status,
+            body: None,
+            allow: None,
+        }
     }
 
-    let result = stream.flush();
-
-    if let Err(err) = result {
-        error!("Failed to flush response: {:?}", err);
-        return Ok(());
+    fn error(status: u16, reason: &'static str) -> Self {
+        Response {
+            status,
+            body: Some(json!({ "error": reason })),
+            allow: None,
+        }
     }
+}
 
-    Ok(())
+struct Connection {
+    stream: TcpStream,
+    /// Received but not yet read, which may include the start of the next request
+    buf: Vec<u8>,
 }
 
-fn send_response<T: ToString>(stream: &mut TcpStream, body: T) -> Result<()> {
-    let body = body.to_string();
-    let result = stream.write_fmt(format_args!(
-        "HTTP/1.0 200 OK\r\n\
-            Content-Length: {length}\r\n\
-            Content-Type: application/json\r\n\r\n\
-            {body}\r\n",
-        length = body.len(),
-        body = body
-    ));
+impl Connection {
+    fn read_request(&mut self, server: &Server) -> std::result::Result<Request, RequestError> {
+        if self.buf.is_empty() {
+            self.set_timeout(server.idle_timeout)?;
+            match self.fill() {
+                Ok(0) | Err(RequestError::TimedOut) => return Err(RequestError::Closed),
+                Ok(_) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.set_timeout(server.read_timeout)?;
 
-    if let Err(err) = result {
-        error!("Failed to write to stream for response: {:?}", err);
-        return Ok(());
+        let head = loop {
+            if let Some((length, head)) = parse_head(&self.buf)? {
+                self.buf.drain(..length);
+                break head;
+            }
+            if self.buf.len() > MAX_HEAD_SIZE {
+                return Err(RequestError::HeadTooLarge);
+            }
+            self.fill_more()?;
+        };
+
+        let transfer_encoding = header(&head.headers, "transfer-encoding");
+        let content_length = header(&head.headers, "content-length");
+        let body = match (transfer_encoding, content_length) {
+            (Some(encoding), _) => {
+                if !encoding
+                    .to_ascii_lowercase()
+                    .trim_end()
+                    .ends_with("chunked")
+                {
+                    return Err(RequestError::Malformed);
+                }
+                self.send_continue(&head)?;
+                self.read_chunked()?
+            }
+            (None, Some(length)) => {
+                let length = length
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_err| RequestError::Malformed)?;
+                if length > MAX_BODY_SIZE {
+                    return Err(RequestError::BodyTooLarge);
+                }
+                self.send_continue(&head)?;
+                self.read_exact(length)?
+            }
+            (None, None) => vec![],
+        };
+
+        let connection = header(&head.headers, "connection")
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let keep_alive = match head.version {
+            0 => connection.contains("keep-alive"),
+            _ => !connection.contains("close"),
+        };
+
+        Ok(Request {
+            method: head.method,
+            path: head.path,
+            headers: head.headers,
+            body,
+            keep_alive,
+        })
     }
 
-    let result = stream.flush();
+    /// Tells a client waiting to send the body that it will be read
+    fn send_continue(&mut self, head: &Head) -> std::result::Result<(), RequestError> {
+        let expects = header(&head.headers, "expect")
+            .is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+        if expects && head.version == 1 {
+            self.stream
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\n")
+                .map_err(|_err| RequestError::Io)?;
+        }
+        Ok(())
+    }
 
-    if let Err(err) = result {
-        error!("Failed to flush response: {:?}", err);
-        return Ok(());
+    fn read_chunked(&mut self) -> std::result::Result<Vec<u8>, RequestError> {
+        let mut body = vec![];
+        loop {
+            let line = self.read_line()?;
+            let line = std::str::from_utf8(&line).map_err(|_err| RequestError::Malformed)?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_err| RequestError::Malformed)?;
+            if size == 0 {
+                // Trailers are not used, but have to be read up to the empty line ending them
+                while !self.read_line()?.is_empty() {}
+                return Ok(body);
+            }
+            if body.len() + size > MAX_BODY_SIZE {
+                return Err(RequestError::BodyTooLarge);
+            }
+            body.extend(self.read_exact(size)?);
+            if !self.read_line()?.is_empty() {
+                return Err(RequestError::Malformed);
+            }
+        }
     }
 
-    Ok(())
-}
+    /// The next line, without its line break
+    fn read_line(&mut self) -> std::result::Result<Vec<u8>, RequestError> {
+        loop {
+            if let Some(end) = self.buf.windows(2).position(|window| window == b"\r\n") {
+                let line = self.buf.drain(..end + 2).take(end).collect();
+                return Ok(line);
+            }
+            if self.buf.len() > MAX_HEAD_SIZE {
+                return Err(RequestError::Malformed);
+            }
+            self.fill_more()?;
+        }
+    }
 
-struct Response {
-    method: String,
-    path: Uri,
-    headers: Vec<(String, String)>,
-    body: Value,
+    fn read_exact(&mut self, length: usize) -> std::result::Result<Vec<u8>, RequestError> {
+        while self.buf.len() < length {
+            self.fill_more()?;
+        }
+        Ok(self.buf.drain(..length).collect())
+    }
+
+    /// Reads more of a request that has begun, which must not end before it is complete
+    fn fill_more(&mut self) -> std::result::Result<(), RequestError> {
+        match self.fill()? {
+            0 => Err(RequestError::Malformed),
+            _ => Ok(()),
+        }
+    }
+
+    fn fill(&mut self) -> std::result::Result<usize, RequestError> {
+        let mut chunk = [0; 8192];
+        let size = self
+            .stream
+            .read(&mut chunk)
+            .map_err(|err| match err.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => RequestError::TimedOut,
+                _ => {
+                    error!("Failed reading request {}", err);
+                    RequestError::Io
+                }
+            })?;
+        self.buf.extend_from_slice(&chunk[..size]);
+        Ok(size)
+    }
+
+    fn set_timeout(&self, timeout: Duration) -> std::result::Result<(), RequestError> {
+        self.stream.set_read_timeout(Some(timeout)).map_err(|err| {
+            error!("Failed to set read timeout {}", err);
+            RequestError::Io
+        })
+    }
+
+    fn send(&mut self, response: &Response, keep_alive: bool) -> std::io::Result<()> {
+        let body = response
+            .body
+            .as_ref()
+            .map(Value::to_string)
+            .unwrap_or_default();
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            response.status,
+            reason(response.status),
+            body.len()
+        );
+        if response.body.is_some() {
+            head.push_str("Content-Type: application/json\r\n");
+        }
+        if let Some(allow) = response.allow {
+            head.push_str(&format!("Allow: {}\r\n", allow));
+        }
+        // HTTP/1.0 clients asking to keep the connection are answered in kind
+        head.push_str(match keep_alive {
+            true => "Connection: keep-alive\r\n\r\n",
+            false => "Connection: close\r\n\r\n",
+        });
+
+        let result = self
+            .stream
+            .write_all(head.as_bytes())
+            .and_then(|_| self.stream.write_all(body.as_bytes()))
+            .and_then(|_| self.stream.flush());
+        if let Err(err) = &result {
+            error!("Failed to write response: {:?}", err);
+        }
+        result
+    }
 }
 
-fn read_stream(stream: &mut TcpStream) -> Result<Response> {
-    let mut buf = [0; 1_000_000];
-    stream.read(&mut buf).map_err(|err| {
-        error!("Failed reading request {}", err);
-        ServerError::Internal
-    })?;
-    let mut header_buf = [EMPTY_HEADER; 64];
+/// The head of the request once all of it has been received
+fn parse_head(buf: &[u8]) -> std::result::Result<Option<(usize, Head)>, RequestError> {
+    let mut header_buf = [EMPTY_HEADER; MAX_HEADERS];
     let mut req = httparse::Request::new(&mut header_buf);
-    let body = req.parse(&buf).map_err(|err| {
-        error!("Failed parsing request {}", err);
-        ServerError::Internal
-    })?;
-    let method = match req.method {
-        None => {
-            error!("Expecting method for request, none found");
-            Err(ServerError::Internal)
-        }
-        Some(method) => Ok(method),
-    }?;
-    let path = match req.path {
-        None => {
-            error!("Expecting path for request, none found");
-            Err(ServerError::Internal)
-        }
-        Some(path) => Ok(path),
-    }?;
+    let length = match req.parse(buf) {
+        Ok(Status::Complete(length)) => length,
+        Ok(Status::Partial) => return Ok(None),
+        Err(httparse::Error::TooManyHeaders) => return Err(RequestError::HeadTooLarge),
+        Err(err) => {
+            info!("Failed parsing request {}", err);
+            return Err(RequestError::Malformed);
+        }
+    };
+
+    let (method, path, version) = match (req.method, req.path, req.version) {
+        (Some(method), Some(path), Some(version)) => (method, path, version),
+        _ => return Err(RequestError::Malformed),
+    };
     let path = path.parse::<Uri>().map_err(|err| {
-        error!("Could not parse path: {} {}", path, err);
-        ServerError::Internal
+        info!("Could not parse path: {} {}", path, err);
+        RequestError::Malformed
     })?;
-
-    let headers: Vec<(String, String)> = req
+    let headers = req
         .headers
         .iter()
         .map(|header| match String::from_utf8(header.value.to_vec()) {
             Ok(value) => Ok((String::from(header.name), value)),
-            Err(_) => Err(Internal),
+            Err(_) => Err(RequestError::Malformed),
         })
-        .collect::<Result<Vec<(String, String)>>>()?;
-
-    let body = get_body(&headers, &buf, body)?;
+        .collect::<std::result::Result<Vec<(String, String)>, RequestError>>()?;
 
-    Ok(Response {
-        method: method.to_string(),
-        path,
-        headers,
-        body,
-    })
+    Ok(Some((
+        length,
+        Head {
+            method: method.to_string(),
+            path,
+            version,
+            headers,
+        },
+    )))
 }
 
-fn get_body(headers: &[(String, String)], buf: &[u8], body_start: Status<usize>) -> Result<Value> {
-    let content_length = headers
+fn header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
         .iter()
-        .find(|(name, _)| name.to_ascii_lowercase() == "content-length");
-    let content_length = match content_length {
-        None => {
-            info!("Empty body");
-            return Ok(Value::Null);
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::http::server::{Handler, Result, Server, ServerError, MAX_BODY_SIZE};
+    use http::Uri;
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::thread;
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+
+    /// Answers with what it received, or with the error the path names
+    struct EchoHandler;
+
+    impl Handler for EchoHandler {
+        fn handle(
+            &self,
+            method: String,
+            path: Uri,
+            _headers: Vec<(String, String)>,
+            body: Value,
+            raw_body: &[u8],
+        ) -> Result<Option<Value>> {
+            match path.path() {
+                "/missing" => Err(ServerError::NotFound),
+                "/get" => Err(ServerError::MethodNotAllowed("GET")),
+                "/fail" => Err(ServerError::Internal),
+                "/empty" => Ok(None),
+                _ => Ok(Some(json!({
+                    "method": method,
+                    "path": path.to_string(),
+                    "body": body,
+                    "raw_body": String::from_utf8_lossy(raw_body),
+                }))),
+            }
         }
-        Some((_, value)) => value,
-    };
-    let content_length = content_length.parse::<usize>().map_err(|err| {
-        error!("Content-length could not be parsed as int: {}", err);
-        ServerError::Internal
-    })?;
+    }
 
-    let body = match body_start {
-        Status::Complete(body) => &buf[body..(body + content_length)],
-        Status::Partial => {
-            error!("Only partially parsed request");
-            return Err(Internal);
+    /// Serves one connection, with timeouts short enough to test
+    fn serve() -> (SocketAddr, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let server = Server {
+                handler: &EchoHandler,
+                read_timeout: Duration::from_millis(200),
+                idle_timeout: Duration::from_millis(200),
+            };
+            server.handle_connection(stream);
+        });
+        (addr, handle)
+    }
+
+    struct Client {
+        stream: TcpStream,
+        buf: Vec<u8>,
+    }
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Client {
+                stream,
+                buf: vec![],
+            }
         }
-    };
 
-    let body = serde_json::from_slice(body).map_err(|err| {
-        error!("Could not parse body as utf8: {}", err);
-        ServerError::Internal
-    })?;
+        fn send(&mut self, request: &[u8]) {
+            self.stream.write_all(request).unwrap();
+        }
 
-    Ok(body)
+        /// The status, headers and body of the next response
+        fn receive(&mut self) -> (u16, Vec<(String, String)>, String) {
+            loop {
+                let mut header_buf = [httparse::EMPTY_HEADER; 16];
+                let mut res = httparse::Response::new(&mut header_buf);
+                if let httparse::Status::Complete(start) = res.parse(&self.buf).unwrap() {
+                    let headers: Vec<(String, String)> = res
+                        .headers
+                        .iter()
+                        .map(|header| {
+                            (
+                                header.name.to_ascii_lowercase(),
+                                String::from_utf8_lossy(header.value).to_string(),
+                            )
+                        })
+                        .collect();
+                    let status = res.code.unwrap();
+                    let length: usize = headers
+                        .iter()
+                        .find(|(name, _)| name == "content-length")
+                        .map(|(_, value)| value.parse().unwrap())
+                        .unwrap();
+                    if self.buf.len() >= start + length {
+                        let body = self.buf[start..start + length].to_vec();
+                        self.buf.drain(..start + length);
+                        return (status, headers, String::from_utf8(body).unwrap());
+                    }
+                }
+                let mut chunk = [0; 4096];
+                let size = self.stream.read(&mut chunk).unwrap();
+                assert!(size > 0, "The connection closed before the response");
+                self.buf.extend_from_slice(&chunk[..size]);
+            }
+        }
+
+        fn is_closed(&mut self) -> bool {
+            let mut chunk = [0; 1];
+            matches!(self.stream.read(&mut chunk), Ok(0) | Err(_)) && self.buf.is_empty()
+        }
+    }
+
+    fn header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        let body = r#"{"answer":"a"}"#;
+        client.send(
+            format!(
+                "POST /app HTTP/1.1\r\nContent-Type: application/json\r\n\
+                Content-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        );
+        let (status, headers, response) = client.receive();
+        assert_eq!(status, 200);
+        assert_eq!(header(&headers, "connection"), Some("keep-alive"));
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["body"], json!({"answer": "a"}));
+
+        // Both sent at once, the second being read from what is left over from the first
+        client.send(
+            b"GET /empty HTTP/1.1\r\n\r\nGET /app?token=1 HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+        let (status, _, response) = client.receive();
+        assert_eq!(status, 200);
+        assert_eq!(response, "");
+        let (status, headers, response) = client.receive();
+        assert_eq!(status, 200);
+        assert_eq!(header(&headers, "connection"), Some("close"));
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["path"], "/app?token=1");
+
+        assert!(client.is_closed());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_http_1_0() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        client.send(b"GET /empty HTTP/1.0\r\n\r\n");
+        let (status, headers, _) = client.receive();
+        assert_eq!(status, 200);
+        assert_eq!(
+            header(&headers, "connection"),
+            Some("close"),
+            "HTTP/1.0 connections should close unless asked to be kept"
+        );
+        assert!(client.is_closed());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_chunked() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        client.send(
+            b"POST /webhook HTTP/1.1\r\nContent-Type: application/json\r\n\
+            Transfer-Encoding: chunked\r\n\r\n\
+            5\r\n{\"a\":\r\n5;ext=1\r\n \"b\"}\r\n0\r\nX-Trailer: 1\r\n\r\n",
+        );
+        let (status, _, response) = client.receive();
+        assert_eq!(status, 200);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["body"], json!({"a": "b"}));
+
+        client.send(b"POST /webhook HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n");
+        let (status, _, _) = client.receive();
+        assert_eq!(status, 400);
+        assert!(client.is_closed());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        client.send(b"GET /missing HTTP/1.1\r\n\r\n");
+        assert_eq!(client.receive().0, 404);
+
+        client.send(b"POST /get HTTP/1.1\r\n\r\n");
+        let (status, headers, _) = client.receive();
+        assert_eq!(status, 405);
+        assert_eq!(header(&headers, "allow"), Some("GET"));
+
+        client.send(b"GET /fail HTTP/1.1\r\n\r\n");
+        assert_eq!(client.receive().0, 500);
+
+        client.send(b"POST /app HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 3\r\n\r\n{\"a");
+        let (status, _, response) = client.receive();
+        assert_eq!(status, 400);
+        assert_eq!(response, r#"{"error":"INVALID_JSON"}"#);
+
+        client.send(
+            b"POST /app HTTP/1.1\r\nContent-Type: text/plain\r\nContent-Length: 3\r\n\r\n{\"a",
+        );
+        let (status, _, response) = client.receive();
+        assert_eq!(
+            status, 200,
+            "Bodies that are not json should be left unparsed"
+        );
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(response["body"], Value::Null);
+        assert_eq!(response["raw_body"], "{\"a");
+
+        client.send(
+            format!(
+                "POST /app HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY_SIZE + 1
+            )
+            .as_bytes(),
+        );
+        assert_eq!(client.receive().0, 413);
+        assert!(client.is_closed());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        client.send(b"POST /app HTTP/1.1\r\nContent-Length: 2\r\nExpect: 100-continue\r\n\r\n");
+        let mut interim = [0; 25];
+        client.stream.read_exact(&mut interim).unwrap();
+        assert_eq!(&interim, b"HTTP/1.1 100 Continue\r\n\r\n");
+
+        client.send(b"hi\r\n");
+        assert_eq!(client.receive().0, 200);
+        drop(client);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+
+        client.send(b"POST /app HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc");
+        assert_eq!(
+            client.receive().0,
+            408,
+            "A request that stops arriving should time out"
+        );
+        assert!(client.is_closed());
+        handle.join().unwrap();
+
+        let (addr, handle) = serve();
+        let mut client = Client::connect(addr);
+        client.send(b"GET /empty HTTP/1.1\r\n\r\n");
+        assert_eq!(client.receive().0, 200);
+        assert!(
+            client.is_closed(),
+            "An idle connection should be closed without a response"
+        );
+        handle.join().unwrap();
+    }
 }
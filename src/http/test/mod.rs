@@ -0,0 +1,96 @@
+use httparse::{Status, EMPTY_HEADER};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys};
+use rustls::{ClientConfig, NoClientAuth, ServerConfig, ServerSession, Session};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+
+const CA: &[u8] = include_bytes!("ca.pem");
+const CERT: &[u8] = include_bytes!("localhost.pem");
+const KEY: &[u8] = include_bytes!("localhost.key");
+
+/// Client config that trusts the certificate of the stub server
+pub fn client_config() -> ClientConfig {
+    let mut client_config = ClientConfig::new();
+    client_config.root_store.add_pem_file(&mut &CA[..]).unwrap();
+    client_config
+}
+
+/// Starts a TLS server for `localhost` that answers each connection with the next response,
+/// returning the requests it received once every response has been sent
+pub fn serve(responses: Vec<String>) -> (SocketAddr, JoinHandle<Vec<String>>) {
+    let connections = responses
+        .into_iter()
+        .map(|response| vec![response])
+        .collect();
+    let (addr, handle) = serve_connections(connections);
+    let handle = thread::spawn(move || handle.join().unwrap().into_iter().flatten().collect());
+    (addr, handle)
+}
+
+/// Like `serve`, but answers several requests on each connection before closing it, returning
+/// the requests received on each connection
+pub fn serve_connections(
+    connections: Vec<Vec<String>>,
+) -> (SocketAddr, JoinHandle<Vec<Vec<String>>>) {
+    let mut server_config = ServerConfig::new(NoClientAuth::new());
+    let key = pkcs8_private_keys(&mut &KEY[..]).unwrap().remove(0);
+    server_config
+        .set_single_cert(certs(&mut &CERT[..]).unwrap(), key)
+        .unwrap();
+    let server_config = Arc::new(server_config);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let handle = thread::spawn(move || {
+        let mut requests = vec![];
+        for responses in connections {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut sess = ServerSession::new(&server_config);
+            let mut tls = rustls::Stream::new(&mut sess, &mut sock);
+
+            let mut received = vec![];
+            for response in responses {
+                let mut buf = vec![];
+                while !is_complete(&buf) {
+                    let mut chunk = [0; 4096];
+                    let size = tls.read(&mut chunk).unwrap();
+                    buf.extend_from_slice(&chunk[..size]);
+                }
+                received.push(String::from_utf8(buf).unwrap());
+
+                tls.write_all(response.as_bytes()).unwrap();
+                tls.flush().unwrap();
+            }
+            tls.sess.send_close_notify();
+            tls.flush().unwrap();
+            requests.push(received);
+        }
+        requests
+    });
+
+    (addr, handle)
+}
+
+fn is_complete(buf: &[u8]) -> bool {
+    let mut header_buf = [EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut header_buf);
+    let start = match req.parse(buf) {
+        Ok(Status::Complete(start)) => start,
+        _ => return false,
+    };
+    let content_length = req
+        .headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case("content-length"))
+        .map(|header| {
+            String::from_utf8_lossy(header.value)
+                .parse::<usize>()
+                .unwrap()
+        })
+        .unwrap_or(0);
+    buf.len() >= start + content_length
+}
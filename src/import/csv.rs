@@ -0,0 +1,79 @@
+use std::mem;
+
+/// Splits CSV into rows of fields. Quoted fields may contain commas, line breaks and quotes
+/// written twice, blank lines are skipped
+pub fn parse(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(mem::take(&mut field));
+                push_row(&mut rows, mem::take(&mut row));
+                line += 1;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(format!("Unterminated quote on line {}", line));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        push_row(&mut rows, row);
+    }
+
+    Ok(rows)
+}
+
+fn push_row(rows: &mut Vec<Vec<String>>, row: Vec<String>) {
+    if row.len() > 1 || row.iter().any(|field| !field.trim().is_empty()) {
+        rows.push(row);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::import::csv::parse;
+
+    #[test]
+    fn test_parse() {
+        let rows = parse("a,b\r\n\n\"c, \"\"d\"\"\",\"e\nf\"\ng,").unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["c, \"d\"".to_string(), "e\nf".to_string()],
+                vec!["g".to_string(), "".to_string()],
+            ]
+        );
+
+        assert!(parse("a,\"b").is_err());
+    }
+}
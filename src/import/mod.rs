@@ -0,0 +1,261 @@
+//! Imports question packs from JSON and CSV files
+
+use crate::config::Storage;
+use crate::persistence::pack::Dao;
+use crate::persistence::{Connection, DaoError, Daos};
+use log::info;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub mod csv;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The file is not laid out as a pack, with the reason why
+    Format(String),
+    Connection(String),
+    Persistence(DaoError),
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(err: std::io::Error) -> Self {
+        ImportError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(err: serde_json::Error) -> Self {
+        ImportError::Json(err)
+    }
+}
+
+impl From<DaoError> for ImportError {
+    fn from(err: DaoError) -> Self {
+        ImportError::Persistence(err)
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(err) => write!(f, "Could not read file: {}", err),
+            ImportError::Json(err) => write!(f, "Invalid JSON: {}", err),
+            ImportError::Format(reason) => write!(f, "{}", reason),
+            ImportError::Connection(err) => write!(f, "Could not connect to database: {}", err),
+            ImportError::Persistence(err) => write!(f, "Could not save pack: {}", err),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ImportError>;
+
+/// Questions read from a pack file
+#[derive(Debug, PartialEq)]
+pub struct PackFile {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub questions: Vec<String>,
+}
+
+/// Imports every pack in the files into the database
+pub fn run(storage: &Storage, paths: &[String]) -> Result<()> {
+    let connection = Connection::new(storage).map_err(ImportError::Connection)?;
+    let daos = Daos::new(&connection);
+    load(daos.pack.as_ref(), paths)
+}
+
+/// Imports every pack in the files, adding to packs that already exist
+pub fn load(dao: &dyn Dao, paths: &[String]) -> Result<()> {
+    for path in paths {
+        for pack in read(Path::new(path))? {
+            let imported = dao.import(&pack.name, &pack.tags, &pack.questions)?;
+            info!(
+                "Imported {} of {} questions into pack {}",
+                imported,
+                pack.questions.len(),
+                pack.name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the packs in a `.json` or `.csv` file, packs without a name being named after the file
+pub fn read(path: &Path) -> Result<Vec<PackFile>> {
+    let name = path
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let input = fs::read_to_string(path)?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => parse_json(&name, &input),
+        Some("csv") => parse_csv(&input),
+        _ => Err(ImportError::Format(format!(
+            "Expected a .json or .csv file: {}",
+            path.display()
+        ))),
+    }
+}
+
+/// Accepts a list of questions such as `questions.json`, a pack object with a `name`, optional
+/// `tags` and `questions`, or a list of pack objects
+pub fn parse_json(name: &str, input: &str) -> Result<Vec<PackFile>> {
+    match serde_json::from_str(input)? {
+        Value::Array(items) if items.iter().all(Value::is_string) => Ok(vec![PackFile {
+            name: name.to_string(),
+            tags: vec![],
+            questions: strings(&Value::Array(items), "questions")?,
+        }]),
+        Value::Array(items) => items.iter().map(pack).collect(),
+        object @ Value::Object(_) => Ok(vec![pack(&object)?]),
+        _ => Err(ImportError::Format(
+            "Expected a list of questions or packs".to_string(),
+        )),
+    }
+}
+
+fn pack(value: &Value) -> Result<PackFile> {
+    let name = value
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| ImportError::Format("Pack is missing its name".to_string()))?;
+    let tags = match value.get("tags") {
+        None => vec![],
+        Some(tags) => strings(tags, "tags")?,
+    };
+    let questions = value
+        .get("questions")
+        .ok_or_else(|| ImportError::Format(format!("Pack {} has no questions", name)))?;
+
+    Ok(PackFile {
+        name: name.to_string(),
+        tags,
+        questions: strings(questions, "questions")?,
+    })
+}
+
+fn strings(value: &Value, field: &str) -> Result<Vec<String>> {
+    value
+        .as_array()
+        .and_then(|items| {
+            items
+                .iter()
+                .map(|item| item.as_str().map(String::from))
+                .collect()
+        })
+        .ok_or_else(|| ImportError::Format(format!("Expected {} to be a list of strings", field)))
+}
+
+/// Rows are `pack,tags,question`, tags being separated by semicolons. A header row is skipped
+pub fn parse_csv(input: &str) -> Result<Vec<PackFile>> {
+    let mut rows = csv::parse(input).map_err(ImportError::Format)?;
+    let is_header = rows.first().map(|row| {
+        row.iter()
+            .map(|field| field.trim().to_lowercase())
+            .eq(["pack", "tags", "question"]
+                .iter()
+                .map(|field| field.to_string()))
+    });
+    if is_header == Some(true) {
+        rows.remove(0);
+    }
+
+    let mut packs: Vec<PackFile> = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        let (name, tags, question) = match row.as_slice() {
+            [name, tags, question] => (name.trim(), tags, question.trim()),
+            _ => {
+                return Err(ImportError::Format(format!(
+                    "Expected pack, tags and question on row {}",
+                    i + 1
+                )))
+            }
+        };
+        if name.is_empty() || question.is_empty() {
+            return Err(ImportError::Format(format!(
+                "Missing pack or question on row {}",
+                i + 1
+            )));
+        }
+
+        let index = match packs.iter().position(|pack| pack.name == name) {
+            Some(index) => index,
+            None => {
+                packs.push(PackFile {
+                    name: name.to_string(),
+                    tags: vec![],
+                    questions: vec![],
+                });
+                packs.len() - 1
+            }
+        };
+        let pack = &mut packs[index];
+        for tag in tags.split(';').map(str::trim).filter(|tag| !tag.is_empty()) {
+            if !pack.tags.iter().any(|other| other == tag) {
+                pack.tags.push(tag.to_string());
+            }
+        }
+        pack.questions.push(question.to_string());
+    }
+
+    Ok(packs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::import::{parse_csv, parse_json, PackFile};
+
+    #[test]
+    fn test_parse_json() {
+        let packs = parse_json("questions", r#"["a", "b"]"#).unwrap();
+        assert_eq!(
+            packs,
+            vec![PackFile {
+                name: "questions".to_string(),
+                tags: vec![],
+                questions: vec!["a".to_string(), "b".to_string()],
+            }]
+        );
+
+        let packs = parse_json(
+            "questions",
+            r#"[{"name": "Family", "tags": ["family friendly"], "questions": ["c"]}]"#,
+        )
+        .unwrap();
+        assert_eq!(packs[0].name, "Family");
+        assert_eq!(packs[0].tags, vec!["family friendly".to_string()]);
+
+        assert!(parse_json("questions", r#"{"name": "Empty"}"#).is_err());
+        assert!(parse_json("questions", r#"[1, 2]"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let packs = parse_csv(
+            "pack,tags,question\n\
+            Spicy,NSFW;adult,\"Worst thing to say, on a date\"\n\
+            Family,family friendly,Best pizza topping\n\
+            Spicy,NSFW,Another\n",
+        )
+        .unwrap();
+        assert_eq!(packs.len(), 2);
+        assert_eq!(packs[0].name, "Spicy");
+        assert_eq!(packs[0].tags, vec!["NSFW".to_string(), "adult".to_string()]);
+        assert_eq!(
+            packs[0].questions,
+            vec![
+                "Worst thing to say, on a date".to_string(),
+                "Another".to_string()
+            ]
+        );
+        assert_eq!(packs[1].questions, vec!["Best pizza topping".to_string()]);
+
+        assert!(parse_csv("Spicy,NSFW\n").is_err());
+    }
+}
@@ -8,15 +8,26 @@ mod controller;
 mod game;
 mod handler;
 mod http;
+mod import;
 mod persistence;
+mod poller;
 mod router;
+mod scheduler;
 mod threadpool;
 
-use crate::config::{Config, ConfigError};
+use crate::chat::discord::Discord;
+use crate::chat::telegram::Telegram;
+use crate::config::{ChatBackend, Config, ConfigError, Storage};
+use crate::controller::lock::ChatLocks;
+use crate::persistence::pack;
 use crate::threadpool::ThreadPool;
 use core::fmt;
 use std::fmt::Formatter;
 use std::net::TcpListener;
+use std::sync::Arc;
+
+/// The questions played when games are only kept in memory
+const LOCAL_QUESTIONS: &str = "questions.json";
 
 impl std::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -34,16 +45,82 @@ impl std::fmt::Display for ConfigError {
 fn main() {
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str);
+    if command == Some("import") || command == Some("migrate") {
+        let db_dsn = config::db_dsn().unwrap_or_else(|err| {
+            error!("Configuration error: {}", err);
+            process::exit(1);
+        });
+        let storage = Storage::from_dsn(db_dsn);
+        migrate(&storage);
+        if command == Some("import") {
+            if let Err(err) = import::run(&storage, &args[1..]) {
+                error!("Failed to import question packs: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     let config = Config::from_env().unwrap_or_else(|err| {
         error!("Configuration error: {}", err);
         process::exit(1);
     });
+    migrate(&config.storage);
+
+    if let Storage::Memory(store) = &config.storage {
+        let questions = [LOCAL_QUESTIONS.to_string()];
+        if let Err(err) = import::load(&pack::MemDao::new(store), &questions) {
+            error!("Failed to import {}: {}", LOCAL_QUESTIONS, err);
+            process::exit(1);
+        }
+    }
 
     let listener = TcpListener::bind(&config.bind_addr).unwrap_or_else(|err| {
         error!("Failed to start server: {}", err);
         process::exit(1);
     });
-    let pool = ThreadPool::new(4, config.clone());
+    if let ChatBackend::Discord {
+        hostname,
+        token,
+        application_id,
+        ..
+    } = &config.chat_backend
+    {
+        let result = Discord::new(hostname, token, application_id)
+            .and_then(|discord| discord.register_commands());
+        if let Err(err) = result {
+            error!("Failed to register discord commands: {:?}", err);
+        }
+    }
+    if let ChatBackend::Telegram {
+        hostname,
+        gamename,
+        token,
+        polling: false,
+        webhook_secret,
+    } = &config.chat_backend
+    {
+        let url = format!("{}/webhook", config.app_url);
+        let result = Telegram::new(hostname, token, gamename)
+            .and_then(|telegram| telegram.set_webhook(&url, webhook_secret));
+        if let Err(err) = result {
+            error!("Failed to register the telegram webhook: {:?}", err);
+        }
+    }
+
+    let locks = Arc::new(ChatLocks::default());
+    let pool = ThreadPool::new(4, config.clone(), Arc::clone(&locks));
+    let _scheduler = if config.timer_enabled {
+        Some(scheduler::spawn(config.clone(), Arc::clone(&locks)))
+    } else {
+        None
+    };
+    let _poller = match &config.chat_backend {
+        ChatBackend::Telegram { polling: true, .. } => Some(poller::spawn(config.clone(), locks)),
+        _ => None,
+    };
 
     info!("Server started: {}", config.bind_addr);
 
@@ -61,3 +138,17 @@ fn main() {
         }
     }
 }
+
+/// Brings the schema up to date before anything else connects, exiting if it can not
+fn migrate(storage: &Storage) {
+    let result = persistence::Connection::new(storage)
+        .and_then(|connection| connection.migrate().map_err(|err| err.to_string()));
+    match result {
+        Ok(applied) if !applied.is_empty() => info!("Applied migrations {:?}", applied),
+        Ok(_) => (),
+        Err(err) => {
+            error!("Failed to migrate the database: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -1,30 +1,36 @@
 use crate::game::{Answer, Question, User};
 
+use crate::game::timer::Phase;
 use crate::game::ChatGroup;
-use crate::persistence::postgres::Db;
+use crate::persistence::memory::{AnswerRow, GameState, Store};
+use crate::persistence::sql::Db;
 use crate::persistence::Result;
 
 pub trait Dao {
-    fn find_token(&self, user: &User, chat_group: &ChatGroup) -> Result<Option<String>>;
+    /// The game running in the chat group that the user has prompts in
+    fn find_game(&self, user: &User, chat_group: &ChatGroup) -> Result<Option<i64>>;
+    /// The game and chat group of the user's next prompt to answer in the current round, from the
+    /// game that began first when playing in more than one chat group. Prompts of games the user
+    /// left are forfeited
+    fn find_unanswered(&self, user: &User) -> Result<Option<(i64, ChatGroup)>>;
     fn find(&self, id: i64) -> Result<Vec<Answer>>;
     fn save_all(&self, game_id: i64, answers: &[Answer]) -> Result<()>;
 }
 
-pub struct PqDao<'s> {
+pub struct SqlDao<'s> {
     db: Db<'s>,
 }
 
-impl<'s> PqDao<'s> {
-    pub fn new(connection: &'s libpq::Connection) -> Self {
-        let db = Db::new(connection);
-        PqDao { db }
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> Self {
+        SqlDao { db }
     }
 }
 
-impl Dao for PqDao<'_> {
-    fn find_token(&self, user: &User, ChatGroup(chat_group): &ChatGroup) -> Result<Option<String>> {
+impl Dao for SqlDao<'_> {
+    fn find_game(&self, user: &User, ChatGroup(chat_group): &ChatGroup) -> Result<Option<i64>> {
         let res = self.db.exec_params(
-            "SELECT a.token \
+            "SELECT a.game_id \
             FROM answer a \
             INNER JOIN game g ON (g.id = a.game_id) \
             WHERE a.user_id = $1 \
@@ -36,9 +42,34 @@ impl Dao for PqDao<'_> {
         Ok(res.value(0, 0)?)
     }
 
+    fn find_unanswered(&self, user: &User) -> Result<Option<(i64, ChatGroup)>> {
+        let res = self.db.exec_params(
+            "SELECT a.game_id, g.chatgroup \
+            FROM answer a \
+            INNER JOIN game g ON (g.id = a.game_id) \
+            INNER JOIN game_user gu ON (gu.game_id = g.id AND gu.user_id = a.user_id) \
+            WHERE a.user_id = $1 \
+            AND a.response IS NULL \
+            AND g.state = 'gather_answers' \
+            AND a.round = g.round \
+            ORDER BY a.id \
+            LIMIT 1",
+            &[Box::new(Some(user.id))],
+        )?;
+
+        if res.ntuples() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some((
+            res.value_unchecked(0, 0)?,
+            ChatGroup(res.value_unchecked(0, 1)?),
+        )))
+    }
+
     fn find(&self, id: i64) -> Result<Vec<Answer>> {
         let res = self.db.exec_params(
-            "SELECT a.user_id, q.id, q.text, a.token, a.response \
+            "SELECT a.user_id, q.id, q.text, a.token, a.response, a.round \
             FROM answer a \
             INNER JOIN question q ON (a.question_id = q.id) \
             WHERE a.game_id = $1 \
@@ -58,6 +89,7 @@ impl Dao for PqDao<'_> {
                 },
                 token: res.value_unchecked(i, 3)?,
                 response: res.value(i, 4)?,
+                round: res.value_unchecked(i, 5)?,
             });
         }
 
@@ -70,17 +102,20 @@ impl Dao for PqDao<'_> {
             question,
             token,
             response,
+            round,
         } in answers
         {
             self.db.exec_params(
-                "INSERT INTO answer (user_id, question_id, game_id, response, token) \
-                VALUES ($1, $2, $3, $4, $5) ON CONFLICT (token) DO UPDATE SET response = $4",
+                "INSERT INTO answer (user_id, question_id, game_id, response, token, round) \
+                VALUES ($1, $2, $3, $4, $5, $6) \
+                ON CONFLICT (token) DO UPDATE SET response = $4, user_id = $1",
                 &[
                     Box::new(Some(user.id)),
                     Box::new(Some(question.id)),
                     Box::new(Some(game_id)),
                     Box::new(response.clone()),
                     Box::new(Some(token.clone())),
+                    Box::new(Some(*round)),
                 ],
             )?;
         }
@@ -88,3 +123,94 @@ impl Dao for PqDao<'_> {
         Ok(())
     }
 }
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> Self {
+        MemDao { store }
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn find_game(&self, user: &User, ChatGroup(chat_group): &ChatGroup) -> Result<Option<i64>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .answers
+            .iter()
+            .find(|answer| {
+                answer.user_id == user.id
+                    && tables.game(answer.game_id).is_some_and(|game| {
+                        game.chat_group == *chat_group && game.state != GameState::End
+                    })
+            })
+            .map(|answer| answer.game_id))
+    }
+
+    fn find_unanswered(&self, user: &User) -> Result<Option<(i64, ChatGroup)>> {
+        let tables = self.store.lock();
+        Ok(tables.answers.iter().find_map(|answer| {
+            let game = tables.game(answer.game_id)?;
+            let unanswered = answer.user_id == user.id
+                && tables.game_users.contains(&(game.id, user.id))
+                && answer.response.is_none()
+                && game.state == GameState::Running(Phase::Answering)
+                && game.round == answer.round;
+            if unanswered {
+                Some((game.id, ChatGroup(game.chat_group)))
+            } else {
+                None
+            }
+        }))
+    }
+
+    fn find(&self, id: i64) -> Result<Vec<Answer>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .answers
+            .iter()
+            .filter(|answer| answer.game_id == id)
+            .filter_map(|answer| {
+                Some(Answer {
+                    user: User { id: answer.user_id },
+                    question: tables.question(answer.question_id)?,
+                    token: answer.token.clone(),
+                    response: answer.response.clone(),
+                    round: answer.round,
+                })
+            })
+            .collect())
+    }
+
+    fn save_all(&self, game_id: i64, answers: &[Answer]) -> Result<()> {
+        let mut tables = self.store.lock();
+        for answer in answers {
+            match tables
+                .answers
+                .iter_mut()
+                .find(|other| other.token == answer.token)
+            {
+                Some(other) => {
+                    other.response = answer.response.clone();
+                    // Handed to another player when the one it was for left the game
+                    other.user_id = answer.user.id;
+                }
+                None => {
+                    let id = tables.next_id();
+                    tables.answers.push(AnswerRow {
+                        id,
+                        game_id,
+                        user_id: answer.user.id,
+                        question_id: answer.question.id,
+                        token: answer.token.clone(),
+                        response: answer.response.clone(),
+                        round: answer.round,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
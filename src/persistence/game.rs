@@ -1,84 +1,72 @@
 use crate::game;
-use crate::game::{Answer, ChatGroup, Question, State, Vote};
+use crate::game::timer::{Phase, Timer};
+use crate::game::{Answer, ChatGroup, State, Vote};
 use crate::game::{FullUser, User};
 use crate::persistence::answer::Dao as AnswerDao;
-use crate::persistence::postgres::Db;
+use crate::persistence::memory::{GameRow, GameState, Store};
+use crate::persistence::sql::{Db, ToSql};
 use crate::persistence::user::Dao as UserDao;
 use crate::persistence::vote::Dao as VoteDao;
 use crate::persistence::{answer, user, vote, Result};
 use log::{error, warn};
+use std::time::Instant;
 
-pub struct PqDao<'s> {
+pub struct SqlDao<'s> {
     db: Db<'s>,
-    vote_dao: vote::PqDao<'s>,
-    answer_dao: answer::PqDao<'s>,
-    user_dao: user::PqDao<'s>,
+    vote_dao: vote::SqlDao<'s>,
+    answer_dao: answer::SqlDao<'s>,
+    user_dao: user::SqlDao<'s>,
 }
 
-impl<'s> PqDao<'s> {
-    pub fn new(connection: &'s libpq::Connection) -> Self {
-        let db = Db::new(connection);
-        PqDao {
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> Self {
+        SqlDao {
             db,
-            vote_dao: vote::PqDao::new(connection),
-            answer_dao: answer::PqDao::new(connection),
-            user_dao: user::PqDao::new(connection),
+            vote_dao: vote::SqlDao::new(db),
+            answer_dao: answer::SqlDao::new(db),
+            user_dao: user::SqlDao::new(db),
         }
     }
 
-    fn gather_votes(&self, id: i64) -> Result<game::State> {
+    fn gather_votes(&self, id: i64, round: i64) -> Result<game::State> {
         let answers = self.answer_dao.find(id)?;
         let votes = self.vote_dao.find(id)?;
         let users = self.user_dao.find(id)?;
 
         let res = self.db.exec_params(
-            "SELECT a.user_id, q.id, q.text, a.token, a.response \
-            FROM answer a \
-            INNER JOIN game g ON (g.id = a.game_id) \
-            INNER JOIN question q ON (a.question_id = q.id AND q.id = g.current_question_id) \
-            WHERE g.id = $1",
+            "SELECT current_question_id FROM game WHERE id = $1",
             &[Box::new(Some(id))],
         )?;
+        let question_id: Option<i64> = res.value(0, 0)?;
+
+        let current = answers
+            .iter()
+            .filter(|answer| answer.round == round && Some(answer.question.id) == question_id)
+            .cloned()
+            .collect();
 
         let state = game::State::GatherVotes {
             id,
+            round,
             answers,
             users,
-            current: (
-                Answer {
-                    user: User {
-                        id: res.value_unchecked(0, 0)?,
-                    },
-                    question: Question {
-                        id: res.value_unchecked(0, 1)?,
-                        text: res.value_unchecked(0, 2)?,
-                    },
-                    token: res.value_unchecked(0, 3)?,
-                    response: res.value(0, 4)?,
-                },
-                Answer {
-                    user: User {
-                        id: res.value_unchecked(1, 0)?,
-                    },
-                    question: Question {
-                        id: res.value_unchecked(1, 1)?,
-                        text: res.value_unchecked(1, 2)?,
-                    },
-                    token: res.value_unchecked(1, 3)?,
-                    response: res.value(1, 4)?,
-                },
-            ),
+            current,
             votes,
         };
 
         Ok(state)
     }
 
-    fn gather_answers(&self, id: i64) -> Result<game::State> {
+    fn gather_answers(&self, id: i64, round: i64) -> Result<game::State> {
         let answers = self.answer_dao.find(id)?;
         let users = self.user_dao.find(id)?;
 
-        let state = game::State::GatherAnswers { id, answers, users };
+        let state = game::State::GatherAnswers {
+            id,
+            round,
+            answers,
+            users,
+        };
         Ok(state)
     }
 
@@ -110,11 +98,11 @@ impl<'s> PqDao<'s> {
         Ok(game::State::End { id, votes: vec![] })
     }
 
-    fn persist_new(&self, host_id: i64, chat_group: i64) -> Result<()> {
+    fn persist_new(&self, host_id: i64, chat_group: i64, timer: bool) -> Result<()> {
         let res = self.db.exec_params(
-            "INSERT INTO game (host_id, chatgroup, state, gathering_users_started, warning_state_state, warning_state_warning_value) \
-            VALUES ($1, $2, 'gather_users', now(), '', 0) RETURNING id",
-            &[Box::new(Some(host_id)), Box::new(Some(chat_group))]
+            &format!("INSERT INTO game (host_id, chatgroup, state, gathering_users_started, warning_state_state, warning_state_warning_value, has_timer) \
+            VALUES ($1, $2, 'gather_users', {}, '', 0, $3) RETURNING id", self.db.now()),
+            &[Box::new(Some(host_id)), Box::new(Some(chat_group)), Box::new(Some(timer))]
         )?;
 
         let game_id = res.value_unchecked::<i64>(0, 0)?;
@@ -132,7 +120,7 @@ impl<'s> PqDao<'s> {
             "UPDATE game SET state = 'gather_users' WHERE id = $1",
             &[Box::new(Some(id))],
         )?;
-        self.db.exec_params("UPDATE game SET gathering_users_started = now() WHERE id = $1 AND gathering_users_started IS NULL", &[Box::new(Some(id))])?;
+        self.db.exec_params(&format!("UPDATE game SET gathering_users_started = {} WHERE id = $1 AND gathering_users_started IS NULL", self.db.now()), &[Box::new(Some(id))])?;
 
         for user in users {
             self.db.exec_params(
@@ -143,17 +131,18 @@ impl<'s> PqDao<'s> {
         Ok(())
     }
 
-    fn persist_gather_answers(&self, id: i64, answers: &[Answer]) -> Result<()> {
+    fn persist_gather_answers(&self, id: i64, round: i64, answers: &[Answer]) -> Result<()> {
+        // Each round gets its own time to answer
         self.db.exec_params(
-            "UPDATE game SET state = 'gather_answers' WHERE id = $1",
-            &[Box::new(Some(id))],
+            &format!("UPDATE game \
+            SET gathering_answers_started = {}, warning_state_state = '', warning_state_warning_value = 0 \
+            WHERE id = $1 \
+            AND (gathering_answers_started IS NULL OR round IS DISTINCT FROM $2)", self.db.now()),
+            &[Box::new(Some(id)), Box::new(Some(round))],
         )?;
         self.db.exec_params(
-            "UPDATE game \
-            SET gathering_answers_started = now() \
-            WHERE id = $1 \
-            AND gathering_answers_started IS NULL",
-            &[Box::new(Some(id))],
+            "UPDATE game SET state = 'gather_answers', round = $2 WHERE id = $1",
+            &[Box::new(Some(id)), Box::new(Some(round))],
         )?;
 
         self.answer_dao.save_all(id, answers)?;
@@ -164,42 +153,94 @@ impl<'s> PqDao<'s> {
     fn persist_gather_votes(
         &self,
         id: i64,
+        round: i64,
         votes: &[Vote],
-        current: &(Answer, Answer),
+        current: &[Answer],
     ) -> Result<()> {
-        let (
-            Answer {
-                question: Question {
-                    id: question_id, ..
-                },
-                ..
-            },
-            _,
-        ) = current;
+        let question_id = match current.first() {
+            None => {
+                error!("No answers to vote on for game: {}", id);
+                return Ok(());
+            }
+            Some(answer) => &answer.question.id,
+        };
 
+        // Each question gets its own time to vote
         self.db.exec_params(
-            "UPDATE game SET state = 'gather_votes', current_question_id = $1 WHERE id = $2",
-            &[Box::new(Some(*question_id)), Box::new(Some(id))],
+            &format!("UPDATE game \
+            SET gathering_votes_started = {}, warning_state_state = '', warning_state_warning_value = 0 \
+            WHERE id = $1 \
+            AND current_question_id IS DISTINCT FROM $2", self.db.now()),
+            &[Box::new(Some(id)), Box::new(Some(*question_id))],
         )?;
         self.db.exec_params(
-            "UPDATE game \
-            SET gathering_votes_started = now() \
-            WHERE id = $1 \
-            AND gathering_votes_started IS NULL",
-            &[Box::new(Some(id))],
+            "UPDATE game SET state = 'gather_votes', current_question_id = $1, round = $3 WHERE id = $2",
+            &[
+                Box::new(Some(*question_id)),
+                Box::new(Some(id)),
+                Box::new(Some(round)),
+            ],
         )?;
 
-        for Vote { token, user } in votes {
-            self.db.exec_params("INSERT INTO vote (answer_id, question_id, user_id, game_id) \
-                SELECT a.id as answer_id, a.question_id AS question_id, $1 as user_id, a.game_id AS game_id \
-                FROM answer a \
-                WHERE token = $2 \
-                ON CONFLICT (user_id, answer_id) DO NOTHING", &[Box::new(Some(user.id)), Box::new(Some(token.clone()))])?;
+        for vote in votes {
+            self.vote_dao.save(vote)?;
         }
 
         Ok(())
     }
 
+    /// The timers of the running games with a time limit matching `filter`
+    fn timers(&self, filter: &str, params: &[Box<dyn ToSql>]) -> Result<Vec<Timer>> {
+        let res = self.db.exec_params(
+            &format!(
+                "SELECT id, chatgroup, state, {}, \
+                    CASE WHEN warning_state_state = state THEN warning_state_warning_value END \
+                FROM game \
+                WHERE has_timer \
+                AND state != 'end' {}",
+                self.db.seconds_since(
+                    "CASE state \
+                        WHEN 'gather_users' THEN gathering_users_started \
+                        WHEN 'gather_answers' THEN gathering_answers_started \
+                        ELSE gathering_votes_started \
+                    END"
+                ),
+                filter
+            ),
+            params,
+        )?;
+
+        let mut timers = vec![];
+        for i in 0..res.ntuples() {
+            let id = res.value_unchecked(i, 0)?;
+            let phase = match res.value_unchecked::<String>(i, 2)?.as_str() {
+                "gather_users" => Phase::Joining,
+                "gather_answers" => Phase::Answering,
+                "gather_votes" => Phase::Voting,
+                other => {
+                    error!("Invalid state for timer: {} {}", id, other);
+                    continue;
+                }
+            };
+            let elapsed = match res.value(i, 3)? {
+                None => {
+                    warn!("Missing start time for timer: {}", id);
+                    continue;
+                }
+                Some(elapsed) => elapsed,
+            };
+            timers.push(Timer {
+                id,
+                chat_group: ChatGroup(res.value_unchecked(i, 1)?),
+                phase,
+                elapsed,
+                warning: res.value(i, 4)?,
+            });
+        }
+
+        Ok(timers)
+    }
+
     fn persist_end(&self, id: i64) -> Result<()> {
         self.db.exec_params(
             "UPDATE game SET state = 'end' WHERE id = $1",
@@ -210,14 +251,29 @@ impl<'s> PqDao<'s> {
 }
 
 pub trait Dao {
+    /// The game being played in the chat group, locked until the transaction it is read in ends
     fn find_running(&self, chat_group: &ChatGroup) -> Result<Option<game::State>>;
+    fn find_host(&self, id: i64) -> Result<Option<User>>;
+    fn find_timers(&self) -> Result<Vec<Timer>>;
+    /// The timer of the game, unless it has ended or has no time limit
+    fn find_timer(&self, id: i64) -> Result<Option<Timer>>;
     fn save(&self, game: &game::State) -> Result<()>;
+    fn save_warning(&self, id: i64, warning: i64) -> Result<()>;
+    /// The message showing the progress of the game, if one has been sent
+    fn find_board(&self, id: i64) -> Result<Option<i64>>;
+    fn save_board(&self, id: i64, message_id: i64) -> Result<()>;
+    /// The chat group of the game being voted on that the user plays in, from the game that began
+    /// first when playing in more than one chat group
+    fn find_voting(&self, user: &User) -> Result<Option<ChatGroup>>;
 }
 
-impl Dao for PqDao<'_> {
+impl Dao for SqlDao<'_> {
     fn find_running(&self, ChatGroup(chat_group): &ChatGroup) -> Result<Option<game::State>> {
         let res = self.db.exec_params(
-            "SELECT id, state FROM game WHERE chatgroup = $1 AND state != 'end' LIMIT 1",
+            &format!(
+                "SELECT id, state, round FROM game WHERE chatgroup = $1 AND state != 'end' LIMIT 1{}",
+                self.db.for_update()
+            ),
             &[Box::new(Some(*chat_group))],
         )?;
 
@@ -228,11 +284,12 @@ impl Dao for PqDao<'_> {
 
         let id = res.value_unchecked::<i64>(0, 0)?;
         let state = res.value_unchecked::<String>(0, 1)?;
+        let round = res.value_unchecked::<i64>(0, 2)?;
 
         let state = match state.as_str() {
             "gather_users" => self.gather_users(id)?,
-            "gather_answers" => self.gather_answers(id)?,
-            "gather_votes" => self.gather_votes(id)?,
+            "gather_answers" => self.gather_answers(id, round)?,
+            "gather_votes" => self.gather_votes(id, round)?,
             "end" => self.end(id)?,
             other => {
                 error!("Invalid state: {}", other);
@@ -243,30 +300,317 @@ impl Dao for PqDao<'_> {
         Ok(Some(state))
     }
 
+    fn find_host(&self, id: i64) -> Result<Option<User>> {
+        let res = self.db.exec_params(
+            "SELECT host_id FROM game WHERE id = $1",
+            &[Box::new(Some(id))],
+        )?;
+
+        if res.ntuples() == 0 {
+            return Ok(None);
+        }
+
+        Ok(res.value(0, 0)?.map(|id| User { id }))
+    }
+
+    fn find_timers(&self) -> Result<Vec<Timer>> {
+        self.timers("", &[])
+    }
+
+    fn find_timer(&self, id: i64) -> Result<Option<Timer>> {
+        Ok(self.timers("AND id = $1", &[Box::new(Some(id))])?.pop())
+    }
+
     fn save(&self, game: &game::State) -> Result<()> {
-        match game {
+        self.db.transaction(|| match game {
             State::New {
                 host: User { id: host_id },
                 chat_group,
+                timer,
+            } => self.persist_new(*host_id, *chat_group, *timer),
+            State::GatherUsers { id, users, .. } => self.persist_gather_users(*id, users),
+            State::GatherAnswers {
+                id, round, answers, ..
+            } => self.persist_gather_answers(*id, *round, answers),
+            State::GatherVotes {
+                id,
+                round,
+                current,
+                votes,
                 ..
+            } => self.persist_gather_votes(*id, *round, votes, current),
+            State::End { id, .. } => self.persist_end(*id),
+        })
+    }
+
+    fn save_warning(&self, id: i64, warning: i64) -> Result<()> {
+        self.db.exec_params(
+            "UPDATE game SET warning_state_state = state, warning_state_warning_value = $2 WHERE id = $1",
+            &[Box::new(Some(id)), Box::new(Some(warning))],
+        )?;
+        Ok(())
+    }
+
+    fn find_board(&self, id: i64) -> Result<Option<i64>> {
+        let res = self.db.exec_params(
+            "SELECT board_message_id FROM game WHERE id = $1",
+            &[Box::new(Some(id))],
+        )?;
+        Ok(res.value(0, 0)?)
+    }
+
+    fn save_board(&self, id: i64, message_id: i64) -> Result<()> {
+        self.db.exec_params(
+            "UPDATE game SET board_message_id = $2 WHERE id = $1",
+            &[Box::new(Some(id)), Box::new(Some(message_id))],
+        )?;
+        Ok(())
+    }
+
+    fn find_voting(&self, user: &User) -> Result<Option<ChatGroup>> {
+        let res = self.db.exec_params(
+            "SELECT g.chatgroup \
+            FROM game g \
+            INNER JOIN game_user gu ON (gu.game_id = g.id) \
+            WHERE gu.user_id = $1 \
+            AND g.state = 'gather_votes' \
+            ORDER BY g.id \
+            LIMIT 1",
+            &[Box::new(Some(user.id))],
+        )?;
+        Ok(res.value(0, 0)?.map(ChatGroup))
+    }
+}
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+    vote_dao: vote::MemDao<'s>,
+    answer_dao: answer::MemDao<'s>,
+    user_dao: user::MemDao<'s>,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> Self {
+        MemDao {
+            store,
+            vote_dao: vote::MemDao::new(store),
+            answer_dao: answer::MemDao::new(store),
+            user_dao: user::MemDao::new(store),
+        }
+    }
+
+    /// Moves the game to the phase, restarting its time when `restart` holds or the phase changed
+    fn update(&self, id: i64, phase: Phase, restart: impl FnOnce(&GameRow) -> bool) {
+        let mut tables = self.store.lock();
+        if let Some(game) = tables.game_mut(id) {
+            if game.state != GameState::Running(phase) || restart(game) {
+                game.started = Instant::now();
+                game.warning = None;
+            }
+            game.state = GameState::Running(phase);
+        }
+    }
+}
+
+/// The timer of the game while it runs with a time limit
+fn timer(game: &GameRow) -> Option<Timer> {
+    match game.state {
+        GameState::Running(phase) if game.has_timer => Some(Timer {
+            id: game.id,
+            chat_group: ChatGroup(game.chat_group),
+            phase,
+            elapsed: game.started.elapsed().as_secs() as i64,
+            warning: game.warning,
+        }),
+        _ => None,
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn find_running(&self, ChatGroup(chat_group): &ChatGroup) -> Result<Option<game::State>> {
+        let running = self
+            .store
+            .lock()
+            .games
+            .iter()
+            .find(|game| game.chat_group == *chat_group && game.state != GameState::End)
+            .map(|game| (game.id, game.state, game.round, game.current_question_id));
+
+        let (id, state, round, question_id) = match running {
+            None => {
+                warn!("No game found for group: {:?}", chat_group);
+                return Ok(None);
+            }
+            Some(running) => running,
+        };
+
+        let state = match state {
+            GameState::Running(Phase::Joining) => State::GatherUsers {
+                id,
+                users: self.user_dao.find(id)?,
+            },
+            GameState::Running(Phase::Answering) => State::GatherAnswers {
+                id,
+                round,
+                answers: self.answer_dao.find(id)?,
+                users: self.user_dao.find(id)?,
+            },
+            GameState::Running(Phase::Voting) => {
+                let answers = self.answer_dao.find(id)?;
+                let current = answers
+                    .iter()
+                    .filter(|answer| {
+                        answer.round == round && Some(answer.question.id) == question_id
+                    })
+                    .cloned()
+                    .collect();
+                State::GatherVotes {
+                    id,
+                    round,
+                    answers,
+                    current,
+                    votes: self.vote_dao.find(id)?,
+                    users: self.user_dao.find(id)?,
+                }
+            }
+            GameState::End => State::End { id, votes: vec![] },
+        };
+
+        Ok(Some(state))
+    }
+
+    fn find_host(&self, id: i64) -> Result<Option<User>> {
+        Ok(self
+            .store
+            .lock()
+            .game(id)
+            .map(|game| User { id: game.host_id }))
+    }
+
+    fn find_timers(&self) -> Result<Vec<Timer>> {
+        Ok(self.store.lock().games.iter().filter_map(timer).collect())
+    }
+
+    fn find_timer(&self, id: i64) -> Result<Option<Timer>> {
+        Ok(self
+            .store
+            .lock()
+            .games
+            .iter()
+            .find(|game| game.id == id)
+            .and_then(timer))
+    }
+
+    fn save(&self, game: &game::State) -> Result<()> {
+        match game {
+            State::New {
+                host: User { id: host_id },
+                chat_group,
+                timer,
             } => {
-                self.persist_new(*host_id, *chat_group)?;
+                let mut tables = self.store.lock();
+                let id = tables.next_id();
+                tables.games.push(GameRow {
+                    id,
+                    host_id: *host_id,
+                    chat_group: *chat_group,
+                    state: GameState::Running(Phase::Joining),
+                    round: 1,
+                    current_question_id: None,
+                    has_timer: *timer,
+                    started: Instant::now(),
+                    warning: None,
+                    board_message_id: None,
+                });
+                tables.game_users.push((id, *host_id));
             }
-            State::GatherUsers { id, users, .. } => {
-                self.persist_gather_users(*id, users)?;
+            State::GatherUsers { id, users } => {
+                self.update(*id, Phase::Joining, |_| false);
+                let mut tables = self.store.lock();
+                for user in users {
+                    if !tables.game_users.contains(&(*id, user.id)) {
+                        tables.game_users.push((*id, user.id));
+                    }
+                }
             }
-            State::GatherAnswers { id, answers, .. } => {
-                self.persist_gather_answers(*id, answers)?;
+            State::GatherAnswers {
+                id, round, answers, ..
+            } => {
+                // Each round gets its own time to answer
+                self.update(*id, Phase::Answering, |game| game.round != *round);
+                if let Some(game) = self.store.lock().game_mut(*id) {
+                    game.round = *round;
+                }
+                self.answer_dao.save_all(*id, answers)?;
             }
             State::GatherVotes {
-                id, current, votes, ..
+                id,
+                round,
+                current,
+                votes,
+                ..
             } => {
-                self.persist_gather_votes(*id, votes, current)?;
+                let question_id = match current.first() {
+                    None => {
+                        error!("No answers to vote on for game: {}", id);
+                        return Ok(());
+                    }
+                    Some(answer) => answer.question.id,
+                };
+
+                // Each question gets its own time to vote
+                self.update(*id, Phase::Voting, |game| {
+                    game.current_question_id != Some(question_id)
+                });
+                if let Some(game) = self.store.lock().game_mut(*id) {
+                    game.current_question_id = Some(question_id);
+                    game.round = *round;
+                }
+
+                for vote in votes {
+                    self.vote_dao.save(vote)?;
+                }
             }
             State::End { id, .. } => {
-                self.persist_end(*id)?;
+                if let Some(game) = self.store.lock().game_mut(*id) {
+                    game.state = GameState::End;
+                }
             }
         };
         Ok(())
     }
+
+    fn save_warning(&self, id: i64, warning: i64) -> Result<()> {
+        if let Some(game) = self.store.lock().game_mut(id) {
+            game.warning = Some(warning);
+        }
+        Ok(())
+    }
+
+    fn find_board(&self, id: i64) -> Result<Option<i64>> {
+        Ok(self
+            .store
+            .lock()
+            .game(id)
+            .and_then(|game| game.board_message_id))
+    }
+
+    fn save_board(&self, id: i64, message_id: i64) -> Result<()> {
+        if let Some(game) = self.store.lock().game_mut(id) {
+            game.board_message_id = Some(message_id);
+        }
+        Ok(())
+    }
+
+    fn find_voting(&self, user: &User) -> Result<Option<ChatGroup>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .games
+            .iter()
+            .find(|game| {
+                game.state == GameState::Running(Phase::Voting)
+                    && tables.game_users.contains(&(game.id, user.id))
+            })
+            .map(|game| ChatGroup(game.chat_group)))
+    }
 }
@@ -0,0 +1,290 @@
+//! Keeps everything in memory instead of the database, for tests and playing locally. Nothing
+//! survives a restart
+
+use crate::game::settings::Settings;
+use crate::game::suggestion::{Suggestion, SUGGESTIONS_PACK};
+use crate::game::timer::Phase;
+use crate::game::{FullUser, Question};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+/// The tables shared by every DAO made from the store, safe to share between threads
+pub struct Store {
+    tables: Mutex<Tables>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        let mut tables = Tables::default();
+        for name in &["classic", SUGGESTIONS_PACK] {
+            let id = tables.next_id();
+            tables.packs.push(PackRow {
+                id,
+                name: name.to_string(),
+                enabled_by_default: true,
+                tags: vec![],
+            });
+        }
+
+        Store {
+            tables: Mutex::new(tables),
+        }
+    }
+}
+
+impl Store {
+    /// Locks the tables, which must not be held while calling into another DAO
+    pub fn lock(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+    Running(Phase),
+    End,
+}
+
+pub struct GameRow {
+    pub id: i64,
+    pub host_id: i64,
+    pub chat_group: i64,
+    pub state: GameState,
+    pub round: i64,
+    pub current_question_id: Option<i64>,
+    pub has_timer: bool,
+    /// When the current phase began, reset for each round and question
+    pub started: Instant,
+    /// The last warning sent for the current phase
+    pub warning: Option<i64>,
+    pub board_message_id: Option<i64>,
+}
+
+pub struct AnswerRow {
+    pub id: i64,
+    pub game_id: i64,
+    pub user_id: i64,
+    pub question_id: i64,
+    pub token: String,
+    pub response: Option<String>,
+    pub round: i64,
+}
+
+pub struct VoteRow {
+    pub game_id: i64,
+    pub answer_id: i64,
+    pub user_id: i64,
+    pub audience: bool,
+}
+
+pub struct QuestionRow {
+    pub id: i64,
+    pub text: String,
+    pub pack_id: i64,
+    /// Set for questions only played in the chat group that suggested them
+    pub chat_group: Option<i64>,
+}
+
+pub struct PackRow {
+    pub id: i64,
+    pub name: String,
+    pub enabled_by_default: bool,
+    pub tags: Vec<String>,
+}
+
+pub struct SuggestionRow {
+    pub id: i64,
+    pub chat_group: i64,
+    pub user_id: i64,
+    pub text: String,
+    pub pending: bool,
+}
+
+#[derive(Default)]
+pub struct Tables {
+    pub games: Vec<GameRow>,
+    /// Pairs of game and user ids
+    pub game_users: Vec<(i64, i64)>,
+    pub users: Vec<FullUser>,
+    /// Ids of the users who started the bot in a private chat
+    pub private_chats: Vec<i64>,
+    pub answers: Vec<AnswerRow>,
+    pub votes: Vec<VoteRow>,
+    pub questions: Vec<QuestionRow>,
+    pub packs: Vec<PackRow>,
+    /// Chat group, pack id and whether the chat group plays the pack
+    pub chatgroup_packs: Vec<(i64, i64, bool)>,
+    pub settings: Vec<(i64, Settings)>,
+    pub suggestions: Vec<SuggestionRow>,
+    pub telegram_offset: Option<i64>,
+    last_id: i64,
+}
+
+impl Tables {
+    /// Ids are unique across every table, so rows sort in the order they were added
+    pub fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    pub fn game(&self, id: i64) -> Option<&GameRow> {
+        self.games.iter().find(|game| game.id == id)
+    }
+
+    pub fn game_mut(&mut self, id: i64) -> Option<&mut GameRow> {
+        self.games.iter_mut().find(|game| game.id == id)
+    }
+
+    pub fn user(&self, id: i64) -> Option<&FullUser> {
+        self.users.iter().find(|user| user.id == id)
+    }
+
+    pub fn question(&self, id: i64) -> Option<Question> {
+        self.questions
+            .iter()
+            .find(|question| question.id == id)
+            .map(|question| Question {
+                id: question.id,
+                text: question.text.clone(),
+            })
+    }
+
+    pub fn is_pack_enabled(&self, chat_group: i64, pack: &PackRow) -> bool {
+        self.chatgroup_packs
+            .iter()
+            .find(|(other, pack_id, _)| *other == chat_group && *pack_id == pack.id)
+            .map(|(_, _, enabled)| *enabled)
+            .unwrap_or(pack.enabled_by_default)
+    }
+
+    /// Whether the question can be drawn for the chat group
+    pub fn is_question_enabled(&self, chat_group: i64, question: &QuestionRow) -> bool {
+        question.chat_group.is_none_or(|other| other == chat_group)
+            && self
+                .packs
+                .iter()
+                .any(|pack| pack.id == question.pack_id && self.is_pack_enabled(chat_group, pack))
+    }
+
+    pub fn suggestion(&self, row: &SuggestionRow) -> Option<Suggestion> {
+        self.user(row.user_id).map(|user| Suggestion {
+            id: row.id,
+            user: user.clone(),
+            text: row.text.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::timer::Phase;
+    use crate::game::{ChatGroup, FullUser, State, User};
+    use crate::persistence::game::Dao as GameDao;
+    use crate::persistence::memory::Store;
+    use crate::persistence::pack::Dao as PackDao;
+    use crate::persistence::question::Dao as QuestionDao;
+    use crate::persistence::suggestion::Dao as SuggestionDao;
+    use crate::persistence::user::Dao as UserDao;
+    use crate::persistence::{game, pack, question, suggestion, user};
+
+    #[test]
+    fn test_find_random() {
+        let store = Store::default();
+        let pack_dao = pack::MemDao::new(&store);
+        let question_dao = question::MemDao::new(&store);
+        pack_dao
+            .import("classic", &[], &["a".to_string(), "b".to_string()])
+            .unwrap();
+        pack_dao
+            .import("spicy", &["NSFW".to_string()], &["c".to_string()])
+            .unwrap();
+
+        let questions = question_dao.find_random(&ChatGroup(1), 5).unwrap();
+        assert_eq!(questions.len(), 2, "NSFW packs should be off by default");
+
+        let spicy = pack_dao.find_all(&ChatGroup(1)).unwrap();
+        let spicy = spicy.iter().find(|pack| pack.name == "spicy").unwrap();
+        pack_dao
+            .save_enabled(&ChatGroup(1), spicy.id, true)
+            .unwrap();
+        assert_eq!(question_dao.find_random(&ChatGroup(1), 5).unwrap().len(), 3);
+        assert_eq!(question_dao.find_random(&ChatGroup(2), 5).unwrap().len(), 2);
+
+        let user = FullUser {
+            id: 1,
+            is_bot: false,
+            first_name: None,
+            last_name: None,
+            username: None,
+        };
+        user::MemDao::new(&store).save(&user).unwrap();
+        let suggestion_dao = suggestion::MemDao::new(&store);
+        let suggestion = suggestion_dao.save(&ChatGroup(2), &user, "d").unwrap();
+        assert!(suggestion_dao
+            .approve(&ChatGroup(1), suggestion.id)
+            .unwrap()
+            .is_none());
+        assert!(suggestion_dao
+            .approve(&ChatGroup(2), suggestion.id)
+            .unwrap()
+            .is_some());
+        assert_eq!(question_dao.find_random(&ChatGroup(2), 5).unwrap().len(), 3);
+        assert_eq!(
+            question_dao.find_random(&ChatGroup(3), 5).unwrap().len(),
+            2,
+            "Suggestions should only be played by the chat group that made them"
+        );
+    }
+
+    #[test]
+    fn test_game() {
+        let store = Store::default();
+        let game_dao = game::MemDao::new(&store);
+        game_dao
+            .save(&State::New {
+                host: User { id: 1 },
+                chat_group: 1,
+                timer: true,
+            })
+            .unwrap();
+
+        let id = match game_dao.find_running(&ChatGroup(1)).unwrap() {
+            Some(State::GatherUsers { id, users }) => {
+                assert!(users.is_empty(), "The host has not been saved as a user");
+                id
+            }
+            _ => panic!("Expected a new game to be gathering users"),
+        };
+        assert_eq!(game_dao.find_host(id).unwrap().map(|user| user.id), Some(1));
+        assert!(game_dao.find_running(&ChatGroup(2)).unwrap().is_none());
+
+        game_dao.save_warning(id, 60).unwrap();
+        let timers = game_dao.find_timers().unwrap();
+        assert_eq!(timers.len(), 1);
+        assert_eq!(timers[0].phase, Phase::Joining);
+        assert_eq!(timers[0].warning, Some(60));
+
+        assert_eq!(game_dao.find_board(id).unwrap(), None);
+        game_dao.save_board(id, 7).unwrap();
+        assert_eq!(game_dao.find_board(id).unwrap(), Some(7));
+
+        game_dao
+            .save(&State::GatherAnswers {
+                id,
+                round: 1,
+                answers: vec![],
+                users: vec![],
+            })
+            .unwrap();
+        let timers = game_dao.find_timers().unwrap();
+        assert_eq!(timers[0].phase, Phase::Answering);
+        assert_eq!(timers[0].warning, None, "Warnings should reset each phase");
+        let timer = game_dao.find_timer(id).unwrap();
+        assert_eq!(timer.map(|timer| timer.phase), Some(Phase::Answering));
+
+        game_dao.save(&State::End { id, votes: vec![] }).unwrap();
+        assert!(game_dao.find_running(&ChatGroup(1)).unwrap().is_none());
+        assert!(game_dao.find_timers().unwrap().is_empty());
+        assert!(game_dao.find_timer(id).unwrap().is_none());
+    }
+}
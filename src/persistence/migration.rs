@@ -0,0 +1,255 @@
+//! Brings the schema up to date with the numbered migrations built into the binary, each version
+//! applied being recorded in `schema_migration`
+
+use crate::persistence::sql::{Db, Result, ToSql};
+use log::info;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Applied in order of version, which must count up from 1 without gaps
+const POSTGRES: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../../migrations/postgres/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "rounds",
+        sql: include_str!("../../migrations/postgres/0002_rounds.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "chatgroup_settings",
+        sql: include_str!("../../migrations/postgres/0003_chatgroup_settings.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "packs",
+        sql: include_str!("../../migrations/postgres/0004_packs.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "suggestions",
+        sql: include_str!("../../migrations/postgres/0005_suggestions.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "audience",
+        sql: include_str!("../../migrations/postgres/0006_audience.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "telegram_offset",
+        sql: include_str!("../../migrations/postgres/0007_telegram_offset.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "board_message",
+        sql: include_str!("../../migrations/postgres/0008_board_message.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "private_chat",
+        sql: include_str!("../../migrations/postgres/0009_private_chat.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "kick_and_late_join",
+        sql: include_str!("../../migrations/postgres/0010_kick_and_late_join.sql"),
+    },
+];
+
+const SQLITE: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("../../migrations/sqlite/0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "telegram_offset",
+        sql: include_str!("../../migrations/sqlite/0002_telegram_offset.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "board_message",
+        sql: include_str!("../../migrations/sqlite/0003_board_message.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "private_chat",
+        sql: include_str!("../../migrations/sqlite/0004_private_chat.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "kick_and_late_join",
+        sql: include_str!("../../migrations/sqlite/0005_kick_and_late_join.sql"),
+    },
+];
+
+/// Shared by every instance of the app so only one of them migrates at a time
+const LOCK_KEY: i64 = 0x7175_6970_6c61_7368;
+
+fn migrations(db: Db) -> &'static [Migration] {
+    match db {
+        Db::Postgres(_) => POSTGRES,
+        Db::Sqlite(_) => SQLITE,
+    }
+}
+
+/// Applies every pending migration in one transaction, returning the versions applied. Instances
+/// starting together wait for the first one to finish, then find nothing left to apply
+pub fn run(db: Db) -> Result<Vec<i64>> {
+    db.transaction(|| apply(db))
+}
+
+fn apply(db: Db) -> Result<Vec<i64>> {
+    // SQLite is already locked for the transaction
+    if let Db::Postgres(_) = db {
+        db.exec_params(
+            "SELECT pg_advisory_xact_lock($1)",
+            &[Box::new(Some(LOCK_KEY))],
+        )?;
+    }
+    db.exec_batch(match db {
+        Db::Postgres(_) => {
+            "CREATE TABLE IF NOT EXISTS schema_migration
+            (
+                version BIGINT                   PRIMARY KEY,
+                name    TEXT                     NOT NULL,
+                applied TIMESTAMP WITH TIME ZONE NOT NULL
+            )"
+        }
+        Db::Sqlite(_) => {
+            "CREATE TABLE IF NOT EXISTS schema_migration
+            (
+                version INTEGER PRIMARY KEY,
+                name    TEXT    NOT NULL,
+                applied INTEGER NOT NULL
+            )"
+        }
+    })?;
+
+    let res = db.exec_params("SELECT MAX(version) FROM schema_migration", &[])?;
+    let mut current: i64 = res.value(0, 0)?.unwrap_or(0);
+    let migrations = migrations(db);
+    if current == 0 && has_tables(db)? {
+        // Created from the schema file before migrations were kept
+        info!("Recording the existing schema as migration 1");
+        record(db, &migrations[0])?;
+        current = 1;
+    }
+
+    let mut applied = vec![];
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        info!(
+            "Applying migration {} {}",
+            migration.version, migration.name
+        );
+        db.exec_batch(migration.sql)?;
+        record(db, migration)?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+fn has_tables(db: Db) -> Result<bool> {
+    let sql = match db {
+        Db::Postgres(_) => "SELECT to_regclass('game') IS NOT NULL",
+        Db::Sqlite(_) => {
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'game')"
+        }
+    };
+    db.exec_params(sql, &[])?.value_unchecked(0, 0)
+}
+
+fn record(db: Db, migration: &Migration) -> Result<()> {
+    let sql = format!(
+        "INSERT INTO schema_migration (version, name, applied) VALUES ($1, $2, {})",
+        db.now()
+    );
+    let params: [Box<dyn ToSql>; 2] = [
+        Box::new(Some(migration.version)),
+        Box::new(Some(migration.name.to_string())),
+    ];
+    db.exec_params(&sql, &params)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::persistence::migration::{run, POSTGRES, SQLITE};
+    use crate::persistence::sql::Db;
+    use crate::persistence::sqlite::open;
+    use std::fs;
+    use std::thread;
+
+    #[test]
+    fn test_versions() {
+        for migrations in &[POSTGRES, SQLITE] {
+            for (i, migration) in migrations.iter().enumerate() {
+                assert_eq!(migration.version, i as i64 + 1, "{}", migration.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let path = std::env::temp_dir().join(format!("quiplash-{}.db", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap().to_string();
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                thread::spawn(move || run(Db::Sqlite(&open(&path).unwrap())).unwrap())
+            })
+            .collect();
+        let applied: Vec<Vec<i64>> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(
+            applied
+                .iter()
+                .filter(|versions| !versions.is_empty())
+                .count(),
+            1,
+            "Only one worker should apply the migrations"
+        );
+        assert!(applied.contains(&vec![1, 2, 3, 4, 5]));
+
+        let connection = open(&path).unwrap();
+        assert!(run(Db::Sqlite(&connection)).unwrap().is_empty());
+        let versions: i64 = connection
+            .query_row("SELECT COUNT(*) FROM schema_migration", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(versions, 5);
+
+        drop(connection);
+        for suffix in &["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    #[test]
+    fn test_existing_schema() {
+        let connection = open(":memory:").unwrap();
+        connection.execute_batch(SQLITE[0].sql).unwrap();
+
+        assert_eq!(
+            run(Db::Sqlite(&connection)).unwrap(),
+            vec![2, 3, 4, 5],
+            "The schema should be recorded without being created again"
+        );
+        let version: i64 = connection
+            .query_row("SELECT MAX(version) FROM schema_migration", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(version, 5);
+    }
+}
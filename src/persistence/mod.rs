@@ -1,14 +1,25 @@
-use crate::persistence::postgres::PostgresError;
-use crate::persistence::DaoError::Postgres;
+use crate::config::Storage;
+use crate::persistence::memory::Store;
+use crate::persistence::sql::{Db, SqlError};
+use std::fmt;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum DaoError {
-    Postgres(PostgresError),
+    Sql(SqlError),
 }
 
-impl From<PostgresError> for DaoError {
-    fn from(err: PostgresError) -> Self {
-        Postgres(err)
+impl From<SqlError> for DaoError {
+    fn from(err: SqlError) -> Self {
+        DaoError::Sql(err)
+    }
+}
+
+impl fmt::Display for DaoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaoError::Sql(err) => write!(f, "{}", err),
+        }
     }
 }
 
@@ -16,10 +27,107 @@ pub type Result<T> = std::result::Result<T, DaoError>;
 
 pub mod answer;
 pub mod game;
+pub mod memory;
+pub(crate) mod migration;
+pub mod offset;
+pub mod pack;
 pub(crate) mod postgres;
 pub mod question;
+pub mod score;
+pub mod settings;
+pub(crate) mod sql;
+pub(crate) mod sqlite;
+pub mod suggestion;
+pub mod transaction;
 pub mod user;
 pub mod vote;
 
+/// An open connection to where games are kept
+pub enum Connection {
+    Postgres(libpq::Connection),
+    Sqlite(rusqlite::Connection),
+    Memory(Arc<Store>),
+}
+
+impl Connection {
+    pub fn new(storage: &Storage) -> std::result::Result<Connection, String> {
+        match storage {
+            Storage::Postgres { db_dsn } => libpq::Connection::new(db_dsn)
+                .map(Connection::Postgres)
+                .map_err(|err| err.to_string()),
+            Storage::Sqlite { path } => sqlite::open(path)
+                .map(Connection::Sqlite)
+                .map_err(|err| err.to_string()),
+            Storage::Memory(store) => Ok(Connection::Memory(Arc::clone(store))),
+        }
+    }
+
+    /// Applies the migrations the schema is missing, returning the versions applied
+    pub fn migrate(&self) -> std::result::Result<Vec<i64>, SqlError> {
+        match self {
+            Connection::Postgres(connection) => migration::run(Db::Postgres(connection)),
+            Connection::Sqlite(connection) => migration::run(Db::Sqlite(connection)),
+            Connection::Memory(_) => Ok(vec![]),
+        }
+    }
+}
+
+/// Every DAO the controller uses, all kept in the same place
+pub struct Daos<'s> {
+    pub game: Box<dyn game::Dao + 's>,
+    pub question: Box<dyn question::Dao + 's>,
+    pub answer: Box<dyn answer::Dao + 's>,
+    pub user: Box<dyn user::Dao + 's>,
+    pub vote: Box<dyn vote::Dao + 's>,
+    pub score: Box<dyn score::Dao + 's>,
+    pub settings: Box<dyn settings::Dao + 's>,
+    pub pack: Box<dyn pack::Dao + 's>,
+    pub suggestion: Box<dyn suggestion::Dao + 's>,
+    pub transaction: Box<dyn transaction::Dao + 's>,
+    pub offset: Box<dyn offset::Dao + 's>,
+}
+
+impl<'s> Daos<'s> {
+    pub fn new(connection: &'s Connection) -> Self {
+        match connection {
+            Connection::Postgres(connection) => Daos::sql(Db::Postgres(connection)),
+            Connection::Sqlite(connection) => Daos::sql(Db::Sqlite(connection)),
+            Connection::Memory(store) => Daos::memory(store),
+        }
+    }
+
+    pub fn sql(db: Db<'s>) -> Self {
+        Daos {
+            game: Box::new(game::SqlDao::new(db)),
+            question: Box::new(question::SqlDao::new(db)),
+            answer: Box::new(answer::SqlDao::new(db)),
+            user: Box::new(user::SqlDao::new(db)),
+            vote: Box::new(vote::SqlDao::new(db)),
+            score: Box::new(score::SqlDao::new(db)),
+            settings: Box::new(settings::SqlDao::new(db)),
+            pack: Box::new(pack::SqlDao::new(db)),
+            suggestion: Box::new(suggestion::SqlDao::new(db)),
+            transaction: Box::new(transaction::SqlDao::new(db)),
+            offset: Box::new(offset::SqlDao::new(db)),
+        }
+    }
+
+    pub fn memory(store: &'s Store) -> Self {
+        Daos {
+            game: Box::new(game::MemDao::new(store)),
+            question: Box::new(question::MemDao::new(store)),
+            answer: Box::new(answer::MemDao::new(store)),
+            user: Box::new(user::MemDao::new(store)),
+            vote: Box::new(vote::MemDao::new(store)),
+            score: Box::new(score::MemDao::new(store)),
+            settings: Box::new(settings::MemDao::new(store)),
+            pack: Box::new(pack::MemDao::new(store)),
+            suggestion: Box::new(suggestion::MemDao::new(store)),
+            transaction: Box::new(transaction::MemDao),
+            offset: Box::new(offset::MemDao::new(store)),
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod test;
@@ -0,0 +1,59 @@
+use crate::persistence::memory::Store;
+use crate::persistence::sql::Db;
+use crate::persistence::Result;
+
+pub struct SqlDao<'s> {
+    db: Db<'s>,
+}
+
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> SqlDao<'s> {
+        SqlDao { db }
+    }
+}
+
+/// Where polling telegram for updates left off, so updates are not handled again after a restart
+pub trait Dao {
+    /// The id of the next update to fetch, none before the first update has been handled
+    fn find(&self) -> Result<Option<i64>>;
+    fn save(&self, update_id: i64) -> Result<()>;
+}
+
+impl Dao for SqlDao<'_> {
+    fn find(&self) -> Result<Option<i64>> {
+        let res = self
+            .db
+            .exec_params("SELECT update_id FROM telegram_offset WHERE id = 1", &[])?;
+        Ok(res.value(0, 0)?)
+    }
+
+    fn save(&self, update_id: i64) -> Result<()> {
+        self.db.exec_params(
+            "INSERT INTO telegram_offset (id, update_id) VALUES (1, $1) \
+            ON CONFLICT (id) DO UPDATE SET update_id = $1",
+            &[Box::new(Some(update_id))],
+        )?;
+        Ok(())
+    }
+}
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> MemDao<'s> {
+        MemDao { store }
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn find(&self) -> Result<Option<i64>> {
+        Ok(self.store.lock().telegram_offset)
+    }
+
+    fn save(&self, update_id: i64) -> Result<()> {
+        self.store.lock().telegram_offset = Some(update_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::game::settings::{Role, Settings};
+use crate::game::ChatGroup;
+use crate::persistence::memory::Store;
+use crate::persistence::sql::Db;
+use crate::persistence::Result;
+use log::error;
+
+pub struct SqlDao<'s> {
+    db: Db<'s>,
+}
+
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> SqlDao<'s> {
+        SqlDao { db }
+    }
+}
+
+pub trait Dao {
+    /// Settings for the chat group, falling back to the defaults when none have been saved
+    fn find(&self, chat_group: &ChatGroup) -> Result<Settings>;
+    fn save(&self, chat_group: &ChatGroup, settings: &Settings) -> Result<()>;
+}
+
+fn role(value: Option<String>, default: Role) -> Role {
+    match value {
+        None => default,
+        Some(value) => Role::parse(&value).unwrap_or_else(|| {
+            error!("Invalid role in settings: {}", value);
+            default
+        }),
+    }
+}
+
+impl Dao for SqlDao<'_> {
+    fn find(&self, ChatGroup(chat_group): &ChatGroup) -> Result<Settings> {
+        let res = self.db.exec_params(
+            "SELECT begin_role, end_role, audience, kick_role, late_join \
+            FROM chatgroup_settings WHERE chatgroup = $1",
+            &[Box::new(Some(*chat_group))],
+        )?;
+
+        let default = Settings::default();
+        if res.ntuples() == 0 {
+            return Ok(default);
+        }
+
+        Ok(Settings {
+            begin: role(res.value(0, 0)?, default.begin),
+            end: role(res.value(0, 1)?, default.end),
+            kick: role(res.value(0, 3)?, default.kick),
+            audience: res.value_unchecked(0, 2)?,
+            late_join: res.value_unchecked(0, 4)?,
+        })
+    }
+
+    fn save(&self, ChatGroup(chat_group): &ChatGroup, settings: &Settings) -> Result<()> {
+        self.db.exec_params(
+            "INSERT INTO chatgroup_settings \
+            (chatgroup, begin_role, end_role, audience, kick_role, late_join) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            ON CONFLICT (chatgroup) DO UPDATE \
+            SET begin_role = $2, end_role = $3, audience = $4, kick_role = $5, late_join = $6",
+            &[
+                Box::new(Some(*chat_group)),
+                Box::new(Some(settings.begin.as_str().to_string())),
+                Box::new(Some(settings.end.as_str().to_string())),
+                Box::new(Some(settings.audience)),
+                Box::new(Some(settings.kick.as_str().to_string())),
+                Box::new(Some(settings.late_join)),
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> MemDao<'s> {
+        MemDao { store }
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn find(&self, ChatGroup(chat_group): &ChatGroup) -> Result<Settings> {
+        let tables = self.store.lock();
+        Ok(tables
+            .settings
+            .iter()
+            .find(|(other, _)| other == chat_group)
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default())
+    }
+
+    fn save(&self, ChatGroup(chat_group): &ChatGroup, settings: &Settings) -> Result<()> {
+        let mut tables = self.store.lock();
+        tables.settings.retain(|(other, _)| other != chat_group);
+        tables.settings.push((*chat_group, settings.clone()));
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use crate::persistence::sql::SqlError::{MissingValue, Parse};
+use crate::persistence::transaction::Transaction;
+use crate::persistence::{postgres, sqlite};
+use log::error;
+use std::fmt;
+use std::string::FromUtf8Error;
+
+pub type Result<T> = std::result::Result<T, SqlError>;
+
+#[derive(Debug)]
+pub enum SqlError {
+    MissingValue,
+    Parse,
+    Utf8(FromUtf8Error),
+    Status(libpq::Status),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for SqlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingValue => write!(f, "Missing value"),
+            Parse => write!(f, "Could not parse value"),
+            SqlError::Utf8(err) => write!(f, "Invalid UTF-8: {}", err),
+            SqlError::Status(status) => write!(f, "Postgres error: {:?}", status),
+            SqlError::Sqlite(err) => write!(f, "SQLite error: {}", err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SqlError {
+    fn from(err: rusqlite::Error) -> Self {
+        error!("sqlite error: {}", err);
+        SqlError::Sqlite(err)
+    }
+}
+
+/// A connection to either database, the queries being written for Postgres and run on SQLite
+/// with `$1` style parameters
+#[derive(Clone, Copy)]
+pub enum Db<'s> {
+    Postgres(&'s libpq::Connection),
+    Sqlite(&'s rusqlite::Connection),
+}
+
+impl<'s> Db<'s> {
+    pub fn exec_params(&self, sql: &str, params: &[Box<dyn ToSql>]) -> Result<Res> {
+        let params: Vec<SqlValue> = params.iter().map(|param| param.to_sql()).collect();
+        match self {
+            Db::Postgres(connection) => postgres::exec_params(connection, sql, &params),
+            Db::Sqlite(connection) => sqlite::exec_params(connection, sql, &params),
+        }
+    }
+
+    /// Runs one or more statements separated by semicolons, none of them taking parameters
+    pub fn exec_batch(&self, sql: &str) -> Result<()> {
+        match self {
+            Db::Postgres(connection) => postgres::exec(connection, sql),
+            Db::Sqlite(connection) => Ok(connection.execute_batch(sql)?),
+        }
+    }
+
+    /// Begins a transaction, or a savepoint when one has already begun so that transactions nest.
+    /// On SQLite the database is locked for writing straight away, standing in for `FOR UPDATE`
+    pub fn begin(&self) -> Result<Transaction> {
+        let nested = match self {
+            Db::Postgres(connection) => {
+                connection.transaction_status() != libpq::transaction::Status::Idle
+            }
+            Db::Sqlite(connection) => !connection.is_autocommit(),
+        };
+        self.exec_batch(match (self, nested) {
+            (_, true) => "SAVEPOINT nested",
+            (Db::Postgres(_), false) => "BEGIN",
+            (Db::Sqlite(_), false) => "BEGIN IMMEDIATE",
+        })?;
+        Ok(Transaction { nested })
+    }
+
+    pub fn commit(&self, transaction: Transaction) -> Result<()> {
+        self.exec_batch(match transaction.nested {
+            true => "RELEASE SAVEPOINT nested",
+            false => "COMMIT",
+        })
+    }
+
+    pub fn rollback(&self, transaction: Transaction) -> Result<()> {
+        self.exec_batch(match transaction.nested {
+            true => "ROLLBACK TO SAVEPOINT nested; RELEASE SAVEPOINT nested",
+            false => "ROLLBACK",
+        })
+    }
+
+    /// Runs `f` in a transaction, committed if it succeeds and rolled back otherwise
+    pub fn transaction<T, E: From<SqlError>>(
+        &self,
+        f: impl FnOnce() -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let transaction = self.begin()?;
+        match f() {
+            Ok(value) => {
+                self.commit(transaction)?;
+                Ok(value)
+            }
+            Err(err) => {
+                self.rollback(transaction)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Locks the rows selected until the transaction ends, SQLite having locked the whole
+    /// database when the transaction began
+    pub fn for_update(&self) -> &'static str {
+        match self {
+            Db::Postgres(_) => " FOR UPDATE",
+            Db::Sqlite(_) => "",
+        }
+    }
+
+    /// The current time as stored in timestamp columns
+    pub fn now(&self) -> &'static str {
+        match self {
+            Db::Postgres(_) => "now()",
+            Db::Sqlite(_) => "unixepoch()",
+        }
+    }
+
+    /// The whole seconds elapsed since the timestamp
+    pub fn seconds_since(&self, timestamp: &str) -> String {
+        match self {
+            Db::Postgres(_) => format!("EXTRACT(EPOCH FROM now() - ({}))::BIGINT", timestamp),
+            Db::Sqlite(_) => format!("unixepoch() - ({})", timestamp),
+        }
+    }
+}
+
+/// A query parameter, null values keeping their type
+#[derive(Debug, Clone)]
+pub enum SqlValue {
+    Text(Option<String>),
+    Bool(Option<bool>),
+    Int(Option<i64>),
+}
+
+pub trait ToSql {
+    fn to_sql(&self) -> SqlValue;
+}
+
+impl ToSql for Option<String> {
+    fn to_sql(&self) -> SqlValue {
+        SqlValue::Text(self.clone())
+    }
+}
+
+impl ToSql for Option<bool> {
+    fn to_sql(&self) -> SqlValue {
+        SqlValue::Bool(*self)
+    }
+}
+
+impl ToSql for Option<i64> {
+    fn to_sql(&self) -> SqlValue {
+        SqlValue::Int(*self)
+    }
+}
+
+/// The rows returned by a query, each value read as text
+pub struct Res(pub(crate) Vec<Vec<Option<String>>>);
+
+impl Res {
+    pub fn value_unchecked<T: FromSql>(&self, row: usize, column: usize) -> Result<T> {
+        match self.value(row, column)? {
+            None => {
+                error!("value missing: (row {}, col {})", row, column);
+                Err(MissingValue)
+            }
+            Some(value) => Ok(value),
+        }
+    }
+
+    pub fn value<T: FromSql>(&self, row: usize, column: usize) -> Result<Option<T>> {
+        let Res(rows) = self;
+        let value = match rows.get(row).and_then(|values| values.get(column)) {
+            None | Some(None) => return Ok(None),
+            Some(Some(value)) => value,
+        };
+
+        match T::from_sql(value) {
+            Err(_err) => {
+                error!("parse error: (row {}, col {})", row, column);
+                Err(Parse)
+            }
+            Ok(value) => Ok(Some(value)),
+        }
+    }
+
+    pub fn ntuples(&self) -> usize {
+        let Res(rows) = self;
+        rows.len()
+    }
+}
+
+pub trait FromSql: Sized {
+    fn from_sql(str: &str) -> Result<Self>;
+}
+
+impl FromSql for bool {
+    /// Postgres returns `t` and `f` where SQLite stores booleans as integers
+    fn from_sql(str: &str) -> Result<bool> {
+        match str {
+            "f" | "0" => Ok(false),
+            "t" | "1" => Ok(true),
+            other => {
+                error!("Parse error from sql: {}", other);
+                Err(Parse)
+            }
+        }
+    }
+}
+
+impl FromSql for String {
+    fn from_sql(str: &str) -> Result<String> {
+        Ok(str.to_string())
+    }
+}
+
+impl FromSql for i64 {
+    fn from_sql(str: &str) -> Result<i64> {
+        str.parse().map_err(|err| {
+            error!("Parse error from sql: {:?}", err);
+            Parse
+        })
+    }
+}
+
+impl FromSql for usize {
+    fn from_sql(str: &str) -> Result<usize> {
+        str.parse().map_err(|err| {
+            error!("Parse error from sql: {:?}", err);
+            Parse
+        })
+    }
+}
@@ -0,0 +1,172 @@
+use crate::persistence::sql::{Res, Result, SqlError, SqlValue};
+use rusqlite::types::{Value, ValueRef};
+use std::time::Duration;
+
+/// Prefix of a `DB_DSN` for SQLite, followed by the path of the database file
+pub const SCHEME: &str = "sqlite://";
+
+/// How long to wait for another worker writing to the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens the database file, creating it if needed. The schema is left to `migration::run`
+pub fn open(path: &str) -> Result<rusqlite::Connection> {
+    let connection = rusqlite::Connection::open(path)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+    Ok(connection)
+}
+
+/// Turns the `$1` style parameters of postgres into `?1`, as SQLite numbers `$1` by where it first
+/// appears while `?1` keeps the number. Quoted strings and identifiers are left as they are
+fn placeholders(sql: &str) -> String {
+    let mut converted = String::with_capacity(sql.len());
+    let mut quote = None;
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (_, Some(open)) if c == open => quote = None,
+            ('$', None) if chars.peek().is_some_and(char::is_ascii_digit) => {
+                converted.push('?');
+                continue;
+            }
+            _ => {}
+        }
+        converted.push(c);
+    }
+    converted
+}
+
+pub fn exec_params(
+    connection: &rusqlite::Connection,
+    sql: &str,
+    params: &[SqlValue],
+) -> Result<Res> {
+    let mut statement = connection.prepare(&placeholders(sql))?;
+    let columns = statement.column_count();
+    let mut rows = statement.query(rusqlite::params_from_iter(params.iter().map(value)))?;
+
+    let mut res = vec![];
+    while let Some(row) = rows.next()? {
+        let mut values = vec![];
+        for column in 0..columns {
+            values.push(match row.get_ref(column)? {
+                ValueRef::Null => None,
+                ValueRef::Integer(value) => Some(value.to_string()),
+                ValueRef::Real(value) => Some(value.to_string()),
+                ValueRef::Text(value) | ValueRef::Blob(value) => {
+                    Some(String::from_utf8(value.to_vec()).map_err(SqlError::Utf8)?)
+                }
+            });
+        }
+        res.push(values);
+    }
+    Ok(Res(res))
+}
+
+fn value(param: &SqlValue) -> Value {
+    match param {
+        SqlValue::Text(value) => value.clone().map_or(Value::Null, Value::Text),
+        SqlValue::Bool(value) => value.map_or(Value::Null, |value| Value::Integer(value as i64)),
+        SqlValue::Int(value) => value.map_or(Value::Null, Value::Integer),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::timer::Phase;
+    use crate::game::{ChatGroup, FullUser, State, User};
+    use crate::persistence::sql::Db;
+    use crate::persistence::sqlite::{open, placeholders};
+    use crate::persistence::{migration, Daos};
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders("SELECT $1, $12 FROM t WHERE a = $2"),
+            "SELECT ?1, ?12 FROM t WHERE a = ?2"
+        );
+        assert_eq!(
+            placeholders("SELECT '$1', 'it''s $2', \"$3\" FROM t WHERE a = $4 AND b = '$'"),
+            "SELECT '$1', 'it''s $2', \"$3\" FROM t WHERE a = ?4 AND b = '$'"
+        );
+        assert_eq!(placeholders("SELECT $a, $"), "SELECT $a, $");
+    }
+
+    #[test]
+    fn test_daos() {
+        let connection = open(":memory:").unwrap();
+        migration::run(Db::Sqlite(&connection)).unwrap();
+        let daos = Daos::sql(Db::Sqlite(&connection));
+
+        daos.pack
+            .import(
+                "spicy",
+                &["NSFW".to_string(), "adult".to_string()],
+                &["a".to_string()],
+            )
+            .unwrap();
+        let packs = daos.pack.find_all(&ChatGroup(1)).unwrap();
+        let spicy = packs.iter().find(|pack| pack.name == "spicy").unwrap();
+        assert_eq!(spicy.tags, vec!["NSFW".to_string(), "adult".to_string()]);
+        assert!(!spicy.enabled);
+        assert_eq!(spicy.questions, 1);
+
+        let user = FullUser {
+            id: 1,
+            is_bot: false,
+            first_name: Some("first".to_string()),
+            last_name: None,
+            username: None,
+        };
+        daos.user.save(&user).unwrap();
+        let suggestion = daos.suggestion.save(&ChatGroup(1), &user, "b").unwrap();
+        assert!(daos
+            .suggestion
+            .approve(&ChatGroup(2), suggestion.id)
+            .unwrap()
+            .is_none());
+        let approved = daos
+            .suggestion
+            .approve(&ChatGroup(1), suggestion.id)
+            .unwrap();
+        assert_eq!(
+            approved.map(|suggestion| suggestion.text),
+            Some("b".to_string())
+        );
+        assert!(daos
+            .suggestion
+            .reject(&ChatGroup(1), suggestion.id)
+            .unwrap()
+            .is_none());
+
+        daos.game
+            .save(&State::New {
+                host: User { id: 1 },
+                chat_group: 1,
+                timer: true,
+            })
+            .unwrap();
+        let timers = daos.game.find_timers().unwrap();
+        assert_eq!(timers.len(), 1);
+        assert_eq!(timers[0].phase, Phase::Joining);
+        assert!(timers[0].elapsed < 5);
+        daos.game.save_warning(timers[0].id, 60).unwrap();
+        assert_eq!(daos.game.find_timers().unwrap()[0].warning, Some(60));
+        let timer = daos.game.find_timer(timers[0].id).unwrap();
+        assert_eq!(timer.map(|timer| timer.warning), Some(Some(60)));
+        assert!(daos.game.find_timer(timers[0].id + 1).unwrap().is_none());
+
+        assert_eq!(daos.offset.find().unwrap(), None);
+        daos.offset.save(5).unwrap();
+        daos.offset.save(6).unwrap();
+        assert_eq!(daos.offset.find().unwrap(), Some(6));
+
+        assert!(daos.score.find_top(None, 10, &[]).unwrap().is_empty());
+        assert!(daos
+            .score
+            .find_top(Some(&ChatGroup(1)), 10, &[])
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::persistence::sql::Db;
+use crate::persistence::{DaoError, Result};
+
+/// Returned by `Dao::begin` and given back to end the transaction, remembering whether it was
+/// nested in another one
+pub struct Transaction {
+    pub(crate) nested: bool,
+}
+
+/// Groups calls to the other DAOs so they are saved together or not at all
+pub trait Dao {
+    fn begin(&self) -> Result<Transaction>;
+    fn commit(&self, transaction: Transaction) -> Result<()>;
+    fn rollback(&self, transaction: Transaction) -> Result<()>;
+}
+
+/// Runs `f` in a transaction, committed if it succeeds and rolled back otherwise
+pub fn run<T, E: From<DaoError>>(
+    dao: &dyn Dao,
+    f: impl FnOnce() -> std::result::Result<T, E>,
+) -> std::result::Result<T, E> {
+    let transaction = dao.begin()?;
+    match f() {
+        Ok(value) => {
+            dao.commit(transaction)?;
+            Ok(value)
+        }
+        Err(err) => {
+            dao.rollback(transaction)?;
+            Err(err)
+        }
+    }
+}
+
+pub struct SqlDao<'s> {
+    db: Db<'s>,
+}
+
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> SqlDao<'s> {
+        SqlDao { db }
+    }
+}
+
+impl Dao for SqlDao<'_> {
+    fn begin(&self) -> Result<Transaction> {
+        Ok(self.db.begin()?)
+    }
+
+    fn commit(&self, transaction: Transaction) -> Result<()> {
+        Ok(self.db.commit(transaction)?)
+    }
+
+    fn rollback(&self, transaction: Transaction) -> Result<()> {
+        Ok(self.db.rollback(transaction)?)
+    }
+}
+
+/// Each call to the memory DAOs applies on its own, nothing being rolled back
+pub struct MemDao;
+
+impl Dao for MemDao {
+    fn begin(&self) -> Result<Transaction> {
+        Ok(Transaction { nested: false })
+    }
+
+    fn commit(&self, _transaction: Transaction) -> Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&self, _transaction: Transaction) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::FullUser;
+    use crate::persistence::sql::Db;
+    use crate::persistence::sql::SqlError;
+    use crate::persistence::transaction::{run, SqlDao};
+    use crate::persistence::user::Dao as UserDao;
+    use crate::persistence::{migration, sqlite, user, DaoError};
+
+    fn user(id: i64) -> FullUser {
+        FullUser {
+            id,
+            is_bot: false,
+            first_name: None,
+            last_name: None,
+            username: None,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let connection = sqlite::open(":memory:").unwrap();
+        let db = Db::Sqlite(&connection);
+        migration::run(db).unwrap();
+        let dao = SqlDao::new(db);
+        let user_dao = user::SqlDao::new(db);
+        let count = || -> i64 {
+            connection
+                .query_row("SELECT COUNT(*) FROM \"user\"", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let result: Result<(), DaoError> = run(&dao, || {
+            user_dao.save(&user(1))?;
+            Err(DaoError::Sql(SqlError::MissingValue))
+        });
+        assert!(result.is_err());
+        assert_eq!(count(), 0, "The failed transaction should be rolled back");
+
+        run(&dao, || {
+            user_dao.save(&user(2))?;
+            let nested: Result<(), DaoError> = run(&dao, || {
+                user_dao.save(&user(3))?;
+                Err(DaoError::Sql(SqlError::MissingValue))
+            });
+            assert!(nested.is_err());
+            Ok::<_, DaoError>(())
+        })
+        .unwrap();
+        assert_eq!(
+            count(),
+            1,
+            "Only the nested transaction should be rolled back"
+        );
+        assert!(connection.is_autocommit());
+    }
+}
@@ -1,25 +1,32 @@
-use crate::game::FullUser;
-use crate::persistence::postgres::Db;
+use crate::game::{FullUser, User};
+use crate::persistence::memory::Store;
+use crate::persistence::sql::Db;
 use crate::persistence::Result;
 
-pub struct PqDao<'s> {
+pub struct SqlDao<'s> {
     db: Db<'s>,
 }
 
-impl<'s> PqDao<'s> {
-    pub fn new(connection: &'s libpq::Connection) -> PqDao<'s> {
-        PqDao {
-            db: Db::new(connection),
-        }
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> SqlDao<'s> {
+        SqlDao { db }
     }
 }
 
 pub trait Dao {
     fn save(&self, user: &FullUser) -> Result<()>;
     fn find(&self, id: i64) -> Result<Vec<FullUser>>;
+    /// Whether the user can be sent their prompts in a private chat with the bot
+    fn save_private(&self, user: &User, private: bool) -> Result<()>;
+    /// The players of the game who can be sent their prompts privately
+    fn find_private(&self, id: i64) -> Result<Vec<User>>;
+    /// Adds the user to the players of a game that has already begun
+    fn join(&self, id: i64, user: &User) -> Result<()>;
+    /// Takes the user out of the players of the game, their answers staying with it
+    fn leave(&self, id: i64, user: &User) -> Result<()>;
 }
 
-impl Dao for PqDao<'_> {
+impl Dao for SqlDao<'_> {
     fn save(&self, user: &FullUser) -> Result<()> {
         self.db.exec_params(
             "INSERT INTO \"user\" (id, is_bot, first_name, last_name, username) \
@@ -59,4 +66,119 @@ impl Dao for PqDao<'_> {
 
         Ok(users)
     }
+
+    fn save_private(&self, user: &User, private: bool) -> Result<()> {
+        self.db.exec_params(
+            "UPDATE \"user\" SET private_chat = $2 WHERE id = $1",
+            &[Box::new(Some(user.id)), Box::new(Some(private))],
+        )?;
+        Ok(())
+    }
+
+    fn find_private(&self, id: i64) -> Result<Vec<User>> {
+        let res = self.db.exec_params(
+            "SELECT u.id \
+            FROM \"user\" u \
+            INNER JOIN game_user gu ON (gu.user_id = u.id) \
+            WHERE gu.game_id = $1 \
+            AND u.private_chat",
+            &[Box::new(Some(id))],
+        )?;
+
+        let mut users = vec![];
+        for i in 0..res.ntuples() {
+            users.push(User {
+                id: res.value_unchecked(i, 0)?,
+            });
+        }
+
+        Ok(users)
+    }
+
+    fn join(&self, id: i64, user: &User) -> Result<()> {
+        self.db.exec_params(
+            "INSERT INTO game_user (game_id, user_id) VALUES ($1, $2) \
+            ON CONFLICT (game_id, user_id) DO NOTHING",
+            &[Box::new(Some(id)), Box::new(Some(user.id))],
+        )?;
+        Ok(())
+    }
+
+    fn leave(&self, id: i64, user: &User) -> Result<()> {
+        self.db.exec_params(
+            "DELETE FROM game_user WHERE game_id = $1 AND user_id = $2",
+            &[Box::new(Some(id)), Box::new(Some(user.id))],
+        )?;
+        Ok(())
+    }
+}
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> MemDao<'s> {
+        MemDao { store }
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn save(&self, user: &FullUser) -> Result<()> {
+        let mut tables = self.store.lock();
+        match tables.users.iter_mut().find(|other| other.id == user.id) {
+            Some(other) => {
+                other.first_name = user.first_name.clone();
+                other.last_name = user.last_name.clone();
+                other.username = user.username.clone();
+            }
+            None => tables.users.push(user.clone()),
+        }
+        Ok(())
+    }
+
+    fn find(&self, id: i64) -> Result<Vec<FullUser>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .game_users
+            .iter()
+            .filter(|(game_id, _)| *game_id == id)
+            .filter_map(|(_, user_id)| tables.user(*user_id).cloned())
+            .collect())
+    }
+
+    fn save_private(&self, user: &User, private: bool) -> Result<()> {
+        let mut tables = self.store.lock();
+        tables.private_chats.retain(|id| *id != user.id);
+        if private {
+            tables.private_chats.push(user.id);
+        }
+        Ok(())
+    }
+
+    fn find_private(&self, id: i64) -> Result<Vec<User>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .game_users
+            .iter()
+            .filter(|(game_id, user_id)| *game_id == id && tables.private_chats.contains(user_id))
+            .map(|(_, user_id)| User { id: *user_id })
+            .collect())
+    }
+
+    fn join(&self, id: i64, user: &User) -> Result<()> {
+        let mut tables = self.store.lock();
+        if !tables.game_users.contains(&(id, user.id)) {
+            tables.game_users.push((id, user.id));
+        }
+        Ok(())
+    }
+
+    fn leave(&self, id: i64, user: &User) -> Result<()> {
+        self.store
+            .lock()
+            .game_users
+            .retain(|game_user| *game_user != (id, user.id));
+        Ok(())
+    }
 }
@@ -1,27 +1,28 @@
 use crate::game::{User, Vote};
-use crate::persistence::postgres::Db;
+use crate::persistence::memory::{Store, VoteRow};
+use crate::persistence::sql::Db;
 use crate::persistence::Result;
 
-pub struct PqDao<'s> {
+pub struct SqlDao<'s> {
     db: Db<'s>,
 }
 
-impl<'s> PqDao<'s> {
-    pub fn new(connection: &'s libpq::Connection) -> PqDao<'s> {
-        PqDao {
-            db: Db::new(connection),
-        }
+impl<'s> SqlDao<'s> {
+    pub fn new(db: Db<'s>) -> SqlDao<'s> {
+        SqlDao { db }
     }
 }
 
 pub trait Dao {
     fn find(&self, id: i64) -> Result<Vec<Vote>>;
+    /// Saves the vote for the answer with its token, doing nothing if it was already saved
+    fn save(&self, vote: &Vote) -> Result<()>;
 }
 
-impl Dao for PqDao<'_> {
+impl Dao for SqlDao<'_> {
     fn find(&self, id: i64) -> Result<Vec<Vote>> {
         let res = self.db.exec_params(
-            "SELECT a.token, v.user_id \
+            "SELECT a.token, v.user_id, v.audience \
         FROM vote v \
         INNER JOIN answer a ON (v.answer_id = a.id) \
         WHERE a.game_id = $1",
@@ -35,9 +36,78 @@ impl Dao for PqDao<'_> {
                 user: User {
                     id: res.value_unchecked(i, 1)?,
                 },
+                audience: res.value_unchecked(i, 2)?,
             });
         }
 
         Ok(votes)
     }
+
+    fn save(&self, vote: &Vote) -> Result<()> {
+        self.db.exec_params(
+            "INSERT INTO vote (answer_id, question_id, user_id, game_id, audience) \
+            SELECT a.id as answer_id, a.question_id AS question_id, $1 as user_id, a.game_id AS game_id, $3 \
+            FROM answer a \
+            WHERE token = $2 \
+            ON CONFLICT (user_id, answer_id) DO NOTHING",
+            &[
+                Box::new(Some(vote.user.id)),
+                Box::new(Some(vote.token.clone())),
+                Box::new(Some(vote.audience)),
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+pub struct MemDao<'s> {
+    store: &'s Store,
+}
+
+impl<'s> MemDao<'s> {
+    pub fn new(store: &'s Store) -> MemDao<'s> {
+        MemDao { store }
+    }
+}
+
+impl Dao for MemDao<'_> {
+    fn find(&self, id: i64) -> Result<Vec<Vote>> {
+        let tables = self.store.lock();
+        Ok(tables
+            .votes
+            .iter()
+            .filter(|vote| vote.game_id == id)
+            .filter_map(|vote| {
+                let answer = tables.answers.iter().find(|a| a.id == vote.answer_id)?;
+                Some(Vote {
+                    token: answer.token.clone(),
+                    user: User { id: vote.user_id },
+                    audience: vote.audience,
+                })
+            })
+            .collect())
+    }
+    fn save(&self, vote: &Vote) -> Result<()> {
+        let mut tables = self.store.lock();
+        let answer = tables
+            .answers
+            .iter()
+            .find(|answer| answer.token == vote.token)
+            .map(|answer| (answer.id, answer.game_id));
+        if let Some((answer_id, game_id)) = answer {
+            let exists = tables
+                .votes
+                .iter()
+                .any(|other| other.answer_id == answer_id && other.user_id == vote.user.id);
+            if !exists {
+                tables.votes.push(VoteRow {
+                    game_id,
+                    answer_id,
+                    user_id: vote.user.id,
+                    audience: vote.audience,
+                });
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,93 @@
+use log::{error, info};
+
+use crate::chat;
+use crate::chat::telegram::Telegram;
+use crate::config::{ChatBackend, Config};
+use crate::controller::link::Links;
+use crate::controller::lock::ChatLocks;
+use crate::controller::Controller;
+use crate::persistence::{self, Daos};
+use crate::router::Router;
+use serde_json::Value;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long telegram holds each request open waiting for updates
+const POLL_TIMEOUT: u64 = 30;
+/// How long to wait before polling again after telegram could not be reached
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Starts a thread that polls telegram for updates and handles them like the webhook does,
+/// taking turns with the workers through `locks`
+pub fn spawn(config: Config, locks: Arc<ChatLocks>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let (hostname, token, gamename) = match &config.chat_backend {
+            ChatBackend::Telegram {
+                hostname,
+                token,
+                gamename,
+                ..
+            } => (hostname, token, gamename),
+            ChatBackend::Discord { .. } => panic!("Only telegram can be polled for updates"),
+        };
+
+        let connection = persistence::Connection::new(&config.storage)
+            .map_err(|err| {
+                error!("Database connection error: {}", err);
+                err
+            })
+            .expect("Failed to start poller due to db connection error");
+
+        let telegram = Telegram::new(hostname, token, gamename).expect("Failed to create client");
+        let chat_client = chat::new_client(&config.chat_backend).expect("Failed to create client");
+        let offset_dao = Daos::new(&connection).offset;
+        let controller = Controller::new(
+            Daos::new(&connection),
+            chat_client,
+            locks,
+            Links::new(&config.app_url, &config.app_secret),
+            config.timer_enabled,
+            &config.rounds,
+        );
+        let router = Router::default();
+
+        if let Err(err) = telegram.delete_webhook() {
+            error!("Failed to delete webhook: {:?}", err);
+        }
+        let mut offset = offset_dao.find().unwrap_or_else(|err| {
+            error!("Failed to find the update offset: {:?}", err);
+            None
+        });
+
+        info!("Poller started from update {:?}", offset);
+
+        loop {
+            let updates = match telegram.get_updates(offset, POLL_TIMEOUT) {
+                Ok(updates) => updates,
+                Err(err) => {
+                    error!("Failed to get updates: {:?}", err);
+                    thread::sleep(RETRY_DELAY);
+                    continue;
+                }
+            };
+
+            for update in updates {
+                let update_id = match update.get("update_id").and_then(Value::as_i64) {
+                    None => {
+                        error!("Update without an id: {}", update);
+                        continue;
+                    }
+                    Some(update_id) => update_id,
+                };
+
+                router.handle_update(&controller, update);
+
+                offset = Some(update_id + 1);
+                if let Err(err) = offset_dao.save(update_id + 1) {
+                    error!("Failed to save the update offset: {:?}", err);
+                }
+            }
+        }
+    })
+}
@@ -1,121 +1,201 @@
+use crate::chat::discord::interaction;
+use crate::chat::discord::interaction::Interaction;
 use crate::chat::telegram::update::Update;
-use crate::controller::ClientErrorReason::InvalidQueryParams;
+use crate::chat::Event;
+use crate::controller::ClientErrorReason::{
+    InvalidQueryParams, MethodNotAllowed, NotFound, Unauthorized,
+};
 use crate::controller::Result;
 use crate::controller::{Controller, ControllerError};
-use crate::game::ChatGroup;
+use crate::game::{Callback, ChatGroup, Choice, FullUser};
 use http::Uri;
 use log::{error, info};
 use regex::Regex;
+use ring::constant_time;
 use serde_json::{json, Value};
 
 pub struct Router {
     command_pattern: Regex,
+    /// Verifies that interactions were sent by discord, which is not served without it
+    discord_public_key: Option<Vec<u8>>,
+    /// Required of updates sent to the webhook, either in the path or the header telegram sends
+    /// it in once registered with `setWebhook`. The webhook is not served without it
+    webhook_secret: Option<String>,
 }
 
+/// The header telegram sends the secret registered with the webhook in
+pub const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
 impl Default for Router {
     fn default() -> Self {
-        let command_pattern = Regex::new("/[A-Za-z_]+").unwrap();
-        Router { command_pattern }
+        Router::new(None, None)
     }
 }
 
 impl Router {
+    pub fn new(discord_public_key: Option<Vec<u8>>, webhook_secret: Option<String>) -> Self {
+        let command_pattern = Regex::new("/[A-Za-z_]+").unwrap();
+        Router {
+            command_pattern,
+            discord_public_key,
+            webhook_secret,
+        }
+    }
+
     pub fn route(
         &self,
         controller: &Controller,
         method: String,
         path: Uri,
-        _headers: Vec<(String, String)>,
+        headers: Vec<(String, String)>,
         body: Value,
+        raw_body: &[u8],
     ) -> Result<Option<Value>> {
-        let update = Update(body.clone());
-
-        let route = (
-            method.as_str(),
-            path.path(),
-            update.message_text(),
-            update.callback_data(),
-            update.game_short_name(),
-        );
-
-        match route {
-            ("POST", "/webhook", _, _, Ok(Some(_))) => {
-                let result = Self::handle_launch_game(controller, &update);
-                if let Err(err) = result {
-                    error!("Error launching game: {:?}", err);
-                }
-                Ok(None)
-            }
-            ("POST", "/webhook", Ok(Some(message_text)), _, _) => {
-                let result = self.handle_command(controller, &update, &message_text);
-                if let Err(err) = result {
-                    error!("Error handling command: {:?}", err);
-                }
-                Ok(None)
-            }
-            ("POST", "/webhook", _, Ok(Some(callback_data)), _) => {
-                let result = self.handle_callback(controller, &update, &callback_data);
-                if let Err(err) = result {
-                    error!("Error handling callback: {:?}", err);
-                }
+        let is_webhook = |path: &str| path == "/webhook" || path.starts_with("/webhook/");
+        match (method.as_str(), path.path()) {
+            ("POST", webhook) if is_webhook(webhook) => {
+                self.verify_webhook(webhook, &headers)?;
+                self.handle_update(controller, body);
                 Ok(None)
             }
-            ("GET", "/", _, _, _) => Ok(None),
-            ("GET", "/app", _, _, _) => self.handle_get_prompt(controller, path),
-            ("POST", "/app", _, _, _) => self.handle_post_prompt(controller, path, &body),
-            (_, _, Err(err), _, _) | (_, _, _, Err(err), _) | (_, _, _, _, Err(err)) => {
-                error!("Error parsing update: {:?}", err);
-                Ok(None)
+            ("POST", "/discord") => self.handle_interaction(controller, &headers, body, raw_body),
+            ("GET", "/") => Ok(None),
+            ("GET", "/app") => self.handle_get_prompt(controller, path),
+            ("POST", "/app") => self.handle_post_prompt(controller, path, &body),
+            (_, "/") => Err(ControllerError::ClientError(MethodNotAllowed("GET"))),
+            (_, "/app") => Err(ControllerError::ClientError(MethodNotAllowed("GET, POST"))),
+            (_, "/discord") => Err(ControllerError::ClientError(MethodNotAllowed("POST"))),
+            (_, webhook) if is_webhook(webhook) => {
+                Err(ControllerError::ClientError(MethodNotAllowed("POST")))
             }
-            (_, _, _, _, _) => Ok(None),
+            (_, _) => Err(ControllerError::ClientError(NotFound)),
         }
     }
 
-    fn handle_post_prompt(
+    /// Handles an update from telegram, whether sent to the webhook or polled for
+    pub fn handle_update(&self, controller: &Controller, update: Value) {
+        match Update(update).event() {
+            Ok(Some(event)) => self.handle_event(controller, event),
+            Ok(None) => {}
+            Err(err) => error!("Error parsing update: {:?}", err),
+        }
+    }
+
+    /// Rejects updates without the secret, and every update when none is configured
+    fn verify_webhook(&self, path: &str, headers: &[(String, String)]) -> Result<()> {
+        let given = [
+            path.strip_prefix("/webhook/"),
+            header(headers, SECRET_TOKEN_HEADER),
+        ];
+        let verified = match &self.webhook_secret {
+            None => false,
+            Some(secret) => given.iter().flatten().any(|given| {
+                constant_time::verify_slices_are_equal(given.as_bytes(), secret.as_bytes()).is_ok()
+            }),
+        };
+        if !verified {
+            info!("Rejected update without the webhook secret");
+            return Err(ControllerError::ClientError(Unauthorized));
+        }
+        Ok(())
+    }
+
+    fn handle_interaction(
         &self,
         controller: &Controller,
-        path: Uri,
-        body: &Value,
+        headers: &[(String, String)],
+        body: Value,
+        raw_body: &[u8],
     ) -> Result<Option<Value>> {
-        let path = match path.query() {
+        let public_key = match &self.discord_public_key {
             None => {
-                error!("Query params not valid");
-                Err(ControllerError::ClientError(InvalidQueryParams))
+                error!("Received a discord interaction without a public key configured");
+                return Err(ControllerError::ClientError(Unauthorized));
             }
-            Some(path) => Ok(path),
-        }?;
-        let params: Vec<(&str, &str)> = path
-            .split('&')
-            .map(|element| {
-                let res: Vec<_> = element.split('=').collect();
-                (res[0], res[1])
-            })
-            .collect();
-        let token = params.iter().find(|(name, _)| name.eq(&"token"));
-        let token = match token {
-            Some((_, token)) => Ok(token),
-            None => {
-                error!("Missing query param, token: {}", path);
-                Err(ControllerError::ClientError(InvalidQueryParams))
+            Some(public_key) => public_key,
+        };
+        let verified = match (
+            header(headers, "X-Signature-Ed25519"),
+            header(headers, "X-Signature-Timestamp"),
+        ) {
+            (Some(signature), Some(timestamp)) => {
+                interaction::verify(public_key, signature, timestamp, raw_body)
             }
-        }?;
+            _ => false,
+        };
+        if !verified {
+            info!("Rejected interaction with an invalid signature");
+            return Err(ControllerError::ClientError(Unauthorized));
+        }
 
-        let group_id = params.iter().find(|(name, _)| name.eq(&"group_id"));
-        let group_id = match group_id {
-            Some((_, group_id)) => Ok(group_id),
-            None => {
-                error!("Missing query param, group_id: {}", path);
-                Err(ControllerError::ClientError(InvalidQueryParams))
-            }
-        }?;
+        let interaction = Interaction(body);
+        if interaction.is_ping() {
+            return Ok(Some(Interaction::pong()));
+        }
 
-        let group_id = match group_id.parse::<i64>() {
-            Ok(group_id) => group_id,
+        match interaction.event() {
+            Ok(Some(event)) => {
+                let response = Interaction::response(&event);
+                self.handle_event(controller, event);
+                Ok(response)
+            }
+            Ok(None) => Ok(None),
             Err(err) => {
-                error!("Invalid query param, group_id: {}, {}", path, err);
-                return Err(ControllerError::ClientError(InvalidQueryParams));
+                error!("Error parsing interaction: {:?}", err);
+                Ok(None)
             }
+        }
+    }
+
+    fn handle_event(&self, controller: &Controller, event: Event) {
+        // Private and inline events are for the game in another chat group, which the controller
+        // locks
+        let _guard = match event {
+            Event::Answer { .. } => None,
+            _ => event
+                .chat_group()
+                .map(|chat_group| controller.lock(chat_group)),
         };
+        let argument = event.argument();
+        let result = match event {
+            Event::Command {
+                user,
+                chat_group,
+                text,
+            } => self.handle_command(controller, user, chat_group, &text, argument),
+            Event::Callback {
+                user,
+                chat_group,
+                callback,
+                data,
+            } => self.handle_callback(controller, user, chat_group, callback, &data, argument),
+            Event::LaunchGame {
+                user,
+                chat_group,
+                callback,
+            } => controller.launch_game(user.into(), chat_group, callback),
+            Event::Answer { user, text, .. } => controller.answer_privately(user, text),
+            Event::InlineQuery { user, id, text } => {
+                controller.inline_query(user.into(), &id, &text)
+            }
+            Event::InlineAnswer { user, token, text } => {
+                controller.answer_inline(user.into(), &token, text)
+            }
+            Event::InlineVote { user, choice } => controller.vote_inline(user, choice),
+        };
+        if let Err(err) = result {
+            error!("Error handling event: {:?}", err);
+        }
+    }
+
+    fn handle_post_prompt(
+        &self,
+        controller: &Controller,
+        path: Uri,
+        body: &Value,
+    ) -> Result<Option<Value>> {
+        let token = query_param(&path, "token")?;
+        let link = controller.verify_link(token)?;
 
         let answer = match body.get("answer") {
             None => Some(""),
@@ -130,59 +210,59 @@ impl Router {
             Some(value) => value,
         };
 
-        controller.post_prompt(token.to_string(), answer.to_string(), ChatGroup(group_id))?;
+        // The chat group the link was signed for, rather than one named in the query
+        let _guard = controller.lock(&link.chat_group);
+        controller.post_prompt(&link, answer.to_string())?;
         Ok(None)
     }
 
     fn handle_get_prompt(&self, controller: &Controller, path: Uri) -> Result<Option<Value>> {
-        let path = match path.query() {
-            None => {
-                error!("Query params not valid");
-                Err(ControllerError::ClientError(InvalidQueryParams))
-            }
-            Some(path) => Ok(path),
-        }?;
-        let mut params = path.split('&').map(|element| {
-            let res: Vec<_> = element.split('=').collect();
-            (res[0].to_string(), res[1].to_string())
-        });
-        let token = params.find(|(name, _)| name.eq("token"));
-        let token = match token {
-            Some((_, token)) => Ok(token),
-            None => {
-                error!("Missing query param, token: {}", path);
-                Err(ControllerError::ClientError(InvalidQueryParams))
-            }
-        }?;
-
-        let question = controller.get_prompt(token)?;
+        let token = query_param(&path, "token")?;
+        let link = controller.verify_link(token)?;
+        let question = controller.get_prompt(&link)?;
         Ok(Some(json!({ "question": question })))
     }
 
     fn handle_command(
         &self,
         controller: &Controller,
-        update: &Update,
+        user: FullUser,
+        chat_group: ChatGroup,
         message_text: &str,
+        argument: Option<String>,
     ) -> Result<()> {
         let command = self.command_pattern.find(message_text);
         if let Some(command) = command {
             info!("command: {:?}", command.as_str());
-            self.route_command(controller, &update, command.as_str())
+            Self::route_command(controller, user, chat_group, command.as_str(), argument)
         } else {
             error!("Failed to match command: {}", message_text);
             Ok(())
         }
     }
 
-    fn route_command(&self, controller: &Controller, update: &Update, command: &str) -> Result<()> {
+    fn route_command(
+        controller: &Controller,
+        user: FullUser,
+        chat_group: ChatGroup,
+        command: &str,
+        argument: Option<String>,
+    ) -> Result<()> {
         match command {
-            "/top" => controller.top_scores(),
-            "/start" => controller.start(update.chat_group()?),
-            "/new" => controller.new_game(update.user()?, update.chat_group()?),
-            "/begin" => controller.begin_game(update.chat_group()?),
-            "/status" => controller.status(update.chat_group()?),
-            "/end" => controller.end(update.chat_group()?),
+            "/top" => controller.top_scores(chat_group, argument.as_deref() == Some("global")),
+            "/start" => controller.start(user, chat_group),
+            "/new" => controller.new_game(user, chat_group, argument.as_deref() != Some("notimer")),
+            "/begin" => controller.begin_game(user.into(), chat_group),
+            "/status" => controller.status(chat_group),
+            "/end" => controller.end(user.into(), chat_group),
+            "/leave" => controller.leave(user, chat_group),
+            "/kick" => controller.kick(user.into(), chat_group, argument),
+            "/permission" => controller.permission(user.into(), chat_group, argument),
+            "/audience" => controller.audience(user.into(), chat_group, argument),
+            "/latejoin" => controller.late_join(user.into(), chat_group, argument),
+            "/packs" => controller.packs(chat_group),
+            "/pack" => controller.pack(user.into(), chat_group, argument),
+            "/suggest" => controller.suggest(user, chat_group, argument),
             _ => {
                 error!("Unexpected command: {}", command);
                 Ok(())
@@ -194,13 +274,23 @@ impl Router {
     fn handle_callback(
         &self,
         controller: &Controller,
-        update: &Update,
+        user: FullUser,
+        chat_group: ChatGroup,
+        callback: Callback,
         callback_data: &str,
+        argument: Option<String>,
     ) -> Result<()> {
-        let command = self.command_pattern.find(&callback_data);
+        let command = self.command_pattern.find(callback_data);
         if let Some(command) = command {
             info!("callback: {:?}", command.as_str());
-            self.route_callback(controller, &update, command.as_str())
+            Self::route_callback(
+                controller,
+                user,
+                chat_group,
+                callback,
+                command.as_str(),
+                argument,
+            )
         } else {
             error!("Failed to match callback: {}", callback_data);
             Ok(())
@@ -208,34 +298,56 @@ impl Router {
     }
 
     fn route_callback(
-        &self,
         controller: &Controller,
-        update: &Update,
+        user: FullUser,
+        chat_group: ChatGroup,
+        callback: Callback,
         command: &str,
+        argument: Option<String>,
     ) -> Result<()> {
-        match command {
-            "/join_callback" => {
-                controller.join_game(update.user()?, update.chat_group()?, update.callback()?)
+        match (command, argument) {
+            ("/join_callback", _) => controller.join_game(user, chat_group, callback),
+            ("/vote_callback", Some(token)) => {
+                controller.vote(user, Choice { token }, chat_group, callback)
             }
-            "/vote_callback" => controller.vote(
-                update.user()?.into(),
-                update.choice()?,
-                update.chat_group()?,
-                update.callback()?,
-            ),
-            _ => {
+            ("/approve_callback", Some(id)) => {
+                controller.review_suggestion(user.into(), chat_group, callback, &id, true)
+            }
+            ("/reject_callback", Some(id)) => {
+                controller.review_suggestion(user.into(), chat_group, callback, &id, false)
+            }
+            (_, _) => {
                 error!("Unexpected callback: {}", command);
                 Ok(())
             }
         }?;
         Ok(())
     }
+}
 
-    fn handle_launch_game(controller: &Controller, update: &Update) -> Result<()> {
-        controller.launch_game(
-            update.user()?.into(),
-            update.chat_group()?,
-            update.callback()?,
-        )
+/// The value of the query parameter, rejecting queries that are not made of `name=value` pairs
+fn query_param<'u>(path: &'u Uri, name: &str) -> Result<&'u str> {
+    let query = path.query().unwrap_or("");
+    let params = query
+        .split('&')
+        .map(|param| param.split_once('='))
+        .collect::<Option<Vec<_>>>();
+    match params
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(param, _)| *param == name)
+    {
+        Some((_, value)) => Ok(value),
+        None => {
+            error!("Missing or invalid query param, {}: {}", name, query);
+            Err(ControllerError::ClientError(InvalidQueryParams))
+        }
     }
 }
+
+fn header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
@@ -0,0 +1,48 @@
+use log::{error, info};
+
+use crate::chat;
+use crate::config::Config;
+use crate::controller::link::Links;
+use crate::controller::lock::ChatLocks;
+use crate::controller::Controller;
+use crate::persistence::{self, Daos};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Starts a thread that periodically checks the timers of running games, taking turns with the
+/// workers through `locks`
+pub fn spawn(config: Config, locks: Arc<ChatLocks>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let connection = persistence::Connection::new(&config.storage)
+            .map_err(|err| {
+                error!("Database connection error: {}", err);
+                err
+            })
+            .expect("Failed to start scheduler due to db connection error");
+
+        let chat_client = chat::new_client(&config.chat_backend).expect("Failed to create client");
+
+        let controller = Controller::new(
+            Daos::new(&connection),
+            chat_client,
+            locks,
+            Links::new(&config.app_url, &config.app_secret),
+            config.timer_enabled,
+            &config.rounds,
+        );
+
+        info!(
+            "Scheduler started, checking every {}s",
+            config.timer_interval
+        );
+
+        loop {
+            thread::sleep(Duration::from_secs(config.timer_interval));
+
+            if let Err(err) = controller.check_timers(&config.timeouts) {
+                error!("Failed to check timers: {:?}", err);
+            }
+        }
+    })
+}
@@ -1,11 +1,14 @@
 use log::error;
 use log::info;
 
-use crate::chat::telegram::Telegram;
-use crate::config::Config;
+use crate::chat;
+use crate::config::{ChatBackend, Config};
+use crate::controller::link::Links;
+use crate::controller::lock::ChatLocks;
 use crate::controller::Controller;
 use crate::handler::DefaultHandler;
 use crate::http::server::Server;
+use crate::persistence::{self, Daos};
 use crate::router::Router;
 use std::net::TcpStream;
 use std::sync::mpsc;
@@ -33,12 +36,13 @@ enum Message {
 impl ThreadPool {
     /// Create a new ThreadPool.
     ///
-    /// The size is the number of threads in the pool.
+    /// The size is the number of threads in the pool, which take turns handling updates to the
+    /// same chat group through `locks`.
     ///
     /// # Panics
     ///
     /// The `new` function will panic if the size is zero.
-    pub fn new(size: usize, config: Config) -> ThreadPool {
+    pub fn new(size: usize, config: Config, locks: Arc<ChatLocks>) -> ThreadPool {
         assert!(size > 0);
 
         let (sender, receiver) = mpsc::channel();
@@ -48,7 +52,12 @@ impl ThreadPool {
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            let worker = Worker::new(id, Arc::clone(&receiver), config.clone());
+            let worker = Worker::new(
+                id,
+                Arc::clone(&receiver),
+                config.clone(),
+                Arc::clone(&locks),
+            );
             workers.push(worker);
         }
 
@@ -89,24 +98,39 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>, config: Config) -> Worker {
+    fn new(
+        id: usize,
+        receiver: Arc<Mutex<mpsc::Receiver<Message>>>,
+        config: Config,
+        locks: Arc<ChatLocks>,
+    ) -> Worker {
         let thread = thread::spawn(move || {
-            let connection = libpq::Connection::new(&config.db_dsn)
+            let connection = persistence::Connection::new(&config.storage)
                 .map_err(|err| {
                     error!("Database connection error: {}", err);
                     err
                 })
                 .expect("Failed to start due to db connection error");
 
-            let chat_client = Telegram::new(
-                &config.telegram_hostname,
-                &config.telegram_token,
-                &config.telegram_gamename,
-            )
-            .expect("Failed to create client");
-
-            let controller = Controller::new(&connection, Box::new(chat_client), &config.app_url);
-            let router = Router::default();
+            let chat_client =
+                chat::new_client(&config.chat_backend).expect("Failed to create client");
+
+            let controller = Controller::new(
+                Daos::new(&connection),
+                chat_client,
+                locks,
+                Links::new(&config.app_url, &config.app_secret),
+                config.timer_enabled,
+                &config.rounds,
+            );
+            let router = match &config.chat_backend {
+                ChatBackend::Discord { public_key, .. } => {
+                    Router::new(Some(public_key.clone()), None)
+                }
+                ChatBackend::Telegram { webhook_secret, .. } => {
+                    Router::new(None, Some(webhook_secret.clone()))
+                }
+            };
             let handler = DefaultHandler::new(controller, router);
 
             let server = Server::new(&handler);
@@ -122,7 +146,7 @@ impl Worker {
                     .unwrap();
 
                 match message {
-                    Message::NewJob(stream) => server.handle_connection(stream).unwrap(),
+                    Message::NewJob(stream) => server.handle_connection(stream),
                     Message::Terminate => {
                         info!("Worker {} was told to terminate.", id);
 